@@ -1,18 +1,32 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
 use async_std::task;
+use chrono::{Datelike, DateTime, Duration as ChronoDuration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike};
 use rand::Rng;
+use rand::seq::SliceRandom;
 use regex::Regex;
 use serenity::client::Context;
-use serenity::model::channel::{Message, ReactionType};
-use serenity::model::guild::{GuildContainer, Guild};
-use serenity::model::user::User;
-use serenity::prelude::TypeMap;
-use serenity::utils::MessageBuilder;
-use tokio::sync::RwLockWriteGuard;
+use serenity::builder::CreateEmbed;
+use serenity::http::{AttachmentType, Http};
+use serenity::model::channel::{ChannelType, Message, PermissionOverwrite, PermissionOverwriteType, Reaction, ReactionType};
+use serenity::model::event::PresenceUpdateEvent;
+use serenity::model::id::{ChannelId, GuildId, MessageId, RoleId, UserId};
+use serenity::model::permissions::Permissions;
+use serenity::model::user::{OnlineStatus, User};
+use serenity::model::voice::VoiceState;
+use serenity::prelude::{RwLock, TypeMap};
+use serenity::utils::{parse_username, MessageBuilder};
+use tokio_util::sync::CancellationToken;
 
-use crate::{BotState, Config, Draft, Maps, QueueMessages, RiotIdCache, State, StateContainer, TeamNameCache, UserQueue};
+use crate::{cron, delivery, i18n, ops, web, webhooks};
+use crate::messenger::{Discord, Guild, Messenger, Outbox};
+use crate::error::{BotError, BotResult};
+use crate::{read_config, read_config_file, read_config_overrides, lowercase_aliases, resolve_alias, set_config_value, CONFIG_OVERRIDES, SECRET_CONFIG_KEYS, ActiveAfkCheck, ActiveMatch, ActiveVotes, ActiveMatchInfo, AfkCheck, Backup, BotState, CaptainPool, Captains, Command, CommandAliases, ChannelRenames, CommendsGiven, DisabledConfigIds, OriginalChannelNames, Config, CurrentMatch, Draft, LastMatch, LastQueuePing, MapImages, MapPools, Match, MatchHistory, MatchResult, Maps, NotifySubscriptions, OfflineSince, Parties, PendingSwap, PlayerStats, PlayerStatsCache, PriorityPlayers, QueueActivity, QueueActivityInfo, QueueEvent, QueueEventKind, QueueEvents, QueueEventsUnsaved, QueueSessionState, QueueExpiry, QueueJoinTimes, QueueLock, QueueMessages, QueueStatusMessage, RegionCache, RiotAccounts, RiotIdCache, ScheduledScrim, ScheduledScrims, Session, SetupCancel, State, StateContainer, StatusMessage, Spectators, Strike, SubRequest, SubRequests, SwapRequest, Team, TeamNameCache, TeamProfile, Turn, UserQueue, Vote, Waitlist};
 
 struct ReactionResult {
     count: u64,
@@ -20,804 +34,5530 @@ struct ReactionResult {
 }
 
 pub(crate) async fn handle_join(context: &Context, msg: &Message, author: &User) {
+    let outbox = Outbox::default();
+    join_queue(context, &outbox, msg, author).await;
+    outbox.send(context).await;
+}
+
+async fn join_queue(context: &Context, outbox: &Outbox, msg: &Message, author: &User) {
     let mut data = context.data.write().await;
-    let riot_id_cache: &HashMap<u64, String> = &data.get::<RiotIdCache>().unwrap();
+    let riot_id_cache: &HashMap<u64, String> = data.get::<RiotIdCache>().unwrap();
     if !riot_id_cache.contains_key(author.id.as_u64()) {
         let response = MessageBuilder::new()
             .mention(author)
             .push(" riotid not found for your discord user, \
                     please use `.riotid <your riotid>` to assign one. Example: `.riotid Martige#NA1`")
             .build();
-        if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-            eprintln!("Error sending message: {:?}", why);
-        }
+        outbox.say(msg.channel_id, response).await;
         return;
     }
-    let user_queue: &mut Vec<User> = &mut data.get_mut::<UserQueue>().unwrap();
-    if user_queue.contains(&author) {
-        let response = MessageBuilder::new()
-            .mention(author)
-            .push(" is already in the queue.")
-            .build();
-        if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-            eprintln!("Error sending message: {:?}", why);
+    let quote_regex = Regex::new("[\"”“](.*?)[\"”“]").unwrap();
+    let join_duration = if author == &msg.author {
+        parse_join_duration(&quote_regex.replace_all(&msg.content, ""))
+    } else {
+        None
+    };
+    if let Some(duration) = join_duration {
+        if duration < ChronoDuration::minutes(1) || duration > ChronoDuration::hours(24) {
+            announce_tagged(outbox, msg.channel_id, " the join duration must be between 1 minute and 24 hours. Example: `.join 90m` or `.join 2h`", author).await;
+            return;
         }
-        return;
     }
-    if user_queue.len() >= 10 {
-        let response = MessageBuilder::new()
-            .mention(author)
-            .push(" sorry but the queue is full.")
-            .build();
-        if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-            eprintln!("Error sending message: {:?}", why);
+    if add_to_queue(context, outbox, &mut data, msg.channel_id, msg.guild_id, author).await {
+        if author == &msg.author {
+            if let Some(note) = parse_join_note(&msg.content) {
+                let queued_msgs: &mut HashMap<u64, String> = data.get_mut::<QueueMessages>().unwrap();
+                queued_msgs.insert(*author.id.as_u64(), note);
+            }
+        }
+        if let Some(duration) = join_duration {
+            let expires = Local::now() + duration;
+            let queue_expiry: &mut HashMap<u64, DateTime<Local>> = data.get_mut::<QueueExpiry>().unwrap();
+            queue_expiry.insert(*author.id.as_u64(), expires);
+            announce_tagged(outbox, msg.channel_id, &format!(" will be removed from the queue at {}", expires.format("%H:%M")), author).await;
+            let context = context.clone();
+            let channel_id = msg.channel_id;
+            let guild_id = msg.guild_id;
+            let user = author.clone();
+            tokio::spawn(async move {
+                task::sleep(Duration::from_millis(duration.num_milliseconds() as u64)).await;
+                expire_queue_join(&context, channel_id, guild_id, &user, expires).await;
+            });
         }
-        return;
-    }
-    user_queue.push(author.clone());
-    let response = MessageBuilder::new()
-        .mention(author)
-        .push(" has been added to the queue. Queue size: ")
-        .push(user_queue.len().to_string())
-        .push("/10")
-        .build();
-    if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-        eprintln!("Error sending message: {:?}", why);
     }
-    let queued_msgs: &mut HashMap<u64, String> = data.get_mut::<QueueMessages>().unwrap();
+}
+
+/// Parses the note attached to a `.join` message, either in quotes or as plain text i.e. `.join need to leave by 10pm EST`
+pub(crate) fn parse_join_note(content: &str) -> Option<String> {
     let quote_regex = Regex::new("[\"”“](.*?)[\"”“]").unwrap();
-    if let Some(mat) = quote_regex.find(&msg.content) {
-        let start = mat.start();
-        let mut end = mat.end();
-        end = end.min(start + 50);
-        queued_msgs.insert(*msg.author.id.as_u64(), String::from(msg.content[start..end].trim()));
-    }
-    let config: &Config = data.get::<Config>().unwrap();
-    if let Some(role_id) = config.discord.assign_role_id {
-        if let Ok(value) = msg.author.has_role(&context.http, msg.guild_id.unwrap(), role_id).await {
-            if !value {
-                let guild = Guild::get(&context.http, msg.guild_id.unwrap()).await.unwrap();
-                if let Ok(mut member) = guild.member(&context.http, msg.author.id).await {
-                    if let Err(err) = member.add_role(&context.http, role_id).await {
-                        eprintln!("assign_role_id exists but cannot add role to user, check bot permissions");
-                        eprintln!("{:?}", err);
-                    }
-                }
-            }
-        }
+    let note: String = if let Some(captures) = quote_regex.captures(content) {
+        String::from(captures[1].trim())
+    } else {
+        let mention_regex = Regex::new("^<@[!&]?\\d+>$").unwrap();
+        let skipped = if parse_join_duration(content).is_some() { 2 } else { 1 };
+        content.split_whitespace()
+            .skip(skipped)
+            .filter(|arg| !mention_regex.is_match(arg))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    if note.is_empty() {
+        None
+    } else {
+        Some(note.chars().take(50).collect())
     }
 }
 
-pub(crate) async fn handle_leave(context: Context, msg: Message) {
+/// Parses an availability window from the first argument of a `.join` message i.e. `.join 90m` or `.join 2h need to leave early`
+pub(crate) fn parse_join_duration(content: &str) -> Option<ChronoDuration> {
+    let duration_regex = Regex::new("^(\\d+)([mhMH])$").unwrap();
+    let captures = duration_regex.captures(content.split_whitespace().nth(1)?)?;
+    let amount: u32 = captures[1].parse().ok()?;
+    Some(if captures[2].eq_ignore_ascii_case("h") {
+        ChronoDuration::hours(amount as i64)
+    } else {
+        ChronoDuration::minutes(amount as i64)
+    })
+}
+
+pub(crate) async fn expire_queue_join(context: &Context, channel_id: ChannelId, guild_id: Option<GuildId>, user: &User, expires: DateTime<Local>) {
+    let outbox = Outbox::default();
     let mut data = context.data.write().await;
-    let state: &mut StateContainer = data.get_mut::<BotState>().unwrap();
-    if state.state != State::Queue {
-        send_simple_tagged_msg(&context, &msg, " cannot `.leave` the queue after `.start`, use `.cancel` to start over if needed.", &msg.author).await;
-        return;
-    }
-    let user_queue: &mut Vec<User> = data.get_mut::<UserQueue>().unwrap();
-    if !user_queue.contains(&msg.author) {
-        let response = MessageBuilder::new()
-            .mention(&msg.author)
-            .push(" is not in the queue. Type `.join` to join the queue.")
-            .build();
-        if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-            eprintln!("Error sending message: {:?}", why);
-        }
+    let queue_expiry: &HashMap<u64, DateTime<Local>> = data.get::<QueueExpiry>().unwrap();
+    if queue_expiry.get(user.id.as_u64()) != Some(&expires) { return; }
+    if data.get::<BotState>().unwrap().state != State::Queue {
+        let queue_expiry: &mut HashMap<u64, DateTime<Local>> = data.get_mut::<QueueExpiry>().unwrap();
+        queue_expiry.remove(user.id.as_u64());
         return;
     }
-    let index = user_queue.iter().position(|r| r.id == msg.author.id).unwrap();
-    user_queue.remove(index);
+    let queue_len = remove_from_queue(&mut data, std::slice::from_ref(user));
     let response = MessageBuilder::new()
-        .mention(&msg.author)
-        .push(" has left the queue. Queue size: ")
-        .push(user_queue.len().to_string())
-        .push("/10")
+        .mention(user)
+        .push("'s availability window has ended, removed from the queue")
+        .push(queue_size_note(&data, queue_len))
         .build();
-    if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-        eprintln!("Error sending message: {:?}", why);
+    outbox.say(channel_id, response).await;
+    promote_waitlist(&outbox, channel_id, guild_id, &mut data).await;
+    drop(data);
+    outbox.send(context).await;
+}
+
+/// Where a `.join` put the player, the waitlist positions start at 0
+#[derive(Debug, PartialEq)]
+enum QueueJoin {
+    Queued(usize),
+    AlreadyQueued,
+    Waitlisted(usize),
+    AlreadyWaitlisted(usize),
+}
+
+/// Adds the player to the queue, or to the waitlist once the queue is full
+fn enqueue(data: &mut TypeMap, author: &User) -> QueueJoin {
+    let full_size = queue_size(data);
+    let user_queue: &mut Vec<User> = data.get_mut::<UserQueue>().unwrap();
+    if user_queue.contains(author) {
+        return QueueJoin::AlreadyQueued;
     }
-    let queued_msgs: &mut HashMap<u64, String> = data.get_mut::<QueueMessages>().unwrap();
-    if queued_msgs.get(&msg.author.id.as_u64()).is_some() {
-        queued_msgs.remove(&msg.author.id.as_u64());
+    if user_queue.len() >= full_size {
+        let waitlist: &Vec<User> = data.get::<Waitlist>().unwrap();
+        if let Some(index) = waitlist.iter().position(|u| u == author) {
+            return QueueJoin::AlreadyWaitlisted(index);
+        }
+        let priority_players: &Vec<u64> = data.get::<PriorityPlayers>().unwrap();
+        // players left out of the last match skip ahead of everyone without priority
+        let position = if priority_players.contains(author.id.as_u64()) {
+            waitlist.iter().take_while(|u| priority_players.contains(u.id.as_u64())).count()
+        } else {
+            waitlist.len()
+        };
+        data.get_mut::<Waitlist>().unwrap().insert(position, author.clone());
+        return QueueJoin::Waitlisted(position);
     }
+    user_queue.push(author.clone());
+    let queue_len = user_queue.len();
+    let queue_join_times: &mut HashMap<u64, DateTime<Local>> = data.get_mut::<QueueJoinTimes>().unwrap();
+    queue_join_times.insert(*author.id.as_u64(), Local::now());
+    record_queue_event(data, QueueEventKind::Join, Some(*author.id.as_u64()));
+    QueueJoin::Queued(queue_len)
 }
 
-pub(crate) async fn handle_list(context: Context, msg: Message) {
-    let data = context.data.write().await;
-    let user_queue: &Vec<User> = data.get::<UserQueue>().unwrap();
-    let queue_msgs: &HashMap<u64, String> = data.get::<QueueMessages>().unwrap();
-    let mut user_name = String::new();
-    for u in user_queue {
-        user_name.push_str(format!("\n- @{}", u.name).as_str());
-        if let Some(value) = queue_msgs.get(u.id.as_u64()) {
-            user_name.push_str(format!(": `{}`", value).as_str());
+/// Adds the user to the queue, or to the waitlist if the queue is full. Returns true if the user was added to the queue
+pub(crate) async fn add_to_queue(context: &Context, outbox: &Outbox, data: &mut TypeMap, channel_id: ChannelId, guild_id: Option<GuildId>, author: &User) -> bool {
+    let queue_len = match enqueue(data, author) {
+        QueueJoin::Queued(queue_len) => queue_len,
+        QueueJoin::AlreadyQueued => {
+            announce_tagged(outbox, channel_id, " is already in the queue.", author).await;
+            return false;
         }
-    }
-    let response = MessageBuilder::new()
-        .push("Current queue size: ")
-        .push(&user_queue.len())
-        .push("/10")
-        .push(user_name)
-        .build();
+        QueueJoin::AlreadyWaitlisted(index) => {
+            announce_tagged(outbox, channel_id, &format!(" is already on the waitlist. Position: {}", index + 1), author).await;
+            return false;
+        }
+        QueueJoin::Waitlisted(position) => {
+            announce_tagged(outbox, channel_id, &format!(" the queue is full, you have been added to the waitlist. Position: {}", position + 1), author).await;
+            return false;
+        }
+    };
+    announce_tagged(outbox, channel_id, &format!(" has been added to the queue{}", queue_size_note(data, queue_len)), author).await;
+    audit_log(outbox, data, "Join", format!("<@{}> joined the queue ({}/{})", author.id, queue_len, queue_size(data))).await;
+    update_queue_activity(data, channel_id, guild_id);
+    check_queue_thresholds(context, outbox, data, channel_id, guild_id, queue_len - 1, queue_len).await;
+    assign_queue_role(outbox, guild_id, data, author);
+    true
+}
 
-    if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-        eprintln!("Error sending message: {:?}", why);
+pub(crate) async fn handle_join_command(context: Context, msg: Message) -> BotResult {
+    let party_members: Vec<User> = msg.mentions
+        .iter()
+        .filter(|user| !user.bot && user.id != msg.author.id)
+        .cloned()
+        .collect();
+    let joining: Vec<&User> = std::iter::once(&msg.author).chain(party_members.iter()).collect();
+    queue_join_check(&context, msg.guild_id, &joining).await?;
+    if party_members.is_empty() {
+        handle_join(&context, &msg, &msg.author).await;
+    } else {
+        handle_party_join(&context, &msg, party_members).await;
     }
+    Ok(())
 }
 
-pub(crate) async fn handle_clear(context: Context, msg: Message) {
-    if !admin_check(&context, &msg, true).await { return; }
-    let mut data = context.data.write().await;
-    let user_queue: &mut Vec<User> = &mut data.get_mut::<UserQueue>().unwrap();
-    user_queue.clear();
-    let response = MessageBuilder::new()
-        .mention(&msg.author)
-        .push(" cleared queue")
-        .build();
-    if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-        eprintln!("Error sending message: {:?}", why);
+/// Checks the queue window, queue lock, cooldowns and lobby channel before anyone joins the queue
+pub(crate) async fn queue_join_check(context: &Context, guild_id: Option<GuildId>, users: &[&User]) -> BotResult {
+    if let Some(opens) = queue_closed_until(context.data.read().await.get::<Config>().unwrap()) {
+        return Err(BotError::Usage(format!(" the queue is closed right now, it opens at {}", opens.format("%H:%M"))));
+    }
+    let queue_lock = context.data.read().await.get::<QueueLock>().unwrap().clone();
+    if let Some(reason) = queue_lock {
+        return Err(BotError::Usage(format!(" the queue is currently locked: {}", reason)));
+    }
+    {
+        let data = context.data.read().await;
+        let on_cooldown: Vec<String> = users.iter()
+            .filter_map(|user| queue_cooldown(&data, user).map(|until| format!(" <@{}> (until {})", user.id, until.format("%H:%M"))))
+            .collect();
+        if !on_cooldown.is_empty() {
+            return Err(BotError::Usage(format!(" cannot join the queue while on a queue cooldown:{}", on_cooldown.join(","))));
+        }
+    }
+    let lobby_channel_id = {
+        let data = context.data.read().await;
+        enabled_id(&data, data.get::<Config>().unwrap().discord.lobby_channel_id)
+    };
+    if let Some(lobby_channel_id) = lobby_channel_id {
+        let mut not_in_lobby: Vec<&User> = Vec::new();
+        for user in users {
+            if !in_voice_channel(context, guild_id, user, lobby_channel_id).await {
+                not_in_lobby.push(user);
+            }
+        }
+        if !not_in_lobby.is_empty() {
+            let not_in_lobby_mention: String = not_in_lobby.iter().map(|user| format!(" <@{}>", user.id)).collect();
+            return Err(BotError::Usage(format!(" you must be in the <#{}> voice channel to join the queue. Not in the channel:{}",
+                                               lobby_channel_id, not_in_lobby_mention)));
+        }
     }
+    Ok(())
 }
 
-pub(crate) async fn handle_help(context: Context, msg: Message) {
-    let mut commands = String::from("
-`.join` - Join the queue, add a message in quotes (max 50 char) i.e. `.join \"available at 9pm\"`
-`.leave` - Leave the queue
-`.list` - List all users in the queue
-`.riotid` - Set your riotid i.e. `.riotid Martige#NA1`
-`.maps` - Lists all maps available for map vote
-`.teamname` - Sets a custom team name when you are a captain i.e. `.teamname Your Team Name`
-_These are commands used during the `.start` process:_
-`.captain` - Add yourself as a captain.
-`.pick` - If you are a captain, this is used to pick a player by tagging them i.e. `.pick @Martige`
-");
-    let admin_commands = String::from("
-_These are privileged admin commands:_
-`.start` - Start the match setup process
-`.kick` - Kick a player by mentioning them i.e. `.kick @user`
-`.addmap` - Add a map to the map vote i.e. `.addmap mapname`
-`.removemap` - Remove a map from the map vote i.e. `.removemap mapname`
-`.recoverqueue` - Manually set a queue, tag all users to add after the command
-`.clear` - Clear the queue
-`.cancel` - Cancels `.start` process & retains current queue
-    ");
-    if admin_check(&context, &msg, false).await {
-        commands.push_str(&admin_commands)
+/// Joins or leaves the queue when someone reacts ✅ on the pinned queue status message
+pub(crate) async fn handle_queue_reaction(context: &Context, reaction: &Reaction, added: bool) {
+    if reaction.emoji != ReactionType::Unicode(String::from("✅")) { return; }
+    let user_id = match reaction.user_id {
+        Some(user_id) => user_id,
+        None => return,
+    };
+    if user_id == context.cache.current_user_id().await { return; }
+    {
+        let data = context.data.read().await;
+        if data.get::<Config>().unwrap().reaction_join != Some(true) { return; }
+        let status_message: &Option<StatusMessage> = data.get::<QueueStatusMessage>().unwrap();
+        if status_message.as_ref().map(|status| status.message_id) != Some(*reaction.message_id.as_u64()) { return; }
     }
-    let response = MessageBuilder::new()
-        .push(commands)
-        .build();
-    if let Ok(channel) = &msg.author.create_dm_channel(&context.http).await {
-        if let Err(why) = channel.say(&context.http, &response).await {
-            eprintln!("Error sending message: {:?}", why);
+    let user = match user_id.to_user(&context.http).await {
+        Ok(user) if !user.bot => user,
+        _ => return,
+    };
+    if added {
+        if let Err(BotError::Usage(text)) = reaction_join(context, reaction, &user).await {
+            let response = MessageBuilder::new()
+                .mention(&user)
+                .push(i18n::translate(&text))
+                .build();
+            delivery::say(&context.http, reaction.channel_id, &response).await;
+            // the reaction is removed so it keeps matching who is queued, the remove event finds nobody to take out
+            if let Err(why) = reaction.delete(&context.http).await {
+                eprintln!("Error removing reaction: {:?}", why);
+            }
         }
     } else {
-        eprintln!("Error sending .help dm");
+        reaction_leave(context, reaction, &user).await;
     }
 }
 
-pub(crate) async fn handle_recover_queue(context: Context, msg: Message) {
-    if !admin_check(&context, &msg, true).await { return; }
-    {
-        let mut data = context.data.write().await;
-        let user_queue: &mut Vec<User> = &mut data.get_mut::<UserQueue>().unwrap();
-        user_queue.clear();
+async fn reaction_join(context: &Context, reaction: &Reaction, user: &User) -> BotResult {
+    queue_join_check(context, reaction.guild_id, &[user]).await?;
+    let mut data = context.data.write().await;
+    if !data.get::<RiotIdCache>().unwrap().contains_key(user.id.as_u64()) {
+        return Err(BotError::Usage(String::from(" riotid not found for your discord user, \
+                    please use `.riotid <your riotid>` to assign one. Example: `.riotid Martige#NA1`")));
     }
-    for mention in &msg.mentions {
-        handle_join(&context, &msg, &mention).await
+    let outbox = Outbox::default();
+    let added = add_to_queue(context, &outbox, &mut data, reaction.channel_id, reaction.guild_id, user).await;
+    drop(data);
+    outbox.send(context).await;
+    if !added {
+        return Err(BotError::Usage(String::from(" react again once you're out of the queue or waitlist.")));
     }
+    Ok(())
 }
 
-pub(crate) async fn handle_start(context: Context, msg: Message) {
-    let admin_check = admin_check(&context, &msg, true).await;
-    if !admin_check { return; }
+async fn reaction_leave(context: &Context, reaction: &Reaction, user: &User) {
+    let outbox = Outbox::default();
     let mut data = context.data.write().await;
-    let bot_state: &StateContainer = data.get::<BotState>().unwrap();
-    if bot_state.state != State::Queue {
-        send_simple_tagged_msg(&context, &msg, " `.start` command has already been entered", &msg.author).await;
+    let waitlist: &mut Vec<User> = data.get_mut::<Waitlist>().unwrap();
+    if let Some(index) = waitlist.iter().position(|u| u == user) {
+        waitlist.remove(index);
+        audit_log(&outbox, &data, "Leave", format!("<@{}> left the waitlist", user.id)).await;
+        drop(data);
+        outbox.send(context).await;
         return;
     }
-    let user_queue: &mut Vec<User> = data.get_mut::<UserQueue>().unwrap();
-    if !user_queue.contains(&msg.author) && !admin_check {
-        send_simple_tagged_msg(&context, &msg, " non-admin users that are not in the queue cannot start the match", &msg.author).await;
-        return;
-    }
-    if user_queue.len() != 10 {
+    // removing a reaction after `.start` or once already out of the queue does nothing
+    if data.get::<BotState>().unwrap().state != State::Queue || !data.get::<UserQueue>().unwrap().contains(user) { return; }
+    let queue_len = remove_from_queue(&mut data, std::slice::from_ref(user));
+    let response = MessageBuilder::new()
+        .mention(user)
+        .push(" has left the queue")
+        .push(queue_size_note(&data, queue_len))
+        .build();
+    outbox.say(reaction.channel_id, response).await;
+    audit_log(&outbox, &data, "Leave", format!("<@{}> left the queue ({}/{})", user.id, queue_len, queue_size(&data))).await;
+    update_queue_activity(&mut data, reaction.channel_id, reaction.guild_id);
+    promote_waitlist(&outbox, reaction.channel_id, reaction.guild_id, &mut data).await;
+    drop(data);
+    outbox.send(context).await;
+}
+
+pub(crate) async fn in_voice_channel(context: &Context, guild_id: Option<GuildId>, user: &User, channel_id: u64) -> bool {
+    let guild_id = match guild_id {
+        Some(guild_id) => guild_id,
+        None => return false,
+    };
+    context.cache.guild_field(guild_id, |guild| {
+        guild.voice_states
+            .get(&user.id)
+            .and_then(|voice_state| voice_state.channel_id)
+            .map(|voice_channel_id| *voice_channel_id.as_u64() == channel_id)
+            .unwrap_or(false)
+    }).await.unwrap_or(false)
+}
+
+pub(crate) async fn lobby_auto_join(context: &Context, outbox: &Outbox, data: &mut TypeMap, voice_state: &VoiceState, user: User) {
+    if user.bot { return; }
+    if data.get::<QueueLock>().unwrap().is_some() { return; }
+    if queue_closed_until(data.get::<Config>().unwrap()).is_some() { return; }
+    if queue_cooldown(data, &user).is_some() { return; }
+    if data.get::<UserQueue>().unwrap().contains(&user) || data.get::<Waitlist>().unwrap().contains(&user) { return; }
+    let channel_id = match queue_channel(data) {
+        Some(channel_id) => channel_id,
+        None => return,
+    };
+    let riot_id_cache: &HashMap<u64, String> = data.get::<RiotIdCache>().unwrap();
+    if !riot_id_cache.contains_key(user.id.as_u64()) {
         let response = MessageBuilder::new()
-            .mention(&msg.author)
-            .push(" the queue is not full yet")
+            .mention(&user)
+            .push(" riotid not found for your discord user, \
+                    please use `.riotid <your riotid>` to assign one before joining the lobby. Example: `.riotid Martige#NA1`")
             .build();
-        if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-            eprintln!("Error sending message: {:?}", why);
+        outbox.say(channel_id, response).await;
+        return;
+    }
+    add_to_queue(context, outbox, data, channel_id, voice_state.guild_id, &user).await;
+}
+
+pub(crate) async fn handle_voice_state_update(context: Context, voice_state: VoiceState) {
+    // most voice updates have nothing to do with the queue, those are turned away under the read lock
+    let joined_lobby = {
+        let data = context.data.read().await;
+        let lobby_channel_id = match enabled_id(&data, data.get::<Config>().unwrap().discord.lobby_channel_id) {
+            Some(lobby_channel_id) => lobby_channel_id,
+            None => return,
+        };
+        if data.get::<BotState>().unwrap().state != State::Queue { return; }
+        let joined_lobby = voice_state.channel_id.map(|channel_id| *channel_id.as_u64()) == Some(lobby_channel_id);
+        if joined_lobby && !data.get::<Config>().unwrap().lobby_auto_queue.unwrap_or(false) { return; }
+        let queued = data.get::<UserQueue>().unwrap().iter().chain(data.get::<Waitlist>().unwrap().iter())
+            .any(|user| user.id == voice_state.user_id);
+        if !joined_lobby && !queued { return; }
+        joined_lobby
+    };
+    let outbox = Outbox::default();
+    if joined_lobby {
+        // fetched before the bot data is locked, a lobby join needs the whole user
+        let user = match voice_state.member.as_ref() {
+            Some(member) => member.user.clone(),
+            None => match voice_state.user_id.to_user(&context.http).await {
+                Ok(user) => user,
+                Err(_) => return,
+            },
+        };
+        let mut data = context.data.write().await;
+        if data.get::<BotState>().unwrap().state != State::Queue { return; }
+        lobby_auto_join(&context, &outbox, &mut data, &voice_state, user).await;
+        drop(data);
+        outbox.send(&context).await;
+        return;
+    }
+    let mut data = context.data.write().await;
+    if data.get::<BotState>().unwrap().state != State::Queue { return; }
+    let waitlist: &mut Vec<User> = data.get_mut::<Waitlist>().unwrap();
+    if let Some(index) = waitlist.iter().position(|user| user.id == voice_state.user_id) {
+        let user = waitlist.remove(index);
+        if let Some(channel_id) = queue_channel(&data) {
+            let response = MessageBuilder::new()
+                .mention(&user)
+                .push(" left the lobby voice channel and has been removed from the waitlist.")
+                .build();
+            outbox.say(channel_id, response).await;
         }
+        audit_log(&outbox, &data, "Leave", format!("<@{}> left the lobby voice channel and the waitlist", user.id)).await;
+        drop(data);
+        outbox.send(&context).await;
         return;
     }
-    let user_queue_mention: String = user_queue
-        .iter()
-        .map(|user| format!("- <@{}>\n", user.id))
-        .collect();
+    let user_queue: &Vec<User> = data.get::<UserQueue>().unwrap();
+    let user = match user_queue.iter().find(|user| user.id == voice_state.user_id) {
+        Some(user) => user.clone(),
+        None => return,
+    };
+    let queue_len = remove_from_queue(&mut data, std::slice::from_ref(&user));
+    let channel_id = match queue_channel(&data) {
+        Some(channel_id) => channel_id,
+        None => return,
+    };
+    let guild_id = voice_state.guild_id;
     let response = MessageBuilder::new()
-        .push(user_queue_mention)
-        .push_bold_line("Scrim setup is starting...")
+        .mention(&user)
+        .push(" left the lobby voice channel and has been removed from the queue")
+        .push(queue_size_note(&data, queue_len))
         .build();
-    if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-        eprintln!("Error sending message: {:?}", why);
+    outbox.say(channel_id, response).await;
+    promote_waitlist(&outbox, channel_id, guild_id, &mut data).await;
+    drop(data);
+    outbox.send(&context).await;
+}
+
+pub(crate) async fn handle_party_join(context: &Context, msg: &Message, party_members: Vec<User>) {
+    let outbox = Outbox::default();
+    join_queue_as_party(context, &outbox, msg, party_members).await;
+    outbox.send(context).await;
+}
+
+async fn join_queue_as_party(context: &Context, outbox: &Outbox, msg: &Message, party_members: Vec<User>) {
+    let mut data = context.data.write().await;
+    let mut party: Vec<User> = vec![msg.author.clone()];
+    for user in party_members {
+        if !party.contains(&user) {
+            party.push(user);
+        }
     }
-    let bot_state: &mut StateContainer = data.get_mut::<BotState>().unwrap();
-    bot_state.state = State::MapPick;
-    let maps: &Vec<String> = &data.get::<Maps>().unwrap();
-    let mut unicode_to_maps: HashMap<String, String> = HashMap::new();
-    let a_to_z = ('a'..'z').collect::<Vec<_>>();
-    let unicode_emoji_map = populate_unicode_emojis().await;
-    for (i, map) in maps.iter().enumerate() {
-        unicode_to_maps.insert(String::from(unicode_emoji_map.get(&a_to_z[i]).unwrap()), String::from(map));
+    let team_size = queue_size(&data) / 2;
+    if party.len() > team_size {
+        announce_tagged(outbox, msg.channel_id, &format!(" a party can have at most {} players.", team_size), &msg.author).await;
+        return;
     }
-    let emoji_suffixes = a_to_z[..maps.len()].to_vec();
-    let vote_text: String = emoji_suffixes
+    let riot_id_cache: &HashMap<u64, String> = data.get::<RiotIdCache>().unwrap();
+    let missing_riot_ids: Vec<&User> = party
         .iter()
-        .enumerate()
-        .map(|(i, c)| format!(":regional_indicator_{}: `{}`\n", c, &maps[i]))
+        .filter(|user| !riot_id_cache.contains_key(user.id.as_u64()))
         .collect();
-    let response = MessageBuilder::new()
-        .push_bold_line("Map Vote:")
-        .push(vote_text)
-        .build();
-    let vote_msg = msg.channel_id.say(&context.http, &response).await.unwrap();
-    for c in emoji_suffixes {
-        vote_msg.react(&context.http, ReactionType::Unicode(String::from(unicode_emoji_map.get(&c).unwrap()))).await.unwrap();
+    if !missing_riot_ids.is_empty() {
+        let missing: String = missing_riot_ids.iter().map(|user| format!(" <@{}>", user.id)).collect();
+        announce_tagged(outbox, msg.channel_id, &format!(" party cannot join, riotid not found for:{}. \
+        Use `.riotid <your riotid>` to assign one. Example: `.riotid Martige#NA1`", missing), &msg.author).await;
+        return;
+    }
+    let user_queue: &mut Vec<User> = data.get_mut::<UserQueue>().unwrap();
+    let already_queued: Vec<&User> = party.iter().filter(|user| user_queue.contains(user)).collect();
+    if !already_queued.is_empty() {
+        let queued: String = already_queued.iter().map(|user| format!(" <@{}>", user.id)).collect();
+        announce_tagged(outbox, msg.channel_id, &format!(" party cannot join, already in the queue:{}", queued), &msg.author).await;
+        return;
     }
-    task::sleep(Duration::from_secs(50)).await;
+    let open_slots = team_size * 2 - user_queue.len().min(team_size * 2);
+    if party.len() > open_slots {
+        announce_tagged(outbox, msg.channel_id, &format!(" sorry but the queue can't fit your party of {}. Open slots: {}", party.len(), open_slots), &msg.author).await;
+        return;
+    }
+    let previous_len = user_queue.len();
+    user_queue.extend(party.iter().cloned());
+    let queue_len = user_queue.len();
+    // waitlisted members move up with their party instead of being promoted into the queue a second time
+    let waitlist: &mut Vec<User> = data.get_mut::<Waitlist>().unwrap();
+    waitlist.retain(|user| !party.contains(user));
+    let party_mention: String = party.iter().map(|user| format!("<@{}> ", user.id)).collect();
     let response = MessageBuilder::new()
-        .push("Voting will end in 10 seconds")
+        .push(&party_mention)
+        .push("have been added to the queue as a party")
+        .push(queue_size_note(&data, queue_len))
         .build();
-    if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-        eprintln!("Error sending message: {:?}", why);
+    outbox.say(msg.channel_id, response).await;
+    audit_log(outbox, &data, "Join", format!("{}joined the queue as a party ({}/{})", party_mention, queue_len, queue_size(&data))).await;
+    let parties: &mut Vec<Vec<User>> = data.get_mut::<Parties>().unwrap();
+    parties.push(party.clone());
+    let queue_join_times: &mut HashMap<u64, DateTime<Local>> = data.get_mut::<QueueJoinTimes>().unwrap();
+    for user in &party {
+        queue_join_times.insert(*user.id.as_u64(), Local::now());
     }
-    task::sleep(Duration::from_secs(10)).await;
-    let updated_vote_msg = vote_msg.channel_id.message(&context.http, vote_msg.id).await.unwrap();
-    let mut results: Vec<ReactionResult> = Vec::new();
-    for reaction in updated_vote_msg.reactions {
-        let react_as_map: Option<&String> = unicode_to_maps.get(reaction.reaction_type.to_string().as_str());
-        if react_as_map != None {
-            let map = String::from(react_as_map.unwrap());
-            results.push(ReactionResult {
-                count: reaction.count,
-                map,
-            });
-        }
+    for user in &party {
+        record_queue_event(&mut data, QueueEventKind::Join, Some(*user.id.as_u64()));
     }
-    let max_count = results
-        .iter()
-        .max_by(|x, y| x.count.cmp(&y.count))
-        .unwrap()
-        .count;
-    let final_results: Vec<ReactionResult> = results
-        .into_iter()
-        .filter(|m| m.count == max_count)
-        .collect();
-    if final_results.len() > 1 {
-        let map = &final_results.get(rand::thread_rng().gen_range(0, final_results.len())).unwrap().map;
-        let response = MessageBuilder::new()
-            .push("Maps were tied, `")
-            .push(&map)
-            .push("` was selected at random")
-            .build();
-        if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-            eprintln!("Error sending message: {:?}", why);
-        }
-    } else {
-        let map = &final_results[0].map;
-        let response = MessageBuilder::new()
-            .push("Map vote has concluded. `")
-            .push(&map)
-            .push("` will be played")
-            .build();
-        if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-            eprintln!("Error sending message: {:?}", why);
-        }
+    if let Some(note) = parse_join_note(&msg.content) {
+        let queued_msgs: &mut HashMap<u64, String> = data.get_mut::<QueueMessages>().unwrap();
+        queued_msgs.insert(*msg.author.id.as_u64(), note);
+    }
+    update_queue_activity(&mut data, msg.channel_id, msg.guild_id);
+    check_queue_thresholds(context, outbox, &mut data, msg.channel_id, msg.guild_id, previous_len, queue_len).await;
+    for user in &party {
+        assign_queue_role(outbox, msg.guild_id, &data, user);
     }
-    let mut bot_state: &mut StateContainer = data.get_mut::<BotState>().unwrap();
-    bot_state.state = State::CaptainPick;
-    let draft: &mut Draft = &mut data.get_mut::<Draft>().unwrap();
-    draft.captain_a = None;
-    draft.captain_b = None;
-    draft.team_a = Vec::new();
-    draft.team_b = Vec::new();
-    send_simple_msg(&context, &msg, "Starting captain pick phase. Two users type `.captain` to start picking teams.").await;
 }
 
+pub(crate) fn assign_queue_role(outbox: &Outbox, guild_id: Option<GuildId>, data: &TypeMap, user: &User) {
+    if let (Some(guild_id), Some(role_id)) = (guild_id, enabled_id(data, data.get::<Config>().unwrap().discord.assign_role_id)) {
+        outbox.add_role(guild_id, user.id, RoleId(role_id));
+    }
+}
 
-pub(crate) async fn handle_captain(context: Context, msg: Message) {
+pub(crate) async fn handle_leave(context: Context, msg: Message) -> BotResult {
+    let outbox = Outbox::default();
     let mut data = context.data.write().await;
-    let bot_state: &mut StateContainer = &mut data.get_mut::<BotState>().unwrap();
-    if bot_state.state != State::CaptainPick {
-        send_simple_tagged_msg(&context, &msg, " command ignored, not in the captain pick phase", &msg.author).await;
-        return;
+    let waitlist: &mut Vec<User> = data.get_mut::<Waitlist>().unwrap();
+    if let Some(index) = waitlist.iter().position(|user| user == &msg.author) {
+        waitlist.remove(index);
+        announce_tagged(&outbox, msg.channel_id, " has left the waitlist.", &msg.author).await;
+        audit_log(&outbox, &data, "Leave", format!("<@{}> left the waitlist", msg.author.id)).await;
+        drop(data);
+        outbox.send(&context).await;
+        return Ok(());
     }
-    let draft: &mut Draft = &mut data.get_mut::<Draft>().unwrap();
-    if draft.captain_a != None && &msg.author == draft.captain_a.as_ref().unwrap() {
-        send_simple_tagged_msg(&context, &msg, " you're already a captain!", &msg.author).await;
-        return;
+    let state: &mut StateContainer = data.get_mut::<BotState>().unwrap();
+    if state.state != State::Queue {
+        return Err(BotError::Usage(String::from(" cannot `.leave` the queue after `.start`, use `.cancel` to start over if needed.")));
     }
-    if draft.captain_a == None {
-        send_simple_tagged_msg(&context, &msg, " is set as captain.", &msg.author).await;
-        draft.captain_a = Some(msg.author.clone());
+    let user_queue: &mut Vec<User> = data.get_mut::<UserQueue>().unwrap();
+    if !user_queue.contains(&msg.author) {
+        drop(data);
+        send_simple_tagged_msg(&context, &msg, " is not in the queue. Type `.join` to join the queue.", &msg.author).await;
+        return Ok(());
+    }
+    let parties: &Vec<Vec<User>> = data.get::<Parties>().unwrap();
+    let party: Vec<User> = match parties.iter().find(|party| party.contains(&msg.author)) {
+        Some(party) => party.clone(),
+        None => vec![msg.author.clone()],
+    };
+    let queue_len = remove_from_queue(&mut data, &party);
+    let party_mention: String = party.iter().map(|user| format!("<@{}> ", user.id)).collect();
+    if party.len() > 1 {
+        let response = format!("{}{}", party_mention, i18n::translate(&format!("have left the queue as a party{}", queue_size_note(&data, queue_len))));
+        outbox.say(msg.channel_id, response).await;
     } else {
-        send_simple_tagged_msg(&context, &msg, " is set as captain.", &msg.author).await;
-        draft.captain_b = Some(msg.author.clone());
+        announce_tagged(&outbox, msg.channel_id, &format!(" has left the queue{}", queue_size_note(&data, queue_len)), &msg.author).await;
     }
-    if draft.captain_a != None && draft.captain_b != None {
-        send_simple_msg(&context, &msg, "Randomizing captain pick order...").await;
-        // flip a coin, if 1 switch captains
-        if rand::thread_rng().gen_range(0, 2) != 0 {
-            let captain_a = draft.captain_a.clone();
-            let captain_b = draft.captain_b.clone();
-            draft.captain_a = captain_b;
-            draft.captain_b = captain_a;
-        }
-        draft.team_a.push(draft.captain_a.clone().unwrap());
-        draft.team_b.push(draft.captain_b.clone().unwrap());
-        send_simple_tagged_msg(&context, &msg, " is set as the first pick captain (Team A)", &draft.captain_a.clone().unwrap()).await;
-        send_simple_tagged_msg(&context, &msg, " is set as the second captain (Team B)", &draft.captain_b.clone().unwrap()).await;
-        draft.current_picker = draft.captain_a.clone();
+    audit_log(&outbox, &data, "Leave", format!("{}left the queue ({}/{})", party_mention, queue_len, queue_size(&data))).await;
+    update_queue_activity(&mut data, msg.channel_id, msg.guild_id);
+    promote_waitlist(&outbox, msg.channel_id, msg.guild_id, &mut data).await;
+    drop(data);
+    outbox.send(&context).await;
+    Ok(())
+}
+
+pub(crate) async fn promote_waitlist(outbox: &Outbox, channel_id: ChannelId, guild_id: Option<GuildId>, data: &mut TypeMap) {
+    loop {
+        let queue_len = data.get::<UserQueue>().unwrap().len();
+        let full_size = queue_size(data);
+        let waitlist: &mut Vec<User> = data.get_mut::<Waitlist>().unwrap();
+        if queue_len >= full_size || waitlist.is_empty() { return; }
+        let promoted = waitlist.remove(0);
+        let user_queue: &mut Vec<User> = data.get_mut::<UserQueue>().unwrap();
+        // a stale entry of someone who got into the queue another way is dropped
+        if user_queue.contains(&promoted) { continue; }
+        user_queue.push(promoted.clone());
+        let queue_len = user_queue.len();
         let response = MessageBuilder::new()
-            .push("Captain pick has concluded. Starting draft phase. ")
-            .mention(&draft.current_picker.clone().unwrap())
-            .push(" gets first `.pick @<user>`")
+            .mention(&promoted)
+            .push(" has been promoted from the waitlist to the queue")
+            .push(queue_size_note(data, queue_len))
             .build();
-        if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-            eprintln!("Error sending message: {:?}", why);
+        outbox.say(channel_id, response).await;
+        let queue_join_times: &mut HashMap<u64, DateTime<Local>> = data.get_mut::<QueueJoinTimes>().unwrap();
+        queue_join_times.insert(*promoted.id.as_u64(), Local::now());
+        record_queue_event(data, QueueEventKind::Join, Some(*promoted.id.as_u64()));
+        if data.get::<UserQueue>().unwrap().len() == queue_size(data) {
+            record_queue_event(data, QueueEventKind::Full, None);
+            queue_full_webhook(data);
         }
-        let bot_state: &mut StateContainer = &mut data.get_mut::<BotState>().unwrap();
-        bot_state.state = State::Draft;
-        let user_queue: &Vec<User> = &mut data.get::<UserQueue>().unwrap();
-        let draft: &Draft = &mut data.get::<Draft>().unwrap();
-        let teamname_cache = data.get::<TeamNameCache>().unwrap();
-        let team_a_name = teamname_cache.get(draft.captain_a.as_ref().unwrap().id.as_u64())
-            .unwrap_or(&draft.captain_a.as_ref().unwrap().name);
-        let team_b_name = teamname_cache.get(draft.captain_b.as_ref().unwrap().id.as_u64())
-            .unwrap_or(&draft.captain_b.as_ref().unwrap().name);
-        list_unpicked(&user_queue, &draft, &context, &msg, team_a_name, team_b_name).await;
+        assign_queue_role(outbox, guild_id, data, &promoted);
     }
 }
 
-pub(crate) async fn handle_pick(context: Context, msg: Message) {
-    let mut data = context.data.write().await;
-    let bot_state: &mut StateContainer = &mut data.get_mut::<BotState>().unwrap();
-    if bot_state.state != State::Draft {
-        send_simple_tagged_msg(&context, &msg, " it is not currently the draft phase", &msg.author).await;
-        return;
-    }
-    if msg.mentions.is_empty() {
-        send_simple_tagged_msg(&context, &msg, " please mention a discord user in your message.", &msg.author).await;
-        return;
-    }
-    let picked = msg.mentions[0].clone();
-    let user_queue: &Vec<User> = &data.get::<UserQueue>().unwrap().to_vec();
-    if !user_queue.contains(&picked) {
-        send_simple_tagged_msg(&context, &msg, " this user is not in the queue", &msg.author).await;
-        return;
-    }
-    let draft = data.get::<Draft>().unwrap();
-    let current_picker = draft.current_picker.clone().unwrap();
-    if msg.author != *draft.captain_a.as_ref().unwrap() && msg.author != *draft.captain_b.as_ref().unwrap() {
-        send_simple_tagged_msg(&context, &msg, " you are not a captain", &msg.author).await;
-        return;
-    }
-    if current_picker != msg.author {
-        send_simple_tagged_msg(&context, &msg, " it is not your turn to pick", &msg.author).await;
-        return;
-    }
-    if draft.team_a.contains(&picked) || draft.team_b.contains(&picked) {
-        send_simple_tagged_msg(&context, &msg, " this player is already on a team", &msg.author).await;
-        return;
-    }
-
-    let teamname_cache = data.get::<TeamNameCache>().unwrap();
-    let team_a_name = String::from(teamname_cache.get(draft.captain_a.as_ref().unwrap().id.as_u64())
-        .unwrap_or(&draft.captain_a.as_ref().unwrap().name));
-    let team_b_name = String::from(teamname_cache.get(draft.captain_b.as_ref().unwrap().id.as_u64())
-        .unwrap_or(&draft.captain_b.as_ref().unwrap().name));
-    let draft: &mut Draft = &mut data.get_mut::<Draft>().unwrap();
-    if draft.captain_a.as_ref().unwrap() == &current_picker {
-        send_simple_tagged_msg(&context, &msg, &format!(" has been added to Team {}", team_a_name), &picked).await;
-        draft.team_a.push(picked);
-        draft.current_picker = draft.captain_b.clone();
-        list_unpicked(&user_queue, &draft, &context, &msg, &team_a_name, &team_b_name).await;
-    } else {
-        send_simple_tagged_msg(&context, &msg, &format!(" has been added to Team {}", team_b_name), &picked).await;
-        draft.team_b.push(picked);
-        draft.current_picker = draft.captain_a.clone();
-        list_unpicked(&user_queue, &draft, &context, &msg, &team_a_name, &team_b_name).await;
-    }
-    let remaining_users = user_queue
-        .iter()
-        .filter(|user| !draft.team_a.contains(user) && !draft.team_b.contains(user))
-        .count();
-    if remaining_users == 0 {
-        let captain_b = draft.captain_b.clone().unwrap();
-        let bot_state: &mut StateContainer = &mut data.get_mut::<BotState>().unwrap();
-        bot_state.state = State::SidePick;
-        send_simple_tagged_msg(&context, &msg, " type `.defense` or `.attack` to pick a starting side.", &captain_b).await;
-    }
+pub(crate) fn update_queue_activity(data: &mut TypeMap, channel_id: ChannelId, guild_id: Option<GuildId>) {
+    let queue_activity: &mut Option<QueueActivityInfo> = data.get_mut::<QueueActivity>().unwrap();
+    *queue_activity = Some(QueueActivityInfo {
+        channel_id,
+        guild_id,
+        updated: Local::now(),
+    });
 }
 
-pub(crate) async fn list_unpicked(user_queue: &Vec<User>, draft: &Draft, context: &Context, msg: &Message, team_a_name: &String, team_b_name: &String) {
-    let remaining_users: String = user_queue
-        .iter()
-        .filter(|user| !draft.team_a.contains(user) && !draft.team_b.contains(user))
-        .map(|user| format!("- @{}\n", &user.name))
-        .collect();
-    let team_a: String = draft.team_a
-        .iter()
-        .map(|user| format!("- @{}\n", &user.name))
-        .collect();
-    let team_b: String = draft.team_b
-        .iter()
-        .map(|user| format!("- @{}\n", &user.name))
-        .collect();
-    let response = MessageBuilder::new()
-        .push_bold_line(format!("Team {}:", team_a_name))
-        .push_line(team_a)
-        .push_bold_line(format!("Team {}:", team_b_name))
-        .push_line(team_b)
-        .push_bold_line("Remaining players: ")
-        .push_line(remaining_users)
-        .build();
+/// Returns the configured queue opening and closing times if both are set and valid
+pub(crate) fn queue_window(config: &Config) -> Option<(NaiveTime, NaiveTime)> {
+    let open = NaiveTime::parse_from_str(config.queue_open_time.as_ref()?, "%H:%M").ok()?;
+    let close = NaiveTime::parse_from_str(config.queue_close_time.as_ref()?, "%H:%M").ok()?;
+    Some((open, close))
+}
 
-    if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-        eprintln!("Error sending message: {:?}", why);
-    }
+/// Returns when the queue opens next if it's currently outside of the configured queue hours
+pub(crate) fn queue_closed_until(config: &Config) -> Option<DateTime<Local>> {
+    let (open, close) = queue_window(config)?;
+    let now = Local::now();
+    let time = now.time();
+    // windows like 18:00-02:00 wrap around midnight
+    let is_open = if open <= close { time >= open && time < close } else { time >= open || time < close };
+    if is_open { return None; }
+    let open_date = if time < open { now.date_naive() } else { now.date_naive() + ChronoDuration::days(1) };
+    Local.from_local_datetime(&open_date.and_time(open)).earliest()
 }
 
-pub(crate) async fn handle_defense_option(context: Context, msg: Message) {
-    {
-        let mut data: RwLockWriteGuard<TypeMap> = context.data.write().await;
-        let bot_state: &mut StateContainer = &mut data.get_mut::<BotState>().unwrap();
-        if bot_state.state != State::SidePick {
-            send_simple_tagged_msg(&context, &msg, " it is not currently the side pick phase", &msg.author).await;
-            return;
-        }
-        let draft: &mut Draft = &mut data.get_mut::<Draft>().unwrap();
-        if &msg.author != draft.captain_b.as_ref().unwrap() {
-            send_simple_tagged_msg(&context, &msg, " you are not Captain B", &msg.author).await;
-            return;
+/// Announces in the queue channel whenever the queue hours start
+pub(crate) async fn announce_queue_window(context: &Context) {
+    let mut was_closed = queue_closed_until(context.data.read().await.get::<Config>().unwrap()).is_some();
+    loop {
+        task::sleep(Duration::from_secs(60)).await;
+        let data = context.data.read().await;
+        let config: &Config = data.get::<Config>().unwrap();
+        let is_closed = queue_closed_until(config).is_some();
+        if was_closed && !is_closed {
+            if let Some(channel_id) = queue_channel(&data) {
+                let response = format!("The queue is now open until {}, type `.join` to join!", config.queue_close_time.as_deref().unwrap_or_default());
+                delivery::say(&context.http, channel_id, &response).await;
+            }
         }
-        draft.team_b_start_side = String::from("ct");
-        let bot_state: &mut StateContainer = &mut data.get_mut::<BotState>().unwrap();
-        bot_state.state = State::Ready;
-        send_simple_msg(&context, &msg, "Setup is completed.").await;
+        was_closed = is_closed;
     }
-    handle_ready(&context, &msg).await;
 }
 
-pub(crate) async fn handle_attack_option(context: Context, msg: Message) {
-    {
-        let mut data = context.data.write().await;
-        let bot_state: &mut StateContainer = &mut data.get_mut::<BotState>().unwrap();
-        if bot_state.state != State::SidePick {
-            send_simple_tagged_msg(&context, &msg, " it is not currently the side pick phase", &msg.author).await;
-            return;
+pub(crate) fn queue_cooldown(data: &TypeMap, user: &User) -> Option<DateTime<Local>> {
+    let player_stats: &HashMap<u64, PlayerStats> = data.get::<PlayerStatsCache>().unwrap();
+    player_stats.get(user.id.as_u64())
+        .and_then(|stats| stats.cooldown_until)
+        .and_then(|timestamp| Local.timestamp_opt(timestamp, 0).single())
+        .filter(|until| until > &Local::now())
+}
+
+/// Posts an entry to the `audit_channel_id` channel, entries are embeds so mentions don't ping anyone
+pub(crate) async fn audit_log(messenger: &impl Messenger, data: &TypeMap, event: &str, details: String) {
+    let audit_channel_id = match enabled_id(data, data.get::<Config>().unwrap().discord.audit_channel_id) {
+        Some(audit_channel_id) => ChannelId(audit_channel_id),
+        None => return,
+    };
+    let mut embed = CreateEmbed::default();
+    embed.title(event)
+        .description(details)
+        .footer(|f| f.text(Local::now().format("%Y-%m-%d %H:%M:%S").to_string()));
+    messenger.send_embed(audit_channel_id, embed).await;
+}
+
+/// Number of players in a full queue, `queue_size` from the config if it's an even number up to 10, otherwise 10
+pub(crate) fn queue_size(data: &TypeMap) -> usize {
+    data.get::<Config>().unwrap().queue_size.filter(|size| valid_queue_size(*size)).unwrap_or(10)
+}
+
+pub(crate) fn valid_queue_size(size: usize) -> bool {
+    [2, 4, 6, 8, 10].contains(&size)
+}
+
+/// The id unless `validate_config` couldn't find it in the server, see `DisabledConfigIds`
+pub(crate) fn enabled_id(data: &TypeMap, id: Option<u64>) -> Option<u64> {
+    id.filter(|id| !data.get::<DisabledConfigIds>().unwrap().contains(id))
+}
+
+/// The channel used for queue announcements that aren't a reply to a command
+pub(crate) fn queue_channel(data: &TypeMap) -> Option<ChannelId> {
+    if let Some(queue_channel_id) = enabled_id(data, data.get::<Config>().unwrap().discord.queue_channel_id) {
+        return Some(ChannelId(queue_channel_id));
+    }
+    data.get::<QueueActivity>().unwrap().as_ref().map(|activity| activity.channel_id)
+}
+
+/// Follows queue "sessions" through the queue events. A session runs from the first join into an empty queue
+/// until the queue fills, empties or gets cleared
+#[derive(Default)]
+pub(crate) struct QueueSessions {
+    started: Option<i64>,
+    filled: bool,
+    sessions: usize,
+    abandoned: usize,
+}
+
+impl QueueSessions {
+    pub(crate) fn replay<'a>(events: impl IntoIterator<Item = &'a QueueEvent>) -> Self {
+        let mut queue_sessions = QueueSessions::default();
+        for event in events {
+            queue_sessions.track(event);
         }
-        let draft: &mut Draft = &mut data.get_mut::<Draft>().unwrap();
-        if &msg.author != draft.captain_b.as_ref().unwrap() {
-            send_simple_tagged_msg(&context, &msg, " you are not Captain B", &msg.author).await;
-            return;
+        queue_sessions
+    }
+
+    /// Returns the fill time in seconds when `event` fills the queue
+    fn track(&mut self, event: &QueueEvent) -> Option<i64> {
+        match event.kind {
+            QueueEventKind::Join if self.started.is_none() && !self.filled => {
+                self.started = Some(event.time);
+                self.sessions += 1;
+            }
+            QueueEventKind::Full => {
+                self.filled = true;
+                return self.started.take().map(|start| event.time - start);
+            }
+            QueueEventKind::Start => self.filled = false,
+            QueueEventKind::Clear => {
+                self.abandoned += self.started.take().map_or(0, |_| 1);
+                self.filled = false;
+            }
+            QueueEventKind::Leave if event.queue_size == 0 => {
+                self.abandoned += self.started.take().map_or(0, |_| 1);
+                self.filled = false;
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+/// i.e. `24 minutes` or `1h 05m`
+pub(crate) fn format_fill_time(seconds: i64) -> String {
+    if seconds < 3600 {
+        let minutes = (seconds + 30) / 60;
+        format!("{} minute{}", minutes, if minutes == 1 { "" } else { "s" })
+    } else {
+        format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60)
+    }
+}
+
+/// How long the current queue took to fill and the average of the last 10 fill times, `None` until a queue has filled
+pub(crate) fn fill_times(data: &TypeMap) -> Option<(i64, i64)> {
+    let queue_events: &Vec<QueueEvent> = data.get::<QueueEvents>().unwrap();
+    // a queue that was recovered or refilled after a cancel has no fill time of its own
+    let current = queue_events.iter()
+        .rev()
+        .find(|event| matches!(event.kind, QueueEventKind::Full | QueueEventKind::Start))
+        .and_then(|event| event.fill_seconds)?;
+    let recent: Vec<i64> = queue_events.iter().rev().filter_map(|event| event.fill_seconds).take(10).collect();
+    Some((current, recent.iter().sum::<i64>() / recent.len() as i64))
+}
+
+/// Keeps queue events for `.analytics`, events older than `QUEUE_EVENT_DAYS` are dropped
+pub(crate) fn record_queue_event(data: &mut TypeMap, kind: QueueEventKind, user_id: Option<u64>) {
+    let queue_size = data.get::<UserQueue>().unwrap().len();
+    let mut event = QueueEvent { time: Local::now().timestamp(), kind, user_id, queue_size, fill_seconds: None };
+    event.fill_seconds = data.get_mut::<QueueSessionState>().unwrap().track(&event);
+    data.get_mut::<QueueEvents>().unwrap().push(event);
+    data.insert::<QueueEventsUnsaved>(true);
+}
+
+/// Drops the expired queue events and writes the rest once a minute when there are new ones, a crash loses at most a minute of `.analytics`
+pub(crate) async fn save_queue_events(context: &Context) {
+    loop {
+        task::sleep(Duration::from_secs(60)).await;
+        let json = {
+            let mut data = context.data.write().await;
+            if !*data.get::<QueueEventsUnsaved>().unwrap() { continue; }
+            data.insert::<QueueEventsUnsaved>(false);
+            let cutoff = Local::now().timestamp() - QUEUE_EVENT_DAYS * 24 * 60 * 60;
+            let queue_events: &mut Vec<QueueEvent> = data.get_mut::<QueueEvents>().unwrap();
+            queue_events.retain(|event| event.time >= cutoff);
+            serde_json::to_string(queue_events).unwrap()
+        };
+        write_to_file(String::from("queue_events.json"), json).await;
+    }
+}
+
+/// The channel of the pinned queue status message, `None` unless `queue_status_message` is on
+fn status_message_channel(data: &TypeMap) -> Option<ChannelId> {
+    let config: &Config = data.get::<Config>().unwrap();
+    match (config.queue_status_message, enabled_id(data, config.discord.queue_channel_id)) {
+        (Some(true), Some(channel_id)) => Some(ChannelId(channel_id)),
+        _ => None,
+    }
+}
+
+/// `. Queue size: 4/10` for join, leave and kick announcements, left out when the status message already shows the queue
+pub(crate) fn queue_size_note(data: &TypeMap, queue_len: usize) -> String {
+    match status_message_channel(data) {
+        Some(_) => String::new(),
+        None => i18n::translate(&format!(". Queue size: {}/{}", queue_len, queue_size(data))),
+    }
+}
+
+static STATUS_REFRESH_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Edits the pinned queue status message when the queue changed, posting and pinning a new one if it's missing.
+/// Skipped while the bot data stays busy or another refresh is running, the next event or the periodic refresh catches up
+pub(crate) async fn refresh_status_message(context: &Context) {
+    let (channel_id, content, existing, reaction_join, queue_empty) = {
+        let data = match tokio::time::timeout(Duration::from_secs(5), context.data.read()).await {
+            Ok(data) => data,
+            Err(_) => return,
+        };
+        let channel_id = match status_message_channel(&data) {
+            Some(channel_id) => channel_id,
+            None => return,
+        };
+        let content = status_message_content(&data);
+        let existing = data.get::<QueueStatusMessage>().unwrap().clone().filter(|status| status.channel_id == *channel_id.as_u64());
+        if existing.as_ref().map(|status| &status.content) == Some(&content) { return; }
+        (channel_id, content, existing, data.get::<Config>().unwrap().reaction_join == Some(true), data.get::<UserQueue>().unwrap().is_empty())
+    };
+    // the messages are sent without holding the bot data, two refreshes at once would both post a new status message
+    if STATUS_REFRESH_RUNNING.swap(true, Ordering::SeqCst) { return; }
+    let status = post_status_message(context, channel_id, content, existing, reaction_join, queue_empty).await;
+    if let Some(status) = status {
+        write_to_file(String::from("status_message.json"), serde_json::to_string(&status).unwrap()).await;
+        context.data.write().await.insert::<QueueStatusMessage>(Some(status));
+    }
+    STATUS_REFRESH_RUNNING.store(false, Ordering::SeqCst);
+}
+
+async fn post_status_message(context: &Context, channel_id: ChannelId, content: String, existing: Option<StatusMessage>, reaction_join: bool, queue_empty: bool) -> Option<StatusMessage> {
+    let edited = match &existing {
+        Some(status) => channel_id.edit_message(&context.http, status.message_id, |m| m
+            .embed(|e| e.title("Queue Status").description(&content)))
+            .await
+            .is_ok(),
+        None => false,
+    };
+    let message_id = if edited {
+        existing.unwrap().message_id
+    } else {
+        let message = match channel_id.send_message(&context.http, |m| m
+            .embed(|e| e.title("Queue Status").description(&content)))
+            .await {
+            Ok(message) => message,
+            Err(why) => {
+                eprintln!("Error sending queue status message: {:?}", why);
+                return None;
+            }
+        };
+        if let Err(why) = message.pin(&context.http).await {
+            ops::report(context, format!("Cannot pin the queue status message, check the bot's manage messages permission: {:?}", why)).await;
+        }
+        *message.id.as_u64()
+    };
+    // a new message needs the ✅ to click on, an emptied queue starts over without last round's reactions
+    if reaction_join && (!edited || queue_empty) {
+        if edited {
+            if let Err(why) = context.http.delete_message_reactions(*channel_id.as_u64(), message_id).await {
+                eprintln!("Error clearing queue status reactions: {:?}", why);
+            }
+        }
+        if let Err(why) = delivery::react(&context.http, channel_id, MessageId(message_id), ReactionType::Unicode(String::from("✅"))).await {
+            eprintln!("Error adding queue status reaction: {:?}", why);
+        }
+    }
+    Some(StatusMessage { channel_id: *channel_id.as_u64(), message_id, content })
+}
+
+fn status_message_content(data: &TypeMap) -> String {
+    let user_queue: &Vec<User> = data.get::<UserQueue>().unwrap();
+    let mut content = format!("**State:** {}\n**Players:** {}/{}\n", data.get::<BotState>().unwrap().state, user_queue.len(), queue_size(data));
+    for user in user_queue {
+        content.push_str(&format!("- {}\n", user.name));
+    }
+    let waitlist: &Vec<User> = data.get::<Waitlist>().unwrap();
+    if !waitlist.is_empty() {
+        content.push_str(&format!("**Waitlist:** {}\n", waitlist.iter().map(|user| user.name.as_str()).collect::<Vec<_>>().join(", ")));
+    }
+    if let Some(reason) = data.get::<QueueLock>().unwrap() {
+        content.push_str(&format!("**Locked:** {}\n", if reason.is_empty() { "yes" } else { reason }));
+    }
+    if user_queue.len() < queue_size(data) {
+        if data.get::<Config>().unwrap().reaction_join == Some(true) {
+            content.push_str("\nReact ✅ or type `.join` to play");
+        } else {
+            content.push_str("\nType `.join` to play");
+        }
+    }
+    content
+}
+
+/// Catches queue changes from timers and the dashboard that don't come with a Discord event
+pub(crate) async fn keep_status_message_updated(context: &Context) {
+    if context.data.read().await.get::<Config>().unwrap().queue_status_message != Some(true) { return; }
+    loop {
+        refresh_status_message(context).await;
+        task::sleep(Duration::from_secs(60)).await;
+    }
+}
+
+/// Sends the `queue_full` webhook with the queued players
+pub(crate) fn queue_full_webhook(data: &TypeMap) {
+    let players: Vec<serde_json::Value> = data.get::<UserQueue>().unwrap().iter().map(|user| web::player(user, data)).collect();
+    webhooks::send(data.get::<Config>().unwrap(), "queue_full", serde_json::json!({ "players": players }));
+}
+
+pub(crate) async fn check_queue_thresholds(context: &Context, outbox: &Outbox, data: &mut TypeMap, channel_id: ChannelId, guild_id: Option<GuildId>, previous_len: usize, queue_len: usize) {
+    let full_size = queue_size(data);
+    if previous_len < full_size && queue_len >= full_size {
+        record_queue_event(data, QueueEventKind::Full, None);
+        queue_full_webhook(data);
+    }
+    let config: &Config = data.get::<Config>().unwrap();
+    if let (Some(role_id), Some(threshold)) = (enabled_id(data, config.discord.queue_ping_role_id), config.queue_ping_size) {
+        let cooldown = ChronoDuration::minutes(config.queue_ping_cooldown_minutes.unwrap_or(30));
+        let last_queue_ping: &mut Option<DateTime<Local>> = data.get_mut::<LastQueuePing>().unwrap();
+        let cooled_down = last_queue_ping.map(|last_ping| Local::now().signed_duration_since(last_ping) >= cooldown).unwrap_or(true);
+        if previous_len < threshold && queue_len >= threshold && queue_len < full_size && cooled_down {
+            *last_queue_ping = Some(Local::now());
+            let response = MessageBuilder::new()
+                .role(role_id)
+                .push(format!(" the queue is at {}/{}, {} more needed! Type `.join` to play.", queue_len, full_size, full_size - queue_len))
+                .build();
+            outbox.say(channel_id, response).await;
+        }
+    }
+    let user_queue: Vec<User> = data.get::<UserQueue>().unwrap().clone();
+    let notify_subscriptions: &mut HashMap<u64, usize> = data.get_mut::<NotifySubscriptions>().unwrap();
+    let notified: Vec<u64> = notify_subscriptions
+        .iter()
+        .filter(|(_, size)| previous_len < **size && queue_len >= **size)
+        .map(|(user_id, _)| *user_id)
+        .collect();
+    if !notified.is_empty() {
+        for user_id in &notified {
+            notify_subscriptions.remove(user_id);
+            if user_queue.iter().any(|user| user.id.as_u64() == user_id) { continue; }
+            let response = format!("The scrim queue has reached {}/{} players, type `.join` in <#{}> to play!", queue_len, full_size, channel_id);
+            outbox.dm(UserId(*user_id), response);
+        }
+        write_to_file(String::from("notify_subscriptions.json"), serde_json::to_string(notify_subscriptions).unwrap()).await;
+    }
+    if let Some(threshold) = data.get::<Config>().unwrap().afk_check_queue_size {
+        if previous_len < threshold && queue_len >= threshold && queue_len < full_size {
+            // the check posts a message it needs the id of, so it runs once the caller drops the bot data
+            let context = context.clone();
+            tokio::spawn(async move { start_afk_check(&context, channel_id, guild_id).await });
         }
-        draft.team_b_start_side = String::from("t");
-        let bot_state: &mut StateContainer = &mut data.get_mut::<BotState>().unwrap();
-        bot_state.state = State::Ready;
-        send_simple_msg(&context, &msg, "Setup is completed.").await;
     }
-    handle_ready(&context, &msg).await;
 }
 
-pub(crate) async fn handle_riotid(context: Context, msg: Message) {
+pub(crate) async fn handle_notify(context: Context, msg: Message) -> BotResult {
     let mut data = context.data.write().await;
-    let riot_id_cache: &mut HashMap<u64, String> = &mut data.get_mut::<RiotIdCache>().unwrap();
-    let split_content = msg.content.trim().split(' ').take(2).collect::<Vec<_>>();
-    if split_content.len() == 1 {
-        send_simple_tagged_msg(&context, &msg, " please check the command formatting. There must be a space in between `.riotid` and your Riot id. \
-        Example: `.riotid Martige#NA1`", &msg.author).await;
-        return;
+    let split_content = msg.content.trim().split(' ').filter(|s| !s.is_empty()).collect::<Vec<_>>();
+    let full_size = queue_size(&data);
+    let notify_subscriptions: &mut HashMap<u64, usize> = data.get_mut::<NotifySubscriptions>().unwrap();
+    match split_content.get(1).map(|arg| arg.to_lowercase()).as_deref() {
+        Some("off") => {
+            notify_subscriptions.remove(msg.author.id.as_u64());
+            write_to_file(String::from("notify_subscriptions.json"), serde_json::to_string(notify_subscriptions).unwrap()).await;
+            send_simple_tagged_msg(&context, &msg, " queue notifications turned off.", &msg.author).await;
+        }
+        Some(size) if matches!(size.parse::<usize>(), Ok(size) if (1..=full_size).contains(&size)) => {
+            let size: usize = size.parse().unwrap();
+            notify_subscriptions.insert(*msg.author.id.as_u64(), size);
+            write_to_file(String::from("notify_subscriptions.json"), serde_json::to_string(notify_subscriptions).unwrap()).await;
+            send_simple_tagged_msg(&context, &msg, &format!(" you'll get a DM when the queue reaches {}/{}.", size, full_size), &msg.author).await;
+        }
+        _ => {
+            send_simple_tagged_msg(&context, &msg, " invalid message formatting. Example: `.notify 8` or `.notify off`", &msg.author).await;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) async fn start_afk_check(context: &Context, channel_id: ChannelId, guild_id: Option<GuildId>) {
+    let (user_queue, timeout_minutes) = {
+        let data = context.data.read().await;
+        if data.get::<ActiveAfkCheck>().unwrap().is_some() { return; }
+        let user_queue: Vec<User> = data.get::<UserQueue>().unwrap().clone();
+        if user_queue.is_empty() { return; }
+        (user_queue, data.get::<Config>().unwrap().afk_check_timeout_minutes.unwrap_or(3))
+    };
+    let user_queue_mention: String = user_queue
+        .iter()
+        .map(|user| format!("- <@{}>\n", user.id))
+        .collect();
+    let response = MessageBuilder::new()
+        .push(user_queue_mention)
+        .push_bold_line("AFK check!")
+        .push(format!("React with ✅ or type `.here` within {} minutes to stay in the queue.", timeout_minutes))
+        .build();
+    let check_msg = match delivery::post(&context.http, channel_id, &response).await {
+        Some(check_msg) => check_msg,
+        None => return,
+    };
+    if let Err(why) = delivery::react(&context.http, check_msg.channel_id, check_msg.id, ReactionType::Unicode(String::from("✅"))).await {
+        eprintln!("Error adding reaction: {:?}", why);
     }
-    let riot_id_str: String = String::from(split_content[1]);
-    let riot_id_regex = Regex::new("\\w+#\\w+").unwrap();
-    if !riot_id_regex.is_match(&riot_id_str) {
-        send_simple_tagged_msg(&context, &msg, " invalid Riot id formatting. Please follow this example: `.riotid Martige#NA1`", &msg.author).await;
+    let mut data = context.data.write().await;
+    let afk_check: &mut Option<AfkCheck> = data.get_mut::<ActiveAfkCheck>().unwrap();
+    // another check started while this one was being posted
+    if afk_check.is_some() { return; }
+    *afk_check = Some(AfkCheck {
+        message_id: *check_msg.id.as_u64(),
+        pending: user_queue,
+    });
+    let context = context.clone();
+    tokio::spawn(async move {
+        task::sleep(Duration::from_secs(timeout_minutes * 60)).await;
+        finish_afk_check(&context, channel_id, guild_id, *check_msg.id.as_u64()).await;
+    });
+}
+
+pub(crate) async fn finish_afk_check(context: &Context, channel_id: ChannelId, guild_id: Option<GuildId>, message_id: u64) {
+    let outbox = Outbox::default();
+    let mut data = context.data.write().await;
+    let afk_check: &mut Option<AfkCheck> = data.get_mut::<ActiveAfkCheck>().unwrap();
+    if afk_check.as_ref().map(|check| check.message_id) != Some(message_id) { return; }
+    let afk_check: AfkCheck = afk_check.take().unwrap();
+    if data.get::<BotState>().unwrap().state != State::Queue { return; }
+    let user_queue: &mut Vec<User> = data.get_mut::<UserQueue>().unwrap();
+    let removed: Vec<User> = afk_check.pending
+        .into_iter()
+        .filter(|user| user_queue.contains(user))
+        .collect();
+    if removed.is_empty() {
+        drop(data);
+        delivery::say(&context.http, channel_id, "AFK check passed, everyone is here!").await;
         return;
     }
-    riot_id_cache.insert(*msg.author.id.as_u64(), String::from(&riot_id_str));
-    write_to_file(String::from("riot_ids.json"), serde_json::to_string(riot_id_cache).unwrap()).await;
+    let queue_len = remove_from_queue(&mut data, &removed);
+    // players who lost the ready check are placed ahead on the waitlist next time, like waitlisted players
+    let priority_players: &mut Vec<u64> = data.get_mut::<PriorityPlayers>().unwrap();
+    for user in &removed {
+        if !priority_players.contains(user.id.as_u64()) {
+            priority_players.push(*user.id.as_u64());
+        }
+    }
+    let removed_mention: String = removed.iter().map(|user| format!("<@{}> ", user.id)).collect();
     let response = MessageBuilder::new()
-        .push("Updated Riot id for ")
-        .mention(&msg.author)
-        .push(" to `")
-        .push(&riot_id_str)
-        .push("`")
+        .push(removed_mention)
+        .push("removed from the queue for not responding to the AFK check")
+        .push(queue_size_note(&data, queue_len))
         .build();
-    if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-        eprintln!("Error sending message: {:?}", why);
+    outbox.say(channel_id, response).await;
+    promote_waitlist(&outbox, channel_id, guild_id, &mut data).await;
+    drop(data);
+    outbox.send(context).await;
+}
+
+/// Removes the users from the queue along with their parties and queue messages, returns the new queue size
+pub(crate) fn remove_from_queue(data: &mut TypeMap, removed: &[User]) -> usize {
+    // players swapped out during a setup didn't abandon the queue
+    if data.get::<BotState>().unwrap().state == State::Queue {
+        let left: Vec<u64> = data.get::<UserQueue>().unwrap().iter().filter(|user| removed.contains(user)).map(|user| *user.id.as_u64()).collect();
+        for user_id in left {
+            data.get_mut::<UserQueue>().unwrap().retain(|user| *user.id.as_u64() != user_id);
+            record_queue_event(data, QueueEventKind::Leave, Some(user_id));
+        }
+    }
+    let user_queue: &mut Vec<User> = data.get_mut::<UserQueue>().unwrap();
+    user_queue.retain(|user| !removed.contains(user));
+    let queue_len = user_queue.len();
+    let parties: &mut Vec<Vec<User>> = data.get_mut::<Parties>().unwrap();
+    for party in parties.iter_mut() {
+        party.retain(|member| !removed.contains(member));
+    }
+    parties.retain(|party| party.len() > 1);
+    let queued_msgs: &mut HashMap<u64, String> = data.get_mut::<QueueMessages>().unwrap();
+    for user in removed {
+        queued_msgs.remove(user.id.as_u64());
+    }
+    let queue_expiry: &mut HashMap<u64, DateTime<Local>> = data.get_mut::<QueueExpiry>().unwrap();
+    for user in removed {
+        queue_expiry.remove(user.id.as_u64());
     }
+    let queue_join_times: &mut HashMap<u64, DateTime<Local>> = data.get_mut::<QueueJoinTimes>().unwrap();
+    for user in removed {
+        queue_join_times.remove(user.id.as_u64());
+    }
+    queue_len
 }
 
-pub(crate) async fn handle_map_list(context: Context, msg: Message) {
-    let data = context.data.write().await;
-    let maps: &Vec<String> = data.get::<Maps>().unwrap();
-    let map_str: String = maps.iter().map(|map| format!("- `{}`\n", map)).collect();
+pub(crate) async fn handle_presence_update(context: Context, new_data: PresenceUpdateEvent) {
+    let user_id = *new_data.presence.user_id.as_u64();
+    let (grace_minutes, went_offline) = {
+        let mut data = context.data.write().await;
+        let grace_minutes = match data.get::<Config>().unwrap().offline_grace_minutes {
+            Some(grace_minutes) => grace_minutes,
+            None => return,
+        };
+        let offline_since: &mut HashMap<u64, DateTime<Local>> = data.get_mut::<OfflineSince>().unwrap();
+        if new_data.presence.status != OnlineStatus::Offline {
+            offline_since.remove(&user_id);
+            return;
+        }
+        if offline_since.contains_key(&user_id) { return; }
+        let user_queue: &Vec<User> = data.get::<UserQueue>().unwrap();
+        if !user_queue.iter().any(|user| *user.id.as_u64() == user_id) { return; }
+        let went_offline = Local::now();
+        let offline_since: &mut HashMap<u64, DateTime<Local>> = data.get_mut::<OfflineSince>().unwrap();
+        offline_since.insert(user_id, went_offline);
+        (grace_minutes, went_offline)
+    };
+    task::sleep(Duration::from_secs(grace_minutes * 60)).await;
+    let outbox = Outbox::default();
+    let mut data = context.data.write().await;
+    let offline_since: &mut HashMap<u64, DateTime<Local>> = data.get_mut::<OfflineSince>().unwrap();
+    // the player came back online in the meantime, possibly going offline again with a newer timer
+    if offline_since.get(&user_id) != Some(&went_offline) { return; }
+    offline_since.remove(&user_id);
+    if data.get::<BotState>().unwrap().state != State::Queue { return; }
+    let user_queue: &Vec<User> = data.get::<UserQueue>().unwrap();
+    let user = match user_queue.iter().find(|user| *user.id.as_u64() == user_id) {
+        Some(user) => user.clone(),
+        None => return,
+    };
+    let queue_len = remove_from_queue(&mut data, std::slice::from_ref(&user));
+    let channel_id = match queue_channel(&data) {
+        Some(channel_id) => channel_id,
+        None => return,
+    };
+    let guild_id = new_data.guild_id;
     let response = MessageBuilder::new()
-        .push_line("Current map pool:")
-        .push(map_str)
+        .mention(&user)
+        .push(" went offline and has been removed from the queue")
+        .push(queue_size_note(&data, queue_len))
         .build();
-    if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-        eprintln!("Error sending message: {:?}", why);
+    outbox.say(channel_id, response).await;
+    promote_waitlist(&outbox, channel_id, guild_id, &mut data).await;
+    drop(data);
+    outbox.send(&context).await;
+}
+
+pub(crate) async fn idle_afk_check(context: &Context) {
+    let idle_minutes = match context.data.read().await.get::<Config>().unwrap().afk_check_idle_minutes {
+        Some(idle_minutes) => idle_minutes,
+        None => return,
+    };
+    println!("Idle AFK check feature started");
+    loop {
+        task::sleep(Duration::from_secs(60)).await;
+        let mut data = context.data.write().await;
+        if data.get::<BotState>().unwrap().state != State::Queue { continue; }
+        if data.get::<UserQueue>().unwrap().is_empty() { continue; }
+        let queue_activity: &mut Option<QueueActivityInfo> = data.get_mut::<QueueActivity>().unwrap();
+        let (channel_id, guild_id) = match queue_activity {
+            Some(activity) if Local::now().signed_duration_since(activity.updated) >= ChronoDuration::minutes(idle_minutes) => {
+                activity.updated = Local::now();
+                (activity.channel_id, activity.guild_id)
+            }
+            _ => continue,
+        };
+        drop(data);
+        start_afk_check(context, channel_id, guild_id).await;
     }
 }
 
-pub(crate) async fn handle_kick(context: Context, msg: Message) {
-    if !admin_check(&context, &msg, true).await { return; }
+pub(crate) async fn handle_here(context: Context, msg: Message) -> BotResult {
     let mut data = context.data.write().await;
-    let state: &mut StateContainer = data.get_mut::<BotState>().unwrap();
-    if state.state != State::Queue {
-        send_simple_tagged_msg(&context, &msg, " cannot `.kick` the queue after `.start`, use `.cancel` to start over if needed.", &msg.author).await;
-        return;
+    let afk_check: &mut Option<AfkCheck> = data.get_mut::<ActiveAfkCheck>().unwrap();
+    if let Some(afk_check) = afk_check {
+        if let Some(index) = afk_check.pending.iter().position(|user| user == &msg.author) {
+            afk_check.pending.remove(index);
+            send_simple_tagged_msg(&context, &msg, " confirmed, thanks!", &msg.author).await;
+            return Ok(());
+        }
     }
-    let user_queue: &mut Vec<User> = data.get_mut::<UserQueue>().unwrap();
-    let user = &msg.mentions[0];
-    if !user_queue.contains(&user) {
-        let response = MessageBuilder::new()
-            .mention(&msg.author)
-            .push(" is not in the queue.")
-            .build();
-        if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-            eprintln!("Error sending message: {:?}", why);
+    send_simple_tagged_msg(&context, &msg, " there is no AFK check waiting on you.", &msg.author).await;
+    Ok(())
+}
+
+pub(crate) async fn handle_afk_reaction(context: &Context, reaction: &Reaction) {
+    if reaction.emoji != ReactionType::Unicode(String::from("✅")) { return; }
+    let user_id = match reaction.user_id {
+        Some(user_id) => user_id,
+        None => return,
+    };
+    let mut data = context.data.write().await;
+    let afk_check: &mut Option<AfkCheck> = data.get_mut::<ActiveAfkCheck>().unwrap();
+    if let Some(afk_check) = afk_check {
+        if afk_check.message_id == *reaction.message_id.as_u64() {
+            afk_check.pending.retain(|user| user.id != user_id);
         }
-        return;
     }
-    let index = user_queue.iter().position(|r| r.id == user.id).unwrap();
-    user_queue.remove(index);
-    let response = MessageBuilder::new()
-        .mention(user)
-        .push(" has been kicked. Queue size: ")
-        .push(user_queue.len().to_string())
-        .push("/10")
-        .build();
-    if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-        eprintln!("Error sending message: {:?}", why);
+}
+
+pub(crate) async fn handle_schedule(context: Context, msg: Message) -> BotResult {
+    let split_content = msg.content.trim().splitn(4, ' ').collect::<Vec<_>>();
+    if split_content.len() == 1 {
+        let data = context.data.read().await;
+        let scheduled_scrims: &Vec<ScheduledScrim> = data.get::<ScheduledScrims>().unwrap();
+        if scheduled_scrims.is_empty() {
+            return Err(BotError::Usage(String::from(" there are no scheduled scrims.")));
+        }
+        let scrim_list: String = scheduled_scrims.iter()
+            .enumerate()
+            .map(|(i, scrim)| format!("{}. `{}` {} - {} player(s) signed up\n", i + 1, schedule_time(scrim).format("%Y-%m-%d %H:%M"), scrim.title, scrim.rsvps.len()))
+            .collect();
+        send_simple_tagged_msg(&context, &msg, &format!(" scheduled scrims:\n{}", scrim_list), &msg.author).await;
+        return Ok(());
+    }
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    if split_content[1].eq_ignore_ascii_case("cancel") {
+        let mut data = context.data.write().await;
+        let scheduled_scrims: &mut Vec<ScheduledScrim> = data.get_mut::<ScheduledScrims>().unwrap();
+        match split_content.get(2).and_then(|index| index.parse::<usize>().ok()) {
+            Some(index) if index >= 1 && index <= scheduled_scrims.len() => {
+                let scrim = scheduled_scrims.remove(index - 1);
+                write_to_file(String::from("scheduled_scrims.json"), serde_json::to_string(scheduled_scrims).unwrap()).await;
+                send_simple_tagged_msg(&context, &msg, &format!(" cancelled the scheduled scrim `{}`", scrim.title), &msg.author).await;
+            }
+            _ => {
+                send_simple_tagged_msg(&context, &msg, " invalid scrim number, see `.schedule` for the list. Example: `.schedule cancel 1`", &msg.author).await;
+            }
+        }
+        return Ok(());
     }
+    let start = split_content.get(1).zip(split_content.get(2))
+        .and_then(|(date, time)| NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M").ok())
+        .and_then(|start| Local.from_local_datetime(&start).single());
+    let start = match start {
+        Some(start) if start > Local::now() => start,
+        _ => {
+            return Err(BotError::Usage(String::from(" invalid date, it must be in the future. Example: `.schedule 2024-07-01 20:00 Friday scrims`")));
+        }
+    };
+    let title = split_content.get(3).map(|title| title.trim()).filter(|title| !title.is_empty()).unwrap_or("Scrim").to_string();
+    let rsvp_msg = match msg.channel_id.send_message(&context.http, |m| m
+        .embed(|e| e
+            .title(&title)
+            .description(format!("Starts {}\nReact with ✅ to sign up, signed up players are added to the queue when the scrim starts.", start.format("%Y-%m-%d %H:%M")))))
+        .await {
+        Ok(rsvp_msg) => rsvp_msg,
+        Err(why) => {
+            ops::report(&context, format!("Cannot send the schedule embed: {:?}", why)).await;
+            return Ok(());
+        }
+    };
+    if let Err(why) = delivery::react(&context.http, rsvp_msg.channel_id, rsvp_msg.id, ReactionType::Unicode(String::from("✅"))).await {
+        eprintln!("Error adding reaction: {:?}", why);
+    }
+    let scrim = ScheduledScrim {
+        message_id: *rsvp_msg.id.as_u64(),
+        channel_id: *msg.channel_id.as_u64(),
+        guild_id: msg.guild_id.map(|guild_id| *guild_id.as_u64()),
+        start: start.timestamp(),
+        title,
+        rsvps: Vec::new(),
+    };
+    let mut data = context.data.write().await;
+    let scheduled_scrims: &mut Vec<ScheduledScrim> = data.get_mut::<ScheduledScrims>().unwrap();
+    scheduled_scrims.push(scrim.clone());
+    write_to_file(String::from("scheduled_scrims.json"), serde_json::to_string(scheduled_scrims).unwrap()).await;
+    let context = context.clone();
+    tokio::spawn(async move { run_scheduled_scrim(&context, scrim.message_id).await });
+    Ok(())
 }
 
-pub(crate) async fn handle_add_map(context: Context, msg: Message) {
-    if !admin_check(&context, &msg, true).await { return; }
+fn schedule_time(scrim: &ScheduledScrim) -> DateTime<Local> {
+    Local.timestamp_opt(scrim.start, 0).single().unwrap_or_else(Local::now)
+}
+
+pub(crate) async fn handle_schedule_reaction(context: &Context, reaction: &Reaction, added: bool) {
+    if reaction.emoji != ReactionType::Unicode(String::from("✅")) { return; }
+    let user_id = match reaction.user_id {
+        Some(user_id) => user_id,
+        None => return,
+    };
+    if user_id == context.cache.current_user_id().await { return; }
     let mut data = context.data.write().await;
-    let maps: &mut Vec<String> = data.get_mut::<Maps>().unwrap();
-    if maps.len() >= 26 {
-        let response = MessageBuilder::new()
-            .mention(&msg.author)
-            .push(" unable to add map, max amount reached.")
-            .build();
-        if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-            eprintln!("Error sending message: {:?}", why);
+    let scheduled_scrims: &mut Vec<ScheduledScrim> = data.get_mut::<ScheduledScrims>().unwrap();
+    let scrim = match scheduled_scrims.iter_mut().find(|scrim| scrim.message_id == *reaction.message_id.as_u64()) {
+        Some(scrim) => scrim,
+        None => return,
+    };
+    if added && !scrim.rsvps.contains(user_id.as_u64()) {
+        scrim.rsvps.push(*user_id.as_u64());
+    } else if !added {
+        scrim.rsvps.retain(|id| id != user_id.as_u64());
+    }
+    write_to_file(String::from("scheduled_scrims.json"), serde_json::to_string(scheduled_scrims).unwrap()).await;
+}
+
+/// Restarts the timers of scrims scheduled before the bot was restarted
+pub(crate) async fn resume_scheduled_scrims(context: &Context) {
+    let message_ids: Vec<u64> = context.data.read().await.get::<ScheduledScrims>().unwrap()
+        .iter()
+        .map(|scrim| scrim.message_id)
+        .collect();
+    for message_id in message_ids {
+        let context = context.clone();
+        tokio::spawn(async move { run_scheduled_scrim(&context, message_id).await });
+    }
+}
+
+/// Restarts the `.join` duration timers of a restored queue
+pub(crate) async fn resume_queue_expiry(context: &Context) {
+    let data = context.data.read().await;
+    let channel_id = match queue_channel(&data) {
+        Some(channel_id) => channel_id,
+        None => return,
+    };
+    let guild_id = data.get::<QueueActivity>().unwrap().as_ref().and_then(|activity| activity.guild_id);
+    let user_queue: &Vec<User> = data.get::<UserQueue>().unwrap();
+    for (user_id, expires) in data.get::<QueueExpiry>().unwrap() {
+        let user = match user_queue.iter().find(|user| user.id.as_u64() == user_id) {
+            Some(user) => user.clone(),
+            None => continue,
+        };
+        let context = context.clone();
+        let expires = *expires;
+        tokio::spawn(async move {
+            task::sleep((expires - Local::now()).to_std().unwrap_or_default()).await;
+            expire_queue_join(&context, channel_id, guild_id, &user, expires).await;
+        });
+    }
+}
+
+/// Reminds signed up players before a scheduled scrim and adds them to the queue once it starts
+pub(crate) async fn run_scheduled_scrim(context: &Context, message_id: u64) {
+    let reminder_minutes = context.data.read().await.get::<Config>().unwrap().schedule_reminder_minutes.unwrap_or(15);
+    let scrim = match find_scheduled_scrim(context, message_id).await {
+        Some(scrim) => scrim,
+        None => return,
+    };
+    let reminder = schedule_time(&scrim) - ChronoDuration::minutes(reminder_minutes);
+    if reminder > Local::now() {
+        task::sleep((reminder - Local::now()).to_std().unwrap_or_default()).await;
+        let scrim = match find_scheduled_scrim(context, message_id).await {
+            Some(scrim) => scrim,
+            None => return,
+        };
+        if !scrim.rsvps.is_empty() {
+            let mentions: String = scrim.rsvps.iter().map(|id| format!("<@{}> ", id)).collect();
+            let response = MessageBuilder::new()
+                .push(mentions)
+                .push(format!("`{}` starts in {} minutes!", scrim.title, reminder_minutes))
+                .build();
+            delivery::say(&context.http, ChannelId(scrim.channel_id), &response).await;
+        }
+    }
+    let start = schedule_time(&scrim);
+    if start > Local::now() {
+        task::sleep((start - Local::now()).to_std().unwrap_or_default()).await;
+    }
+    let scrim = match find_scheduled_scrim(context, message_id).await {
+        Some(scrim) => scrim,
+        None => return,
+    };
+    // the users are looked up and checked like a `.join` before the bot data is locked
+    let mut signed_up: Vec<User> = Vec::new();
+    let mut rejected: Vec<String> = Vec::new();
+    for user_id in &scrim.rsvps {
+        let user = match UserId(*user_id).to_user(&context.http).await {
+            Ok(user) => user,
+            Err(_) => continue,
+        };
+        match queue_join_check(context, scrim.guild_id.map(GuildId), &[&user]).await {
+            Ok(()) => signed_up.push(user),
+            Err(BotError::Usage(text)) => rejected.push(format!("<@{}>{}", user_id, i18n::translate(&text))),
+            Err(why) => eprintln!("Error checking scheduled scrim player: {}", why),
         }
+    }
+    let mut data = context.data.write().await;
+    let scheduled_scrims: &mut Vec<ScheduledScrim> = data.get_mut::<ScheduledScrims>().unwrap();
+    let scrim = match scheduled_scrims.iter().position(|scrim| scrim.message_id == message_id) {
+        Some(index) => scheduled_scrims.remove(index),
+        None => return,
+    };
+    write_to_file(String::from("scheduled_scrims.json"), serde_json::to_string(scheduled_scrims).unwrap()).await;
+    let channel_id = ChannelId(scrim.channel_id);
+    let guild_id = scrim.guild_id.map(GuildId);
+    if data.get::<BotState>().unwrap().state != State::Queue {
+        drop(data);
+        let response = format!("`{}` is starting but a match setup is in progress, signed up players have to `.join` themselves.", scrim.title);
+        delivery::say(&context.http, channel_id, &response).await;
         return;
     }
-    let map_name: String = String::from(msg.content.trim().split(" ").take(2).collect::<Vec<_>>()[1]);
-    if maps.contains(&map_name) {
+    let outbox = Outbox::default();
+    outbox.say(channel_id, format!("`{}` is starting, adding signed up players to the queue.", scrim.title)).await;
+    let mut missing_riot_ids: Vec<u64> = Vec::new();
+    for user in signed_up {
+        if !data.get::<RiotIdCache>().unwrap().contains_key(user.id.as_u64()) {
+            missing_riot_ids.push(*user.id.as_u64());
+            continue;
+        }
+        add_to_queue(context, &outbox, &mut data, channel_id, guild_id, &user).await;
+    }
+    drop(data);
+    outbox.send(context).await;
+    if !missing_riot_ids.is_empty() {
+        let mentions: String = missing_riot_ids.iter().map(|id| format!("<@{}> ", id)).collect();
         let response = MessageBuilder::new()
-            .mention(&msg.author)
-            .push(" unable to add map, already exists.")
+            .push(mentions)
+            .push("riotid not found for your discord user, please use `.riotid <your riotid>` to assign one and `.join` the queue.")
             .build();
-        if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-            eprintln!("Error sending message: {:?}", why);
+        delivery::say(&context.http, channel_id, &response).await;
+    }
+    if !rejected.is_empty() {
+        delivery::say(&context.http, channel_id, format!("Not added to the queue:\n{}", rejected.join("\n"))).await;
+    }
+}
+
+async fn find_scheduled_scrim(context: &Context, message_id: u64) -> Option<ScheduledScrim> {
+    context.data.read().await.get::<ScheduledScrims>().unwrap()
+        .iter()
+        .find(|scrim| scrim.message_id == message_id)
+        .cloned()
+}
+
+pub(crate) async fn handle_status(context: Context, msg: Message) -> BotResult {
+    let data = context.data.read().await;
+    let bot_state: &StateContainer = data.get::<BotState>().unwrap();
+    let draft: &Draft = &data.get::<CurrentMatch>().unwrap().draft;
+    let queue_len = data.get::<UserQueue>().unwrap().len();
+    let (captain_a, captain_b) = setup_captains(&data);
+    let captain = |captain: &Option<User>| match captain {
+        Some(captain) => format!("<@{}> (Team {})", captain.id, team_label(&data, captain)),
+        None => String::from("-"),
+    };
+    let side = match (&bot_state.state, draft.team_b_start_side.as_str()) {
+        (State::SidePick { .. }, _) | (_, "") => String::from("-"),
+        _ => {
+            let (team_a_side, team_b_side) = start_sides(draft, data.get::<Config>().unwrap());
+            format!("Team A {}, Team B {}", team_a_side, team_b_side)
         }
-        return;
+    };
+    let mut status = format!("**State:** {}\n**Queue:** {}/{}\n", bot_state.state, queue_len, queue_size(&data));
+    if bot_state.state != State::Queue {
+        status.push_str(&format!("**Map:** {}\n**Captain A:** {}\n**Captain B:** {}\n**Current picker:** {}\n**Teams:** {} - {}\n**Sides:** {}\n",
+                                 draft.map.as_deref().unwrap_or("-"),
+                                 captain(&captain_a),
+                                 captain(&captain_b),
+                                 current_picker(&data).map(|user| format!("<@{}>", user.id)).unwrap_or_else(|| String::from("-")),
+                                 draft.team_a.len(),
+                                 draft.team_b.len(),
+                                 side));
     }
-    maps.push(String::from(&map_name));
-    write_to_file(String::from("maps.json"), serde_json::to_string(maps).unwrap()).await;
+    if let Some(reason) = data.get::<QueueLock>().unwrap() {
+        status.push_str(&format!("**Queue locked:** {}\n", reason));
+    }
+    if data.get::<ActiveMatch>().unwrap().is_some() {
+        status.push_str("**Match in progress:** yes, end it with `.end`\n");
+    }
+    if let Err(why) = msg.channel_id.send_message(&context.http, |m| m
+        .embed(|e| e
+            .title("Status")
+            .description(status)))
+        .await {
+        ops::report(&context, format!("Cannot send the status embed: {:?}", why)).await;
+    }
+    Ok(())
+}
+
+pub(crate) async fn handle_list(context: Context, msg: Message) -> BotResult {
+    let data = context.data.read().await;
+    let user_queue: &Vec<User> = data.get::<UserQueue>().unwrap();
+    let queue_msgs: &HashMap<u64, String> = data.get::<QueueMessages>().unwrap();
+    let queue_expiry: &HashMap<u64, DateTime<Local>> = data.get::<QueueExpiry>().unwrap();
+    let queue_join_times: &HashMap<u64, DateTime<Local>> = data.get::<QueueJoinTimes>().unwrap();
+    let riot_id_cache: &HashMap<u64, String> = data.get::<RiotIdCache>().unwrap();
+    let mut queue_list = String::new();
+    for (i, u) in user_queue.iter().enumerate() {
+        queue_list.push_str(format!("{}. <@{}>", i + 1, u.id).as_str());
+        if let Some(riot_id) = riot_id_cache.get(u.id.as_u64()) {
+            queue_list.push_str(format!(" `{}`", riot_id).as_str());
+        }
+        if let Some(joined) = queue_join_times.get(u.id.as_u64()) {
+            queue_list.push_str(format!(" - joined {}", joined.format("%H:%M")).as_str());
+        }
+        if let Some(expires) = queue_expiry.get(u.id.as_u64()) {
+            queue_list.push_str(format!(" (until {})", expires.format("%H:%M")).as_str());
+        }
+        if let Some(value) = queue_msgs.get(u.id.as_u64()) {
+            queue_list.push_str(format!("\n    _{}_", value).as_str());
+        }
+        queue_list.push('\n');
+    }
+    if queue_list.is_empty() {
+        queue_list.push_str("The queue is empty, type `.join` to join.");
+    }
+    let priority_players: &Vec<u64> = data.get::<PriorityPlayers>().unwrap();
+    let waitlist: String = data.get::<Waitlist>().unwrap()
+        .iter()
+        .enumerate()
+        .map(|(i, u)| if priority_players.contains(u.id.as_u64()) {
+            format!("{}. <@{}> (priority)\n", i + 1, u.id)
+        } else {
+            format!("{}. <@{}>\n", i + 1, u.id)
+        })
+        .collect();
+    let spectators: String = data.get::<Spectators>().unwrap()
+        .iter()
+        .map(|u| format!("- <@{}>\n", u.id))
+        .collect();
+    let bot_state: &StateContainer = data.get::<BotState>().unwrap();
+    if let Err(why) = msg.channel_id.send_message(&context.http, |m| m
+        .embed(|e| {
+            e.title(format!("Current queue size: {}/{}", user_queue.len(), queue_size(&data)))
+                .description(queue_list)
+                .footer(|f| f.text(format!("State: {}", bot_state.state)));
+            if !waitlist.is_empty() {
+                e.field("Waitlist", waitlist, false);
+            }
+            if !spectators.is_empty() {
+                e.field("Spectators", spectators, false);
+            }
+            e
+        }))
+        .await {
+        ops::report(&context, format!("Cannot send the queue list embed: {:?}", why)).await;
+    }
+    Ok(())
+}
+
+pub(crate) async fn handle_clear(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let mut data = context.data.write().await;
+    if !data.get::<UserQueue>().unwrap().is_empty() {
+        record_queue_event(&mut data, QueueEventKind::Clear, None);
+    }
+    let user_queue: &mut Vec<User> = data.get_mut::<UserQueue>().unwrap();
+    user_queue.clear();
+    let parties: &mut Vec<Vec<User>> = data.get_mut::<Parties>().unwrap();
+    parties.clear();
+    let waitlist: &mut Vec<User> = data.get_mut::<Waitlist>().unwrap();
+    waitlist.clear();
+    let queue_expiry: &mut HashMap<u64, DateTime<Local>> = data.get_mut::<QueueExpiry>().unwrap();
+    queue_expiry.clear();
+    let queue_join_times: &mut HashMap<u64, DateTime<Local>> = data.get_mut::<QueueJoinTimes>().unwrap();
+    queue_join_times.clear();
+    let response = MessageBuilder::new()
+        .mention(&msg.author)
+        .push(" cleared queue")
+        .build();
+    delivery::say(&context.http, msg.channel_id, &response).await;
+    audit_log(&context, &data, "Clear", format!("<@{}> cleared the queue", msg.author.id)).await;
+    Ok(())
+}
+
+pub(crate) async fn handle_lock(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let reason = msg.content
+        .split_once(' ')
+        .map(|(_, reason)| reason.trim())
+        .filter(|reason| !reason.is_empty())
+        .unwrap_or("an admin has paused joining")
+        .to_string();
+    let mut data = context.data.write().await;
+    let queue_lock: &mut Option<String> = data.get_mut::<QueueLock>().unwrap();
+    *queue_lock = Some(reason.clone());
+    let response = MessageBuilder::new()
+        .mention(&msg.author)
+        .push(" locked the queue, `.join` is disabled until `.unlock`. Reason: ")
+        .push(&reason)
+        .build();
+    delivery::say(&context.http, msg.channel_id, &response).await;
+    audit_log(&context, &data, "Lock", format!("<@{}> locked the queue: {}", msg.author.id, reason)).await;
+    Ok(())
+}
+
+pub(crate) async fn handle_unlock(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let mut data = context.data.write().await;
+    let queue_lock: &mut Option<String> = data.get_mut::<QueueLock>().unwrap();
+    if queue_lock.take().is_none() {
+        return Err(BotError::Usage(String::from(" the queue is not locked")));
+    }
+    let response = MessageBuilder::new()
+        .mention(&msg.author)
+        .push(" unlocked the queue, `.join` is enabled again")
+        .build();
+    delivery::say(&context.http, msg.channel_id, &response).await;
+    audit_log(&context, &data, "Unlock", format!("<@{}> unlocked the queue", msg.author.id)).await;
+    Ok(())
+}
+
+pub(crate) async fn handle_alias(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let mut data = context.data.write().await;
+    let split_content = msg.content.to_lowercase().split_whitespace().map(String::from).collect::<Vec<_>>();
+    let with_prefix = |name: &str| if name.starts_with('.') { name.to_string() } else { format!(".{}", name) };
+    let aliases: &mut HashMap<String, String> = data.get_mut::<CommandAliases>().unwrap();
+    match split_content.iter().skip(1).map(String::as_str).collect::<Vec<_>>().as_slice() {
+        [] => {
+            let config_aliases = data.get::<Config>().unwrap().aliases.clone().unwrap_or_default();
+            let aliases = data.get::<CommandAliases>().unwrap();
+            let mut alias_list: Vec<String> = config_aliases.iter()
+                .filter(|(alias, _)| !aliases.contains_key(*alias))
+                .chain(aliases.iter())
+                .map(|(alias, target)| format!("- `{}` → `{}`", alias, target))
+                .collect();
+            if alias_list.is_empty() {
+                return Err(BotError::Usage(String::from(" no aliases configured. Example: `.alias .q .join`")));
+            }
+            alias_list.sort();
+            send_simple_tagged_msg(&context, &msg, &format!(" command aliases:\n{}", alias_list.join("\n")), &msg.author).await;
+        }
+        ["remove", alias] => {
+            if aliases.remove(&with_prefix(alias)).is_none() {
+                return Err(BotError::Usage(String::from(" no runtime alias with that name, aliases from the config file have to be removed there.")));
+            }
+            write_to_file(String::from("aliases.json"), serde_json::to_string(aliases).unwrap()).await;
+            send_simple_tagged_msg(&context, &msg, &format!(" removed the `{}` alias", with_prefix(alias)), &msg.author).await;
+        }
+        [alias, target] => {
+            let alias = with_prefix(alias);
+            let target = with_prefix(target);
+            if Command::from_str(&alias).is_ok() {
+                return Err(BotError::Usage(format!(" `{}` is already a command and can't be used as an alias", alias)));
+            }
+            if Command::from_str(&target).is_err() {
+                return Err(BotError::Usage(format!(" `{}` is not a command", target)));
+            }
+            aliases.insert(alias.clone(), target.clone());
+            write_to_file(String::from("aliases.json"), serde_json::to_string(aliases).unwrap()).await;
+            send_simple_tagged_msg(&context, &msg, &format!(" `{}` is now an alias for `{}`", alias, target), &msg.author).await;
+        }
+        _ => {
+            send_simple_tagged_msg(&context, &msg, " invalid message formatting. Example: `.alias .q .join`, `.alias remove .q` or `.alias` to list aliases", &msg.author).await;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) async fn handle_set_config(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let (key, value) = match msg.content.trim().splitn(3, ' ').collect::<Vec<_>>().as_slice() {
+        [_, key, value] => (key.to_string(), value.trim().to_string()),
+        _ => {
+            return Err(BotError::Usage(String::from(" invalid message formatting. Example: `.setconfig autoclear_hour 4` or `.setconfig discord.team_a_channel_id none`")));
+        }
+    };
+    // setting a whole section like `discord` would replace the secrets in it too
+    if SECRET_CONFIG_KEYS.iter().any(|secret| *secret == key || secret.starts_with(&format!("{}.", key))) {
+        return Err(BotError::Usage(format!(" `{}` can only be changed in the config file", key)));
+    }
+    let mut data = context.data.write().await;
+    let in_setup = data.get::<BotState>().unwrap().state != State::Queue;
+    let config: &mut Config = data.get_mut::<Config>().unwrap();
+    let mut config_value = serde_json::to_value(&*config).unwrap();
+    let mut overrides = read_config_overrides();
+    let new_value = if value.eq_ignore_ascii_case("default") {
+        // back to the config file's value
+        let file_config = match read_config_file() {
+            Ok(file_config) => serde_json::to_value(file_config).unwrap(),
+            Err(why) => return Err(BotError::Usage(format!(" the config file could not be loaded. {}", why))),
+        };
+        key.split('.').try_fold(&file_config, |target, part| target.get(part)).cloned()
+    } else if value.eq_ignore_ascii_case("none") {
+        Some(serde_json::Value::Null)
+    } else {
+        Some(serde_yaml::from_str(&value).unwrap_or_else(|_| serde_json::Value::String(value.clone())))
+    };
+    let new_value = match new_value {
+        Some(new_value) if set_config_value(&mut config_value, &key, new_value.clone()) => new_value,
+        _ => return Err(BotError::Usage(format!(" unknown config key `{}`, see `.showconfig` for all keys", key))),
+    };
+    let mut updated = serde_json::from_value::<Config>(config_value.clone()).map(|config| (config, new_value));
+    if updated.is_err() {
+        // text options like post_setup_msg can look like numbers, retry with the raw string
+        let new_value = serde_json::Value::String(value.clone());
+        set_config_value(&mut config_value, &key, new_value.clone());
+        if let Ok(config) = serde_json::from_value::<Config>(config_value) {
+            updated = Ok((config, new_value));
+        }
+    }
+    match updated {
+        Ok((updated, new_value)) => {
+            if let Some(why) = config_change_error(config, &updated, in_setup) {
+                return Err(BotError::Usage(why));
+            }
+            *config = updated;
+            lowercase_aliases(config);
+            i18n::set_language(config.language.as_deref());
+            ops::set_channel(config.discord.ops_channel_id);
+            if value.eq_ignore_ascii_case("default") {
+                overrides.remove(&key);
+            } else {
+                overrides.insert(key.clone(), new_value);
+            }
+            write_to_file(String::from(CONFIG_OVERRIDES), serde_json::to_string_pretty(&overrides).unwrap()).await;
+            send_simple_tagged_msg(&context, &msg, &format!(" updated `{}`", key), &msg.author).await;
+            audit_log(&context, &data, "Config change", format!("<@{}> set `{}` to `{}`", msg.author.id, key, value)).await;
+            drop(data);
+            validate_config(&context, &context.cache.guilds().await).await;
+        }
+        Err(why) => {
+            send_simple_tagged_msg(&context, &msg, &format!(" invalid value for `{}`: {}", key, why), &msg.author).await;
+        }
+    }
+    Ok(())
+}
+
+/// Why a changed config can't replace the current one, checked by `.setconfig` and `.reloadconfig` before applying it
+fn config_change_error(current: &Config, updated: &Config, in_setup: bool) -> Option<String> {
+    if let Some(size) = updated.queue_size.filter(|size| !valid_queue_size(*size)) {
+        return Some(format!(" `queue_size` must be an even number from 2 to 10, not {}", size));
+    }
+    if in_setup && updated.queue_size != current.queue_size {
+        return Some(String::from(" `queue_size` can't be changed during a setup"));
+    }
+    None
+}
+
+pub(crate) async fn handle_reload_config(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let reloaded = match read_config().await {
+        Ok(reloaded) => reloaded,
+        Err(why) => {
+            return Err(BotError::Usage(format!(" the config could not be loaded, keeping the current config. {}", why)));
+        }
+    };
+    let mut data = context.data.write().await;
+    let in_setup = data.get::<BotState>().unwrap().state != State::Queue;
+    let config: &mut Config = data.get_mut::<Config>().unwrap();
+    if let Some(why) = config_change_error(config, &reloaded, in_setup) {
+        return Err(BotError::Usage(format!("{}, keeping the current config.", why)));
+    }
+    let mut response = String::from(" reloaded the config");
+    if reloaded.discord.token != config.discord.token || reloaded.offline_grace_minutes.is_some() != config.offline_grace_minutes.is_some() || reloaded.http_port != config.http_port {
+        response.push_str(", changes to the bot token, `offline_grace_minutes` or `http_port` require a restart");
+    }
+    *config = reloaded;
+    i18n::set_language(config.language.as_deref());
+    ops::set_channel(config.discord.ops_channel_id);
+    send_simple_tagged_msg(&context, &msg, &response, &msg.author).await;
+    audit_log(&context, &data, "Config change", format!("<@{}> reloaded the config file", msg.author.id)).await;
+    drop(data);
+    validate_config(&context, &context.cache.guilds().await).await;
+    Ok(())
+}
+
+pub(crate) async fn handle_show_config(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let data = context.data.read().await;
+    let mut config: Config = data.get::<Config>().unwrap().clone();
+    config.discord.token = String::from("<hidden>");
+    if config.api_key.is_some() {
+        config.api_key = Some(String::from("<hidden>"));
+    }
+    if config.dashboard_token.is_some() {
+        config.dashboard_token = Some(String::from("<hidden>"));
+    }
+    let mut overridden: Vec<String> = read_config_overrides().into_keys().map(|key| format!("`{}`", key)).collect();
+    overridden.sort();
+    let mut response = MessageBuilder::new();
+    response.push_codeblock(serde_yaml::to_string(&config).unwrap(), Some("yaml"));
+    if !overridden.is_empty() {
+        response.push(format!("Changed with `.setconfig`: {}", overridden.join(", ")));
+    }
+    delivery::say(&context.http, msg.channel_id, &response.build()).await;
+    Ok(())
+}
+
+pub(crate) async fn handle_help(context: Context, msg: Message) -> BotResult {
+    let mut commands = String::from("
+`.join` - Join the queue, add a note (max 50 char) i.e. `.join need to leave by 10pm EST`, add a duration to leave automatically i.e. `.join 90m`, tag friends to join as a party i.e. `.join @friend1 @friend2`
+`.leave` - Leave the queue or the waitlist
+`.list` - List the queue with Riot IDs, join times and notes, plus the waitlist, spectators and setup state
+`.riotid` - Set your riotid i.e. `.riotid Martige#NA1`, you can also DM it to the bot. Alt accounts: `.riotid add Smurf#EU1`, `.riotid list`, `.riotid use 2`, region: `.riotid region eu`
+`.maps` - Lists all maps available for map vote
+`.teamname` - Sets a custom team name when you are a captain i.e. `.teamname Your Team Name`, add a team emoji or embed color with `.teamname emoji 🔥` or `.teamname color #ff4655`, remove them with `none`
+`.here` - Confirm you're still here during an AFK check
+`.votekick` - Start a vote among queued players to kick an AFK player from the queue i.e. `.votekick @user`
+`.notify` - Get a one-time DM when the queue reaches a size i.e. `.notify 8`, turn it off with `.notify off`
+`.status` - Show the current setup state, map, captains and current picker
+`.schedule` - List scheduled scrims, react with ✅ on a scrim's message to sign up
+`.stats` - Show your wins, losses, dodge, no-show and commend counts and any queue cooldown, tag a user to see theirs i.e. `.stats @user`
+`.commend` - Commend a teammate from the last match i.e. `.commend @user`
+`.commends` - Show the most commended players
+`.last` - Show the last completed setup with its map, teams, Riot IDs, sides and result
+`.whois` - Show a player's Riot ID, region, team name, record and recent matches i.e. `.whois @user`
+_These are commands used during the `.start` process:_
+`.spectate` - Spectate the match, type it again to stop spectating
+`.coach` - Join a team as its coach without taking a player slot i.e. `.coach A` or `.coach B`
+`.captain` - Add yourself as a captain.
+`.captainpool` - Volunteer to captain in advance with `.captainpool join`, captains are then picked from queued pool members when the setup starts. `.captainpool leave` opts out, `.captainpool list` shows the pool
+`.pick` - If you are a captain, this is used to pick a player by tagging them i.e. `.pick @Martige`
+`.skip` - If you are a captain, pass the other captain's turn to yourself when they haven't picked for a while
+`.defense`/`.attack` - If you are Captain B, pick the starting side after the draft, reacting with 🛡️ or ⚔️ on the prompt works too
+`.coinflip` - During the side pick, flip a coin for the starting sides instead of Captain B picking (if you are a captain)
+`.end` - If you were a captain of the last match, end the match with an optional score (Team A first) i.e. `.end 13-7`
+`.needsub` - If you were a captain of the last match, request a sub for the tagged player on your team i.e. `.needsub @user`
+`.votecancel` - Start a vote to cancel the setup, it's cancelled if a majority of the players vote yes
+`.swap` - During the side pick or a match, swap a player from each team i.e. `.swap @playerA @playerB`, both captains must confirm unless an admin uses it
+`.transfercaptain` - If you are a captain, hand captaincy to a teammate i.e. `.transfercaptain @user`
+`.rematch` - If you were a captain of the last match, queue the same 10 players again and start a new map vote, the queue has to be empty
+`.redraft` - If you were a captain of the last match, take the same 10 players back to the captain pick on the same map for new captains and picks, `.redraft vote` runs a new map vote first
+`.noshow` - If you were a captain of the last match, report a player that didn't show up i.e. `.noshow @user`
+");
+    let admin_commands = String::from("
+_These are privileged admin commands:_
+`.start` - Start the match setup process
+`.kick` - Kick a player by mentioning them i.e. `.kick @user`
+`.addmap` - Add a map to the map vote with an optional image url i.e. `.addmap mapname https://image.url`
+`.removemap` - Remove a map from the map vote i.e. `.removemap mapname`
+`.mappool` - Switch the map vote to a saved map pool i.e. `.mappool competitive`, `.mappool all` or `.mappool custom <name>`, save the current maps with `.mappool save <name>`
+`.recoverqueue` - Manually set a queue, tag all users to add after the command
+`.clear` - Clear the queue
+`.lock` - Stop new players from joining the queue without clearing it, with an optional reason i.e. `.lock scrim night starting soon`
+`.unlock` - Allow players to join the queue again
+`.schedule` - Schedule a scrim with sign ups i.e. `.schedule 2024-07-01 20:00 Friday scrims`, cancel it with `.schedule cancel 1`
+`.setconfig` - Change a config option at runtime i.e. `.setconfig autoclear_hour 4` or `.setconfig discord.team_a_channel_id none`, `default` goes back to the config file's value
+`.showconfig` - Show the current config
+`.reloadconfig` - Reload the config file without restarting the bot or losing the queue
+`.alias` - Add a command alias i.e. `.alias .q .join`, remove it with `.alias remove .q`, list aliases with `.alias`
+`.setstate` - Force the setup into another state if it gets stuck i.e. `.setstate draft`, states are `queue`, `captainpick`, `draft` and `sidepick`
+`.replace` - Replace a queued player that disappeared during the setup with someone outside the queue i.e. `.replace @missing @substitute`
+`.forcepick` - Make the current pick for an AFK captain i.e. `.forcepick @user`
+`.forcecaptain` - Set a team's captain during the captain pick i.e. `.forcecaptain @user A` or `.forcecaptain @user B`
+`.forceteams` - Skip the captain pick and draft and set both teams directly, captains first i.e. `.forceteams @a @b @c @d @e | @f @g @h @i @j`
+`.cancel` - Cancels `.start` process & retains current queue, tag players that dodged to remove them and apply a queue cooldown i.e. `.cancel @user`
+`.strike` - Give a player a strike with a reason i.e. `.strike @user toxic in voice chat`, reaching `strike_threshold` strikes bans them from the queue
+`.strikes` - List a player's strikes i.e. `.strikes @user`, take one back with `.strikes @user remove 1`
+`.analytics` - Show the busiest queue hours and days, average time to fill the queue and abandonment rates for the last 30 days, or another number of days i.e. `.analytics 7`
+`.activity` - Show how often a member joined the queue, played, dodged, got kicked or struck over the last 30 and 90 days i.e. `.activity @user`
+`.export` - Upload the match history or player stats as a csv file i.e. `.export matches` or `.export stats`, add `dm` to get it as a direct message
+`.backup` - Get a backup of Riot IDs, team names, maps, player stats, bans, match history and aliases as a direct message
+`.restore` - Load a backup file attached to the message, replacing the current data i.e. `.restore` then `.restore confirm`
+`.setriotid` - Set or fix another user's Riot ID i.e. `.setriotid @user Martige#NA1`
+`.importriotids` - Set Riot IDs in bulk from an attached csv (`discord id or mention,Riot ID` per line) or json (`{\"discord id\": \"Name#TAG\"}`) file
+    ");
+    if admin_check(&context, &msg, false).await {
+        commands.push_str(&admin_commands)
+    } else if moderator_check(&context, &msg).await {
+        commands.push_str("
+_These are moderator commands:_
+`.kick` - Kick a player by mentioning them i.e. `.kick @user`
+`.clear` - Clear the queue
+`.cancel` - Cancels `.start` process & retains current queue, tag players that dodged to remove them and apply a queue cooldown i.e. `.cancel @user`
+    ")
+    }
+    let response = MessageBuilder::new()
+        .push(commands)
+        .build();
+    if let Ok(channel) = &msg.author.create_dm_channel(&context.http).await {
+        delivery::say(&context.http, channel.id, &response).await;
+    } else {
+        eprintln!("Error sending .help dm");
+    }
+    Ok(())
+}
+
+pub(crate) async fn handle_recover_queue(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    {
+        let mut data = context.data.write().await;
+        let user_queue: &mut Vec<User> = data.get_mut::<UserQueue>().unwrap();
+        user_queue.clear();
+        let parties: &mut Vec<Vec<User>> = data.get_mut::<Parties>().unwrap();
+        parties.clear();
+    }
+    for mention in &msg.mentions {
+        handle_join(&context, &msg, mention).await
+    }
+    Ok(())
+}
+
+pub(crate) async fn handle_start(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    start_setup(context, msg).await
+}
+
+/// Lets an admin or a captain of the last match queue its 10 players again and start a new map vote right away
+pub(crate) async fn handle_rematch(context: Context, msg: Message) -> BotResult {
+    let is_admin = admin_check(&context, &msg, false).await;
+    requeue_join_check(&context, &msg).await?;
+    {
+        let mut data = context.data.write().await;
+        requeue_last_match(&mut data, &msg, is_admin, ".rematch")?;
+        audit_log(&context, &data, "Rematch", format!("<@{}> queued the last match's players for a rematch", msg.author.id)).await;
+    }
+    send_simple_msg(&context, &msg, "Rematch! The last match's players are back in the queue.").await;
+    start_setup(context, msg).await
+}
+
+/// Takes the last match's players back to the captain pick on the same map, `.redraft vote` runs a new map vote first
+pub(crate) async fn handle_redraft(context: Context, msg: Message) -> BotResult {
+    let is_admin = admin_check(&context, &msg, false).await;
+    let vote = match msg.content.split_whitespace().nth(1) {
+        None => false,
+        Some("vote") => true,
+        Some(_) => return Err(BotError::Usage(String::from(" invalid message formatting. Example: `.redraft` to keep the map or `.redraft vote` for a new map vote"))),
+    };
+    let players = requeue_join_check(&context, &msg).await?;
+    let captain_role_id = {
+        let data = context.data.read().await;
+        enabled_id(&data, data.get::<Config>().unwrap().discord.captain_role_id)
+    };
+    let mut excluded = ineligible_captains(&context, msg.guild_id, captain_role_id, &players).await;
+    let mut data = context.data.write().await;
+    let last_match = requeue_last_match(&mut data, &msg, is_admin, ".redraft")?;
+    audit_log(&context, &data, "Redraft", format!("<@{}> sent the last match's players back to the captain pick", msg.author.id)).await;
+    let map = match last_match.map.clone() {
+        Some(map) if !vote => map,
+        _ => {
+            drop(data);
+            send_simple_msg(&context, &msg, "Redraft! The last match's players are back in the queue for a new map vote.").await;
+            return start_setup(context, msg).await;
+        }
+    };
+    send_simple_msg(&context, &msg, &format!("Redraft! The last match's players are back for new captains and picks on `{}`.", map)).await;
+    // the previous captains sit this one out so the teams actually change
+    excluded.extend(last_match.captain_a.iter().chain(last_match.captain_b.iter()).cloned());
+    let players = data.get::<UserQueue>().unwrap().clone();
+    data.get_mut::<CurrentMatch>().unwrap().start(players);
+    start_setup_timer(&context, msg.channel_id, &mut data);
+    record_queue_event(&mut data, QueueEventKind::Start, None);
+    begin_captain_pick(&context, msg.channel_id, &mut data, map, &excluded).await;
+    Ok(())
+}
+
+/// Runs the `.join` checks for the last match's players before they go back in the queue, returns the players
+async fn requeue_join_check(context: &Context, msg: &Message) -> BotResult<Vec<User>> {
+    let players: Vec<User> = match context.data.read().await.get::<LastMatch>().unwrap() {
+        Some(last_match) => last_match.draft.team_a.iter().chain(last_match.draft.team_b.iter()).cloned().collect(),
+        // requeue_last_match reports the missing match
+        None => return Ok(Vec::new()),
+    };
+    queue_join_check(context, msg.guild_id, &players.iter().collect::<Vec<&User>>()).await?;
+    Ok(players)
+}
+
+/// Puts the last match's 10 players back in the empty queue for `.rematch` and `.redraft`, returns the last match
+fn requeue_last_match(data: &mut TypeMap, msg: &Message, is_admin: bool, command: &str) -> BotResult<Draft> {
+    if data.get::<BotState>().unwrap().state != State::Queue {
+        return Err(BotError::Usage(String::from(" a setup is already in progress")));
+    }
+    let last_match = data.get::<LastMatch>().unwrap().as_ref().map(|last_match| last_match.draft.clone())
+        .ok_or_else(|| BotError::Usage(String::from(" there is no completed setup to play again")))?;
+    let is_captain = last_match.captain_a.as_ref() == Some(&msg.author) || last_match.captain_b.as_ref() == Some(&msg.author);
+    if !is_admin && !is_captain {
+        return Err(BotError::Usage(format!(" only admins and captains of the last match can use `{}`.", command)));
+    }
+    if !data.get::<UserQueue>().unwrap().is_empty() {
+        return Err(BotError::Usage(format!(" the queue already has players, `{}` only works while the queue is empty", command)));
+    }
+    let players: Vec<User> = last_match.team_a.iter().chain(last_match.team_b.iter()).cloned().collect();
+    if players.len() != queue_size(data) {
+        return Err(BotError::Usage(format!(" the last match had {} players, {} are needed", players.len(), queue_size(data))));
+    }
+    data.get_mut::<Waitlist>().unwrap().retain(|user| !players.contains(user));
+    data.get_mut::<Parties>().unwrap().clear();
+    let queue_join_times: &mut HashMap<u64, DateTime<Local>> = data.get_mut::<QueueJoinTimes>().unwrap();
+    for user in &players {
+        queue_join_times.insert(*user.id.as_u64(), Local::now());
+    }
+    data.insert::<UserQueue>(players);
+    Ok(last_match)
+}
+
+/// Posts the map vote for a full queue, the vote is counted by a separate task so other commands keep working meanwhile
+async fn start_setup(context: Context, msg: Message) -> BotResult {
+    let mut data = context.data.write().await;
+    let bot_state: &StateContainer = data.get::<BotState>().unwrap();
+    if bot_state.state != State::Queue {
+        return Err(BotError::Usage(String::from(" `.start` command has already been entered")));
+    }
+    let user_queue: &Vec<User> = data.get::<UserQueue>().unwrap();
+    if user_queue.len() != queue_size(&data) {
+        let response = MessageBuilder::new()
+            .mention(&msg.author)
+            .push(" the queue is not full yet")
+            .build();
+        delivery::say(&context.http, msg.channel_id, &response).await;
+        return Ok(());
+    }
+    let map_count = data.get::<Maps>().unwrap().len();
+    if map_count == 0 || map_count > 26 {
+        return Err(BotError::Usage(format!(" cannot start a map vote with {} maps, an admin needs to configure between 1 and 26 maps", map_count)));
+    }
+    let queue_msgs: &HashMap<u64, String> = data.get::<QueueMessages>().unwrap();
+    let user_queue_mention: String = user_queue
+        .iter()
+        .map(|user| match queue_msgs.get(user.id.as_u64()) {
+            Some(note) => format!("- <@{}>: `{}`\n", user.id, note),
+            None => format!("- <@{}>\n", user.id),
+        })
+        .collect();
+    let mut response = MessageBuilder::new();
+    response
+        .push(&user_queue_mention)
+        .push_bold_line("Scrim setup is starting...");
+    if let Some((latest, average)) = fill_times(&data) {
+        response.push_line(format!("Queue filled in {} (average {} over the last queues)", format_fill_time(latest), format_fill_time(average)));
+    }
+    let response = response.build();
+    delivery::say(&context.http, msg.channel_id, &response).await;
+    audit_log(&context, &data, "Start", format!("<@{}> started the setup with {}", msg.author.id, user_queue_mention.replace('\n', " "))).await;
+    let bumped: Vec<u64> = data.get::<Waitlist>().unwrap().iter().map(|u| *u.id.as_u64()).collect();
+    let priority_players: &mut Vec<u64> = data.get_mut::<PriorityPlayers>().unwrap();
+    for user_id in bumped {
+        if !priority_players.contains(&user_id) {
+            priority_players.push(user_id);
+        }
+    }
+    let bot_state: &mut StateContainer = data.get_mut::<BotState>().unwrap();
+    bot_state.state = State::MapPick;
+    let players = data.get::<UserQueue>().unwrap().clone();
+    data.get_mut::<CurrentMatch>().unwrap().start(players);
+    start_setup_timer(&context, msg.channel_id, &mut data);
+    record_queue_event(&mut data, QueueEventKind::Start, None);
+    let afk_check: &mut Option<AfkCheck> = data.get_mut::<ActiveAfkCheck>().unwrap();
+    *afk_check = None;
+    let maps: &Vec<String> = data.get::<Maps>().unwrap();
+    let mut unicode_to_maps: HashMap<String, String> = HashMap::new();
+    let a_to_z = ('a'..='z').collect::<Vec<_>>();
+    let unicode_emoji_map = populate_unicode_emojis().await;
+    for (i, map) in maps.iter().enumerate() {
+        unicode_to_maps.insert(String::from(unicode_emoji_map.get(&a_to_z[i]).unwrap()), String::from(map));
+    }
+    let emoji_suffixes = a_to_z[..maps.len()].to_vec();
+    let vote_text: String = emoji_suffixes
+        .iter()
+        .enumerate()
+        .map(|(i, c)| format!(":regional_indicator_{}: `{}`\n", c, &maps[i]))
+        .collect();
+    let vote_seconds = data.get::<Config>().unwrap().map_vote_seconds.unwrap_or(60).max(10);
+    let cancel = data.get::<SetupCancel>().unwrap().clone();
+    drop(data);
+    // without a working vote message the setup can't go on, so it goes back to the full queue
+    let vote_msg = match msg.channel_id.send_message(&context.http, |m| m
+        .embed(|e| e
+            .title("Map Vote")
+            .description(vote_text)))
+        .await {
+        Ok(vote_msg) => vote_msg,
+        Err(why) => {
+            reset_failed_map_vote(&context, &cancel).await;
+            return Err(why.into());
+        }
+    };
+    let reactions: Vec<ReactionType> = emoji_suffixes.iter().map(|c| ReactionType::Unicode(String::from(unicode_emoji_map.get(c).unwrap()))).collect();
+    if let Err(why) = delivery::react_all(&context.http, vote_msg.channel_id, vote_msg.id, reactions).await {
+        reset_failed_map_vote(&context, &cancel).await;
+        if let Err(why) = vote_msg.delete(&context.http).await {
+            eprintln!("Error deleting the map vote: {:?}", why);
+        }
+        return Err(why.into());
+    }
+    tokio::spawn(async move {
+        let mut reminder = None;
+        let result = tokio::select! {
+            result = run_map_vote(&context, &msg, &vote_msg, &mut reminder, unicode_to_maps, vote_seconds, &cancel) => result,
+            _ = cancel.cancelled() => Ok(()),
+        };
+        if cancel.is_cancelled() {
+            for message in std::iter::once(&vote_msg).chain(reminder.as_ref()) {
+                if let Err(why) = message.delete(&context.http).await {
+                    eprintln!("Error deleting the map vote: {:?}", why);
+                }
+            }
+        }
+        if let Err(why) = result {
+            handle_command_error(&context, &msg, why).await;
+        }
+    });
+    Ok(())
+}
+
+/// The map with the most votes and the announcement for it, ties and a vote without votes are decided at random
+fn tally_map_vote(maps: &[String], results: Vec<ReactionResult>) -> (String, String) {
+    let max_count = results
+        .iter()
+        .map(|result| result.count)
+        .max()
+        .unwrap_or(0);
+    let final_results: Vec<ReactionResult> = results
+        .into_iter()
+        .filter(|m| m.count == max_count)
+        .collect();
+    if final_results.is_empty() {
+        let map = maps.get(rand::thread_rng().gen_range(0, maps.len())).unwrap();
+        (String::from(map), format!("No votes were counted, `{}` was selected at random", map))
+    } else if final_results.len() > 1 {
+        let map = &final_results.get(rand::thread_rng().gen_range(0, final_results.len())).unwrap().map;
+        (String::from(map), format!("Maps were tied, `{}` was selected at random", map))
+    } else {
+        let map = &final_results[0].map;
+        (String::from(map), format!("Map vote has concluded. `{}` will be played", map))
+    }
+}
+
+/// A map vote that couldn't be posted or counted goes back to the full queue so `.start` can be run again,
+/// unless the setup was cancelled meanwhile
+async fn reset_failed_map_vote(context: &Context, cancel: &CancellationToken) {
+    let mut data = context.data.write().await;
+    if !cancel.is_cancelled() && data.get::<BotState>().unwrap().state == State::MapPick {
+        reset_setup(&mut data);
+    }
+}
+
+/// Replaces the cancellation token of the previous setup and starts the `setup_timeout_minutes` timer for a new one
+fn start_setup_timer(context: &Context, channel_id: ChannelId, data: &mut TypeMap) {
+    data.get::<SetupCancel>().unwrap().cancel();
+    let cancel = CancellationToken::new();
+    data.insert::<SetupCancel>(cancel.clone());
+    let timeout_minutes = match data.get::<Config>().unwrap().setup_timeout_minutes {
+        Some(timeout_minutes) if timeout_minutes > 0 => timeout_minutes,
+        _ => return,
+    };
+    let context = context.clone();
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = task::sleep(Duration::from_secs(timeout_minutes * 60)) => {}
+            _ = cancel.cancelled() => return,
+        }
+        let mut data = context.data.write().await;
+        let state = data.get::<BotState>().unwrap().state.clone();
+        if cancel.is_cancelled() || state == State::Queue || state == State::Ready { return; }
+        reset_setup(&mut data);
+        let response = MessageBuilder::new()
+            .push_bold(format!("The setup was stuck in the {} phase for {} minutes and has been cancelled.", state, timeout_minutes))
+            .push(" The queue is kept, an admin can run `.start` to try again.")
+            .build();
+        delivery::say(&context.http, channel_id, &response).await;
+        audit_log(&context, &data, "Setup timeout", format!("The setup was cancelled after {} minutes in the {} phase", timeout_minutes, state)).await;
+    });
+}
+
+/// Waits out the map vote without holding the bot data, then counts the reactions and moves on to the captain pick.
+/// `reminder` gets the "Voting will end" message so a cancelled vote can delete it
+async fn run_map_vote(context: &Context, msg: &Message, vote_msg: &Message, reminder: &mut Option<Message>, unicode_to_maps: HashMap<String, String>, vote_seconds: u64, cancel: &CancellationToken) -> BotResult {
+    task::sleep(Duration::from_secs(vote_seconds - 10)).await;
+    let response = MessageBuilder::new()
+        .push("Voting will end in 10 seconds")
+        .build();
+    *reminder = delivery::say(&context.http, msg.channel_id, &response).await;
+    task::sleep(Duration::from_secs(10)).await;
+    let updated_vote_msg = match vote_msg.channel_id.message(&context.http, vote_msg.id).await {
+        Ok(updated_vote_msg) => updated_vote_msg,
+        Err(why) => {
+            reset_failed_map_vote(context, cancel).await;
+            return Err(why.into());
+        }
+    };
+    let (captain_role_id, players) = {
+        let data = context.data.read().await;
+        (enabled_id(&data, data.get::<Config>().unwrap().discord.captain_role_id), data.get::<UserQueue>().unwrap().clone())
+    };
+    let ineligible = ineligible_captains(context, msg.guild_id, captain_role_id, &players).await;
+    let mut data = context.data.write().await;
+    // a `.cancel` may have come in while the results were being fetched
+    if cancel.is_cancelled() || data.get::<BotState>().unwrap().state != State::MapPick {
+        return Ok(());
+    }
+    let results: Vec<ReactionResult> = updated_vote_msg.reactions
+        .iter()
+        .filter_map(|reaction| unicode_to_maps.get(reaction.reaction_type.to_string().as_str())
+            .map(|map| ReactionResult { count: reaction.count, map: String::from(map) }))
+        .collect();
+    conclude_map_vote(context, msg.channel_id, &mut data, results, &ineligible).await;
+    Ok(())
+}
+
+/// Announces the map that won the vote and starts the captain pick on it, `excluded` is passed on to `begin_captain_pick`
+async fn conclude_map_vote(discord: &impl Discord, channel_id: ChannelId, data: &mut TypeMap, results: Vec<ReactionResult>, excluded: &[User]) {
+    let (map, description) = tally_map_vote(data.get::<Maps>().unwrap(), results);
+    let mut embed = CreateEmbed::default();
+    embed.title(&map).description(description);
+    if let Some(url) = data.get::<MapImages>().unwrap().get(&map) {
+        embed.thumbnail(url);
+    }
+    discord.send_embed(channel_id, embed).await;
+    audit_log(discord, data, "Map vote", format!("`{}` was picked", map)).await;
+    begin_captain_pick(discord, channel_id, data, map, excluded).await;
+}
+
+/// Starts the captain pick on `map`, picking the captains automatically with `captain_selection: rating` or from the
+/// captain pool. Players in `excluded` are never picked automatically, callers add the ones `ineligible_captains` returns
+pub(crate) async fn begin_captain_pick(discord: &impl Discord, channel_id: ChannelId, data: &mut TypeMap, map: String, excluded: &[User]) {
+    let bot_state: &mut StateContainer = data.get_mut::<BotState>().unwrap();
+    bot_state.state = State::captain_pick();
+    let draft: &mut Draft = &mut data.get_mut::<CurrentMatch>().unwrap().draft;
+    draft.map = Some(map);
+    draft.captain_a = None;
+    draft.captain_b = None;
+    draft.team_a = Vec::new();
+    draft.team_b = Vec::new();
+    draft.skipped_turns = Vec::new();
+    let mut eligible: Vec<User> = data.get::<UserQueue>().unwrap().iter().filter(|user| !excluded.contains(user)).cloned().collect();
+    // without two eligible players the captains are left to `.captain` like the manual selection
+    if data.get::<Config>().unwrap().captain_selection.as_deref() == Some("rating") && eligible.len() >= 2 {
+        sort_by_rating(data, &mut eligible);
+        // the lower rated of the two captains gets the first pick
+        let captains = Captains { a: eligible[1].clone(), b: eligible[0].clone() };
+        announce(discord, channel_id, "Starting captain pick phase. The two highest rated players are the captains, the lower rated one picks first.").await;
+        start_draft(discord, channel_id, data, captains, false).await;
+        return;
+    }
+    match pool_captains(data, excluded).as_slice() {
+        [captain_a, captain_b] => {
+            let captains = Captains { a: captain_a.clone(), b: captain_b.clone() };
+            announce(discord, channel_id, "Starting captain pick phase. Both captains were picked from the captain pool.").await;
+            start_draft(discord, channel_id, data, captains, true).await;
+        }
+        [captain] => {
+            data.get_mut::<BotState>().unwrap().state = State::CaptainPick { captain_a: Some(captain.clone()), captain_b: None };
+            announce(discord, channel_id, "Starting captain pick phase.").await;
+            announce_tagged(discord, channel_id, " was picked from the captain pool, one more user types `.captain` to start picking teams.", captain).await;
+        }
+        _ => announce(discord, channel_id, "Starting captain pick phase. Two users type `.captain` to start picking teams.").await,
+    }
+}
+
+/// The players without the `captain_role_id` role. Role checks can go to Discord, so this runs before the bot data is locked
+async fn ineligible_captains(guild: &impl Guild, guild_id: Option<GuildId>, captain_role_id: Option<u64>, players: &[User]) -> Vec<User> {
+    let mut ineligible = Vec::new();
+    for user in players {
+        if !captain_eligible(guild, guild_id, captain_role_id, user).await {
+            ineligible.push(user.clone());
+        }
+    }
+    ineligible
+}
+
+/// Whether the user may be a captain, everyone can unless `captain_role_id` is set
+async fn captain_eligible(guild: &impl Guild, guild_id: Option<GuildId>, captain_role_id: Option<u64>, user: &User) -> bool {
+    match (captain_role_id, guild_id) {
+        (None, _) => true,
+        (Some(role_id), Some(guild_id)) => has_any_role(guild, guild_id, user, [role_id]).await,
+        (Some(_), None) => false,
+    }
+}
+
+/// Up to two queued `.captainpool` members, picked at random or the highest rated with `captain_pool_selection: rating`
+fn pool_captains(data: &TypeMap, excluded: &[User]) -> Vec<User> {
+    let pool: &Vec<u64> = data.get::<CaptainPool>().unwrap();
+    let mut candidates: Vec<User> = data.get::<UserQueue>().unwrap()
+        .iter()
+        .filter(|user| pool.contains(user.id.as_u64()) && !excluded.contains(user))
+        .cloned()
+        .collect();
+    if data.get::<Config>().unwrap().captain_pool_selection.as_deref() == Some("rating") {
+        sort_by_rating(data, &mut candidates);
+    } else {
+        candidates.shuffle(&mut rand::thread_rng());
+    }
+    candidates.truncate(2);
+    candidates
+}
+
+/// Highest rated first
+fn sort_by_rating(data: &TypeMap, users: &mut [User]) {
+    users.sort_by(|a, b| player_rating(data, *b.id.as_u64()).total_cmp(&player_rating(data, *a.id.as_u64())));
+}
+
+/// Win rate from the reported results, pulled towards 50% while a player has few matches so one lucky win doesn't top the list
+pub(crate) fn player_rating(data: &TypeMap, user_id: u64) -> f64 {
+    let stats = data.get::<PlayerStatsCache>().unwrap().get(&user_id).cloned().unwrap_or_default();
+    (stats.wins as f64 + 5.0) / ((stats.wins + stats.losses) as f64 + 10.0)
+}
+
+pub(crate) async fn handle_captain_pool(context: Context, msg: Message) -> BotResult {
+    let mut data = context.data.write().await;
+    let user_id = *msg.author.id.as_u64();
+    let captain_pool: &mut Vec<u64> = data.get_mut::<CaptainPool>().unwrap();
+    match msg.content.split_whitespace().nth(1).map(|arg| arg.to_lowercase()).as_deref() {
+        Some("join") => {
+            if captain_pool.contains(&user_id) {
+                return Err(BotError::Usage(String::from(" you're already in the captain pool")));
+            }
+            captain_pool.push(user_id);
+            write_to_file(String::from("captain_pool.json"), serde_json::to_string(captain_pool).unwrap()).await;
+            send_simple_tagged_msg(&context, &msg, " joined the captain pool, you may be picked as a captain when you're in a starting queue.", &msg.author).await;
+        }
+        Some("leave") => {
+            if !captain_pool.contains(&user_id) {
+                return Err(BotError::Usage(String::from(" you're not in the captain pool")));
+            }
+            captain_pool.retain(|id| *id != user_id);
+            write_to_file(String::from("captain_pool.json"), serde_json::to_string(captain_pool).unwrap()).await;
+            send_simple_tagged_msg(&context, &msg, " left the captain pool.", &msg.author).await;
+        }
+        None | Some("list") => {
+            let members: String = captain_pool.iter().map(|id| format!("- <@{}>\n", id)).collect();
+            let description = if members.is_empty() { String::from("The captain pool is empty, type `.captainpool join` to volunteer") } else { members };
+            if let Err(why) = msg.channel_id.send_message(&context.http, |m| m
+                .embed(|e| e
+                    .title("Captain Pool")
+                    .description(description)))
+                .await {
+                ops::report(&context, format!("Cannot send the captain pool embed: {:?}", why)).await;
+            }
+        }
+        _ => return Err(BotError::Usage(String::from(" invalid message formatting. Example: `.captainpool join`, `.captainpool leave` or `.captainpool list`"))),
+    }
+    Ok(())
+}
+
+
+pub(crate) async fn handle_captain(context: Context, msg: Message) -> BotResult {
+    let captain_role_id = {
+        let data = context.data.read().await;
+        enabled_id(&data, data.get::<Config>().unwrap().discord.captain_role_id)
+    };
+    if !captain_eligible(&context, msg.guild_id, captain_role_id, &msg.author).await {
+        return Err(BotError::Usage(String::from(" only members with the captain role can be captains")));
+    }
+    let mut data = context.data.write().await;
+    claim_captain(&context, msg.channel_id, &mut data, &msg.author).await
+}
+
+/// Makes the user a captain, the draft starts with a coin flip for the first pick once both captains are set
+pub(crate) async fn claim_captain(messenger: &impl Messenger, channel_id: ChannelId, data: &mut TypeMap, user: &User) -> BotResult {
+    let (captain_a, captain_b) = match &mut data.get_mut::<BotState>().unwrap().state {
+        State::CaptainPick { captain_a, captain_b } => (captain_a, captain_b),
+        _ => return Err(BotError::Usage(String::from(" command ignored, not in the captain pick phase"))),
+    };
+    if captain_a.as_ref() == Some(user) || captain_b.as_ref() == Some(user) {
+        return Err(BotError::Usage(String::from(" you're already a captain!")));
+    }
+    announce_tagged(messenger, channel_id, " is set as captain.", user).await;
+    if captain_a.is_none() {
+        *captain_a = Some(user.clone());
+    } else {
+        *captain_b = Some(user.clone());
+    }
+    if let (Some(a), Some(b)) = (captain_a.clone(), captain_b.clone()) {
+        start_draft(messenger, channel_id, data, Captains { a, b }, true).await;
+    }
+    Ok(())
+}
+
+pub(crate) async fn handle_force_captain(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let mut data = context.data.write().await;
+    let team = msg.content.split_whitespace().last().unwrap_or("").to_uppercase();
+    let user = match msg.mentions.first() {
+        Some(user) if team == "A" || team == "B" => user.clone(),
+        _ => {
+            return Err(BotError::Usage(String::from(" invalid message formatting. Example: `.forcecaptain @user A` or `.forcecaptain @user B`")));
+        }
+    };
+    if !data.get::<UserQueue>().unwrap().contains(&user) {
+        return Err(BotError::Usage(String::from(" this user is not in the queue")));
+    }
+    let (captain_a, captain_b) = match &mut data.get_mut::<BotState>().unwrap().state {
+        State::CaptainPick { captain_a, captain_b } => (captain_a, captain_b),
+        _ => return Err(BotError::Usage(String::from(" command ignored, not in the captain pick phase"))),
+    };
+    let (captain, other_captain) = if team == "A" { (&mut *captain_a, &*captain_b) } else { (&mut *captain_b, &*captain_a) };
+    if other_captain.as_ref() == Some(&user) {
+        return Err(BotError::Usage(String::from(" this user is already the other team's captain")));
+    }
+    *captain = Some(user.clone());
+    let captains = match (captain_a.clone(), captain_b.clone()) {
+        (Some(a), Some(b)) => Some(Captains { a, b }),
+        _ => None,
+    };
+    send_simple_tagged_msg(&context, &msg, &format!(" is set as captain for Team {}.", team), &user).await;
+    audit_log(&context, &data, "Force captain", format!("<@{}> set <@{}> as captain for Team {}", msg.author.id, user.id, team)).await;
+    if let Some(captains) = captains {
+        start_draft(&context, msg.channel_id, &mut data, captains, false).await;
+    }
+    Ok(())
+}
+
+/// Ends the captain pick with both captains set and starts the draft, `randomize` flips a coin for the first pick
+async fn start_draft(messenger: &impl Messenger, channel_id: ChannelId, data: &mut TypeMap, mut captains: Captains, randomize: bool) {
+    if randomize {
+        announce(messenger, channel_id, "Randomizing captain pick order...").await;
+        // flip a coin, if 1 switch captains
+        if rand::thread_rng().gen_range(0, 2) != 0 {
+            std::mem::swap(&mut captains.a, &mut captains.b);
+        }
+    }
+    let draft: &mut Draft = &mut data.get_mut::<CurrentMatch>().unwrap().draft;
+    draft.team_a.push(captains.a.clone());
+    draft.team_b.push(captains.b.clone());
+    announce_tagged(messenger, channel_id, " is set as the first pick captain (Team A)", &captains.a).await;
+    announce_tagged(messenger, channel_id, " is set as the second captain (Team B)", &captains.b).await;
+    let response = MessageBuilder::new()
+        .push("Captain pick has concluded. Starting draft phase. ")
+        .mention(&captains.a)
+        .push(" gets first `.pick @<user>`")
+        .build();
+    messenger.say(channel_id, response).await;
+    audit_log(messenger, data, "Captains", format!("<@{}> is Captain A, <@{}> is Captain B", captains.a.id, captains.b.id)).await;
+    let bot_state: &mut StateContainer = data.get_mut::<BotState>().unwrap();
+    bot_state.state = State::draft(captains);
+    update_draft_embed(messenger, channel_id, data).await;
+}
+
+/// Handles both `.pick` and `.forcepick`, which lets an admin make the current pick for an AFK captain
+pub(crate) async fn handle_pick(context: Context, msg: Message, force_pick: bool) -> BotResult {
+    if force_pick && !admin_check(&context, &msg, true).await { return Ok(()); }
+    let mut data = context.data.write().await;
+    let picked = match msg.mentions.first() {
+        Some(picked) => picked.clone(),
+        None => return Err(BotError::Usage(String::from(" please mention a discord user in your message."))),
+    };
+    if pick_player(&context, msg.channel_id, &mut data, &msg.author, picked, force_pick).await? {
+        drop(data);
+        handle_ready(&context, &msg).await;
+    }
+    Ok(())
+}
+
+/// Adds `picked` to the team of the captain whose turn it is, `force_pick` skips the check that `author` is that
+/// captain. Returns true once the setup is complete and `handle_ready` should run
+pub(crate) async fn pick_player(messenger: &impl Messenger, channel_id: ChannelId, data: &mut TypeMap, author: &User, picked: User, force_pick: bool) -> BotResult<bool> {
+    let (captains, turn) = match &data.get::<BotState>().unwrap().state {
+        State::Draft { captains, turn, .. } => (captains.clone(), *turn),
+        _ => return Err(BotError::Usage(String::from(" it is not currently the draft phase"))),
+    };
+    let user_queue: Vec<User> = data.get::<UserQueue>().unwrap().to_vec();
+    if !user_queue.contains(&picked) {
+        return Err(BotError::Usage(String::from(" this user is not in the queue")));
+    }
+    let draft = &data.get::<CurrentMatch>().unwrap().draft;
+    let current_picker = captains.get(turn.team);
+    if !force_pick && !captains.contains(author) {
+        return Err(BotError::Usage(String::from(" you are not a captain")));
+    }
+    if !force_pick && current_picker != author {
+        return Err(BotError::Usage(String::from(" it is not your turn to pick")));
+    }
+    if draft.team_a.contains(&picked) || draft.team_b.contains(&picked) {
+        return Err(BotError::Usage(String::from(" this player is already on a team")));
+    }
+
+    let team_a_name = team_label(data, &captains.a);
+    let team_b_name = team_label(data, &captains.b);
+    let picking_team = if turn.team == Team::A { &team_a_name } else { &team_b_name };
+    let forced = if force_pick { format!(" (forced by <@{}>)", author.id) } else { String::new() };
+    audit_log(messenger, data, "Pick", format!("<@{}> picked <@{}> for Team {}{}", current_picker.id, picked.id, picking_team, forced)).await;
+    let draft: &mut Draft = &mut data.get_mut::<CurrentMatch>().unwrap().draft;
+    if turn.team == Team::A {
+        announce_tagged(messenger, channel_id, &format!(" has been added to Team {}", team_a_name), &picked).await;
+        draft.team_a.push(picked);
+    } else {
+        announce_tagged(messenger, channel_id, &format!(" has been added to Team {}", team_b_name), &picked).await;
+        draft.team_b.push(picked);
+    }
+    data.get_mut::<BotState>().unwrap().state.pass_turn();
+    update_draft_embed(messenger, channel_id, data).await;
+    let draft: &Draft = &data.get::<CurrentMatch>().unwrap().draft;
+    let remaining_users = user_queue
+        .iter()
+        .filter(|user| !draft.team_a.contains(user) && !draft.team_b.contains(user))
+        .count();
+    if remaining_users == 0 {
+        return Ok(begin_side_pick(messenger, channel_id, data, captains).await);
+    }
+    Ok(false)
+}
+
+/// Passes the turn of a captain that isn't picking to the other captain. The other captain has to wait
+/// `pick_skip_seconds` into the turn first, admins can skip right away
+pub(crate) async fn handle_skip(context: Context, msg: Message) -> BotResult {
+    let is_admin = admin_check(&context, &msg, false).await;
+    let mut data = context.data.write().await;
+    skip_turn(&context, msg.channel_id, &mut data, &msg.author, is_admin).await
+}
+
+/// Passes the turn on for `author`, see `handle_skip`
+pub(crate) async fn skip_turn(messenger: &impl Messenger, channel_id: ChannelId, data: &mut TypeMap, author: &User, is_admin: bool) -> BotResult {
+    let (current_picker, other_captain, turn) = match &data.get::<BotState>().unwrap().state {
+        State::Draft { captains, turn, .. } => (captains.get(turn.team).clone(), captains.get(turn.team.other()).clone(), *turn),
+        _ => return Err(BotError::Usage(String::from(" it is not currently the draft phase"))),
+    };
+    let skip_seconds = data.get::<Config>().unwrap().pick_skip_seconds.unwrap_or(60);
+    let draft: &mut Draft = &mut data.get_mut::<CurrentMatch>().unwrap().draft;
+    if !is_admin {
+        if *author != other_captain {
+            return Err(BotError::Usage(String::from(" only the other captain or an admin can skip a captain's turn")));
+        }
+        let waited = Local::now().timestamp() - turn.started;
+        if waited < skip_seconds {
+            return Err(BotError::Usage(format!(" <@{}> still has {} seconds to pick", current_picker.id, skip_seconds - waited)));
+        }
+    }
+    draft.skipped_turns.push(*current_picker.id.as_u64());
+    data.get_mut::<BotState>().unwrap().state.pass_turn();
+    let response = MessageBuilder::new()
+        .mention(&current_picker)
+        .push("'s turn was skipped. ")
+        .mention(&other_captain)
+        .push(" it's your turn to `.pick @<user>`")
+        .build();
+    messenger.say(channel_id, response).await;
+    audit_log(messenger, data, "Skip", format!("<@{}> skipped the turn of <@{}>", author.id, current_picker.id)).await;
+    update_draft_embed(messenger, channel_id, data).await;
+    Ok(())
+}
+
+/// Starts the side pick once the teams are set. Unless `side_selection` is `captain_b` the side is picked right away,
+/// returns true if the setup is complete and `handle_ready` should run
+pub(crate) async fn begin_side_pick(messenger: &impl Messenger, channel_id: ChannelId, data: &mut TypeMap, captains: Captains) -> bool {
+    let side = match data.get::<Config>().unwrap().side_selection.as_deref() {
+        Some("random") => if rand::thread_rng().gen_range(0, 2) == 0 { "ct" } else { "t" },
+        Some("default_defense") => match data.get::<Config>().unwrap().default_defense_team.as_deref() {
+            Some("A") | Some("a") => "t",
+            _ => "ct",
+        },
+        _ => {
+            let captain_b = captains.b.clone();
+            data.get_mut::<BotState>().unwrap().state = State::side_pick(captains);
+            prompt_side_pick(messenger, channel_id, data, &captain_b).await;
+            return false;
+        }
+    };
+    complete_setup(data, captains, side);
+    let (team_a_side, team_b_side) = start_sides(&data.get::<CurrentMatch>().unwrap().draft, data.get::<Config>().unwrap());
+    let response = MessageBuilder::new()
+        .push_line(i18n::translate(&format!("Team A starts on {}, Team B starts on {}.", team_a_side, team_b_side)))
+        .push(i18n::translate("Setup is completed."))
+        .build();
+    messenger.say(channel_id, response).await;
+    true
+}
+
+/// Ends the side pick with Team B starting on `side`, `ct` or `t`. The captains move from the state to the match record
+fn complete_setup(data: &mut TypeMap, captains: Captains, side: &str) {
+    let draft: &mut Draft = &mut data.get_mut::<CurrentMatch>().unwrap().draft;
+    draft.captain_a = Some(captains.a);
+    draft.captain_b = Some(captains.b);
+    draft.team_b_start_side = String::from(side);
+    data.get_mut::<BotState>().unwrap().state = State::Ready;
+}
+
+/// Asks Captain B to pick a starting side, the side can be picked by reacting to the prompt as well
+pub(crate) async fn prompt_side_pick(messenger: &impl Messenger, channel_id: ChannelId, data: &mut TypeMap, captain_b: &User) {
+    let (attack, defense) = side_names(data.get::<Config>().unwrap());
+    let response = MessageBuilder::new()
+        .mention(captain_b)
+        .push(i18n::translate(" type `.defense` or `.attack` to pick a starting side, or `.coinflip` to pick one at random."))
+        .push(i18n::translate(&format!(" You can also react with 🛡️ for {} or ⚔️ for {}.", defense, attack)))
+        .build();
+    let prompt_id = match messenger.say(channel_id, response).await {
+        Some(prompt_id) => prompt_id,
+        None => return,
+    };
+    let reactions = ["🛡️", "⚔️"].map(|emoji| ReactionType::Unicode(String::from(emoji))).to_vec();
+    messenger.react(channel_id, prompt_id, reactions).await;
+    if let State::SidePick { prompt_id: prompt, .. } = &mut data.get_mut::<BotState>().unwrap().state {
+        *prompt = Some(*prompt_id.as_u64());
+    }
+}
+
+pub(crate) async fn handle_side_pick_reaction(context: &Context, reaction: &Reaction) {
+    let side = match &reaction.emoji {
+        ReactionType::Unicode(emoji) if emoji == "🛡️" => "ct",
+        ReactionType::Unicode(emoji) if emoji == "⚔️" => "t",
+        _ => return,
+    };
+    let user_id = match reaction.user_id {
+        Some(user_id) => user_id,
+        None => return,
+    };
+    {
+        let mut data = context.data.write().await;
+        let captains = match &data.get::<BotState>().unwrap().state {
+            State::SidePick { captains, prompt_id: Some(prompt_id) } if *prompt_id == *reaction.message_id.as_u64() => captains.clone(),
+            _ => return,
+        };
+        if captains.b.id != user_id { return; }
+        complete_setup(&mut data, captains, side);
+    }
+    let mut msg = match reaction.message(&context.http).await {
+        Ok(msg) => msg,
+        Err(why) => {
+            ops::report(context, format!("Cannot fetch side pick message: {:?}", why)).await;
+            return;
+        }
+    };
+    // messages fetched over http don't include the guild
+    msg.guild_id = reaction.guild_id;
+    send_simple_msg(context, &msg, "Setup is completed.").await;
+    handle_ready(context, &msg).await;
+}
+
+/// The captain whose turn it is to pick, only during the draft
+pub(crate) fn current_picker(data: &TypeMap) -> Option<User> {
+    match &data.get::<BotState>().unwrap().state {
+        State::Draft { captains, turn, .. } => Some(captains.get(turn.team).clone()),
+        _ => None,
+    }
+}
+
+/// Captain A and B of the current setup. They're kept in the state until the setup is ready and in the match record after
+pub(crate) fn setup_captains(data: &TypeMap) -> (Option<User>, Option<User>) {
+    match &data.get::<BotState>().unwrap().state {
+        State::CaptainPick { captain_a, captain_b } => (captain_a.clone(), captain_b.clone()),
+        State::Draft { captains, .. } | State::SidePick { captains, .. } => (Some(captains.a.clone()), Some(captains.b.clone())),
+        _ => {
+            let draft = &data.get::<CurrentMatch>().unwrap().draft;
+            (draft.captain_a.clone(), draft.captain_b.clone())
+        }
+    }
+}
+
+/// Posts the draft embed with both teams, the remaining players and whose turn it is, or edits it after a pick
+pub(crate) async fn update_draft_embed(messenger: &impl Messenger, channel_id: ChannelId, data: &mut TypeMap) {
+    let captains = match data.get::<BotState>().unwrap().state.captains() {
+        Some(captains) => captains.clone(),
+        None => return,
+    };
+    let user_queue: &Vec<User> = data.get::<UserQueue>().unwrap();
+    let draft: &Draft = &data.get::<CurrentMatch>().unwrap().draft;
+    let remaining_users: String = user_queue
+        .iter()
+        .filter(|user| !draft.team_a.contains(user) && !draft.team_b.contains(user))
+        .map(|user| format!("- @{}\n", &user.name))
+        .collect();
+    let mut team_a: String = draft.team_a
+        .iter()
+        .map(|user| format!("- @{}\n", &user.name))
+        .collect();
+    let mut team_b: String = draft.team_b
+        .iter()
+        .map(|user| format!("- @{}\n", &user.name))
+        .collect();
+    if let Some(coach) = &draft.coach_a {
+        team_a.push_str(&format!("- Coach: @{}\n", &coach.name));
+    }
+    if let Some(coach) = &draft.coach_b {
+        team_b.push_str(&format!("- Coach: @{}\n", &coach.name));
+    }
+    let team_a_name = team_label(data, &captains.a);
+    let team_b_name = team_label(data, &captains.b);
+    let (description, color) = match current_picker(data) {
+        Some(picker) if !remaining_users.is_empty() => (format!("<@{}>'s turn to `.pick @<user>`", picker.id), team_color(data, &picker)),
+        _ => (String::from("All players have been picked"), None),
+    };
+    let remaining_users = if remaining_users.is_empty() { String::from("-") } else { remaining_users };
+    let mut embed = CreateEmbed::default();
+    embed.title("Draft")
+        .description(description)
+        .field(format!("Team {}", team_a_name), team_a, true)
+        .field(format!("Team {}", team_b_name), team_b, true)
+        .field("Remaining players", remaining_users, false);
+    if let Some(color) = color {
+        embed.color(color);
+    }
+    if let State::Draft { message_id: Some(message_id), .. } = data.get::<BotState>().unwrap().state {
+        if messenger.edit_embed(channel_id, MessageId(message_id), embed.clone()).await {
+            return;
+        }
+    }
+    let sent = messenger.send_embed(channel_id, embed).await;
+    if let State::Draft { message_id, .. } = &mut data.get_mut::<BotState>().unwrap().state {
+        *message_id = sent.map(|sent| *sent.as_u64());
+    }
+}
+
+pub(crate) async fn handle_defense_option(context: Context, msg: Message) -> BotResult {
+    let mut data = context.data.write().await;
+    pick_side(&context, msg.channel_id, &mut data, &msg.author, "ct").await?;
+    drop(data);
+    handle_ready(&context, &msg).await;
+    Ok(())
+}
+
+pub(crate) async fn handle_attack_option(context: Context, msg: Message) -> BotResult {
+    let mut data = context.data.write().await;
+    pick_side(&context, msg.channel_id, &mut data, &msg.author, "t").await?;
+    drop(data);
+    handle_ready(&context, &msg).await;
+    Ok(())
+}
+
+/// Sets Team B's starting side, `ct` or `t`, if `author` is Captain B. Completes the setup, `handle_ready` runs next
+pub(crate) async fn pick_side(messenger: &impl Messenger, channel_id: ChannelId, data: &mut TypeMap, author: &User, side: &str) -> BotResult {
+    let captains = match &data.get::<BotState>().unwrap().state {
+        State::SidePick { captains, .. } => captains.clone(),
+        _ => return Err(BotError::Usage(String::from(" it is not currently the side pick phase"))),
+    };
+    if *author != captains.b {
+        return Err(BotError::Usage(String::from(" you are not Captain B")));
+    }
+    complete_setup(data, captains, side);
+    announce(messenger, channel_id, "Setup is completed.").await;
+    Ok(())
+}
+
+pub(crate) async fn handle_coinflip(context: Context, msg: Message) -> BotResult {
+    let is_admin = admin_check(&context, &msg, false).await;
+    {
+        let mut data = context.data.write().await;
+        let captains = match &data.get::<BotState>().unwrap().state {
+            State::SidePick { captains, .. } => captains.clone(),
+            _ => return Err(BotError::Usage(String::from(" it is not currently the side pick phase"))),
+        };
+        if !is_admin && !captains.contains(&msg.author) {
+            return Err(BotError::Usage(String::from(" you are not a captain")));
+        }
+        complete_setup(&mut data, captains, if rand::thread_rng().gen_range(0, 2) == 0 { "ct" } else { "t" });
+        let (team_a_side, team_b_side) = start_sides(&data.get::<CurrentMatch>().unwrap().draft, data.get::<Config>().unwrap());
+        send_simple_msg(&context, &msg, &format!("Coin flip: Team A starts on {}, Team B starts on {}.", team_a_side, team_b_side)).await;
+        send_simple_msg(&context, &msg, "Setup is completed.").await;
+    }
+    handle_ready(&context, &msg).await;
+    Ok(())
+}
+
+pub(crate) fn is_valid_riot_id(riot_id: &str) -> bool {
+    Regex::new("\\w+#\\w+").unwrap().is_match(riot_id)
+}
+
+/// All Riot accounts of a user, players who only used `.riotid Name#TAG` have just their active one
+pub(crate) fn riot_accounts(data: &TypeMap, user_id: u64) -> Vec<String> {
+    match data.get::<RiotAccounts>().unwrap().get(&user_id) {
+        Some(accounts) if !accounts.is_empty() => accounts.clone(),
+        _ => data.get::<RiotIdCache>().unwrap().get(&user_id).cloned().into_iter().collect(),
+    }
+}
+
+/// Swaps the user's active Riot account for `riot_id` and keeps their other accounts, returns the replaced one
+pub(crate) fn replace_active_riot_id(data: &mut TypeMap, user_id: u64, riot_id: &str) -> Option<String> {
+    let mut accounts = riot_accounts(data, user_id);
+    let previous = data.get_mut::<RiotIdCache>().unwrap().insert(user_id, riot_id.to_string());
+    match previous.as_ref().and_then(|previous| accounts.iter().position(|account| account == previous)) {
+        Some(index) => accounts[index] = riot_id.to_string(),
+        None => accounts.push(riot_id.to_string()),
+    }
+    let mut seen = Vec::new();
+    accounts.retain(|account| if seen.contains(account) { false } else { seen.push(account.clone()); true });
+    data.get_mut::<RiotAccounts>().unwrap().insert(user_id, accounts);
+    previous
+}
+
+pub(crate) async fn save_riot_ids(data: &TypeMap) {
+    write_to_file(String::from("riot_ids.json"), serde_json::to_string(data.get::<RiotIdCache>().unwrap()).unwrap()).await;
+    write_to_file(String::from("riot_accounts.json"), serde_json::to_string(data.get::<RiotAccounts>().unwrap()).unwrap()).await;
+    write_to_file(String::from("regions.json"), serde_json::to_string(data.get::<RegionCache>().unwrap()).unwrap()).await;
+}
+
+pub(crate) const REGIONS: [&str; 6] = ["na", "eu", "ap", "kr", "latam", "br"];
+
+/// The most common region among the players and how many of them are in it, ties go to the region listed first in `REGIONS`
+pub(crate) fn majority_region<'a>(data: &TypeMap, players: impl Iterator<Item = &'a User>) -> Option<(String, usize)> {
+    let region_cache: &HashMap<u64, String> = data.get::<RegionCache>().unwrap();
+    let regions: Vec<&String> = players.filter_map(|user| region_cache.get(user.id.as_u64())).collect();
+    REGIONS.iter()
+        .map(|region| (region.to_string(), regions.iter().filter(|r| r.as_str() == *region).count()))
+        .filter(|(_, count)| *count > 0)
+        .fold(None, |best: Option<(String, usize)>, (region, count)| match best {
+            Some((_, best_count)) if best_count >= count => best,
+            _ => Some((region, count)),
+        })
+}
+
+pub(crate) async fn handle_riotid(context: Context, msg: Message) -> BotResult {
+    let args: Vec<&str> = msg.content.split_whitespace().skip(1).collect();
+    let user_id = *msg.author.id.as_u64();
+    let mut data = context.data.write().await;
+    let mut accounts = riot_accounts(&data, user_id);
+    let active = data.get::<RiotIdCache>().unwrap().get(&user_id).cloned();
+    let (accounts, active, response) = match args.as_slice() {
+        [] => {
+            send_simple_tagged_msg(&context, &msg, " please check the command formatting. There must be a space in between `.riotid` and your Riot id. \
+            Example: `.riotid Martige#NA1`", &msg.author).await;
+            return Ok(());
+        }
+        ["list"] => {
+            if accounts.is_empty() {
+                return Err(BotError::Usage(String::from(" you have no Riot id set. Example: `.riotid Martige#NA1`")));
+            }
+            let list: String = accounts.iter()
+                .enumerate()
+                .map(|(i, account)| format!("{}. `{}`{}\n", i + 1, account, if Some(account) == active.as_ref() { " (active)" } else { "" }))
+                .collect();
+            send_simple_tagged_msg(&context, &msg, &format!(" your Riot accounts:\n{}Switch with `.riotid use <number>`", list), &msg.author).await;
+            return Ok(());
+        }
+        ["add", riot_id] => {
+            if !is_valid_riot_id(riot_id) {
+                return Err(BotError::Usage(String::from(" invalid Riot id formatting. Please follow this example: `.riotid add Martige#NA1`")));
+            }
+            if accounts.iter().any(|account| account == riot_id) {
+                return Err(BotError::Usage(format!(" `{}` is already one of your accounts, see `.riotid list`", riot_id)));
+            }
+            accounts.push(riot_id.to_string());
+            let active = active.unwrap_or_else(|| riot_id.to_string());
+            let response = format!("Added Riot account `{}` for <@{}>, the active account is `{}`", riot_id, user_id, active);
+            (accounts, active, response)
+        }
+        ["use", number] | ["remove", number] => {
+            let index = match number.parse::<usize>() {
+                Ok(number) if number >= 1 && number <= accounts.len() => number - 1,
+                _ => return Err(BotError::Usage(format!(" pick an account number from `.riotid list` i.e. `.riotid {} 2`", args[0]))),
+            };
+            if args[0] == "use" {
+                let active = accounts[index].clone();
+                let response = format!("Switched <@{}> to Riot account `{}`", user_id, active);
+                (accounts, active, response)
+            } else {
+                if accounts.len() == 1 {
+                    return Err(BotError::Usage(String::from(" you can't remove your only Riot account, replace it with `.riotid Name#TAG` instead")));
+                }
+                let removed = accounts.remove(index);
+                let active = active.filter(|active| active != &removed).unwrap_or_else(|| accounts[0].clone());
+                let response = format!("Removed Riot account `{}` for <@{}>, the active account is `{}`", removed, user_id, active);
+                (accounts, active, response)
+            }
+        }
+        ["region", region] => {
+            let region = region.to_lowercase();
+            if !REGIONS.contains(&region.as_str()) {
+                return Err(BotError::Usage(format!(" unknown region, use one of {}", REGIONS.join(", "))));
+            }
+            if active.is_none() {
+                return Err(BotError::Usage(String::from(" set your Riot id first i.e. `.riotid Martige#NA1 eu`")));
+            }
+            data.get_mut::<RegionCache>().unwrap().insert(user_id, region.clone());
+            let response = format!("Set the region of <@{}> to `{}`", user_id, region.to_uppercase());
+            (accounts, active.unwrap(), response)
+        }
+        [riot_id, rest @ ..] => {
+            if !is_valid_riot_id(riot_id) {
+                return Err(BotError::Usage(String::from(" invalid Riot id formatting. Please follow this example: `.riotid Martige#NA1`")));
+            }
+            let region = rest.first().map(|region| region.to_lowercase());
+            if let Some(region) = &region {
+                if !REGIONS.contains(&region.as_str()) {
+                    return Err(BotError::Usage(format!(" unknown region `{}`, use one of {} i.e. `.riotid Martige#NA1 na`", region, REGIONS.join(", "))));
+                }
+                data.get_mut::<RegionCache>().unwrap().insert(user_id, region.clone());
+            }
+            replace_active_riot_id(&mut data, user_id, riot_id);
+            let mut response = MessageBuilder::new();
+            response
+                .push("Updated Riot id for ")
+                .mention(&msg.author)
+                .push(" to `")
+                .push(riot_id)
+                .push("`");
+            if let Some(region) = region {
+                response.push(format!(" ({})", region.to_uppercase()));
+            }
+            let response = response.build();
+            (riot_accounts(&data, user_id), riot_id.to_string(), response)
+        }
+    };
+    data.get_mut::<RiotIdCache>().unwrap().insert(user_id, active);
+    data.get_mut::<RiotAccounts>().unwrap().insert(user_id, accounts);
+    save_riot_ids(&data).await;
+    delivery::say(&context.http, msg.channel_id, &response).await;
+    Ok(())
+}
+
+pub(crate) async fn handle_set_riot_id(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let args: Vec<&str> = msg.content.split_whitespace().skip(1).collect();
+    let (user, riot_id) = match (msg.mentions.first(), args.as_slice()) {
+        (Some(user), [_, riot_id]) => (user, riot_id.to_string()),
+        _ => return Err(BotError::Usage(String::from(" invalid message formatting. Example: `.setriotid @user Martige#NA1`"))),
+    };
+    if !is_valid_riot_id(&riot_id) {
+        return Err(BotError::Usage(String::from(" invalid Riot id formatting. Example: `.setriotid @user Martige#NA1`")));
+    }
+    let mut data = context.data.write().await;
+    let previous = replace_active_riot_id(&mut data, *user.id.as_u64(), &riot_id);
+    save_riot_ids(&data).await;
+    let response = MessageBuilder::new()
+        .push("Updated Riot id for ")
+        .mention(user)
+        .push(" to `")
+        .push(&riot_id)
+        .push("`")
+        .build();
+    delivery::say(&context.http, msg.channel_id, &response).await;
+    audit_log(&context, &data, "Riot ID", format!("<@{}> set <@{}>'s Riot ID to `{}` (was `{}`)", msg.author.id, user.id, riot_id,
+                                                 previous.as_deref().unwrap_or("not set"))).await;
+    Ok(())
+}
+
+/// Reads `discord id or mention,Riot ID` lines, or a json object / array of `{"discord_id", "riot_id"}` objects
+fn parse_riot_id_import(content: &str) -> Result<Vec<(String, String)>, String> {
+    let trimmed = content.trim_start_matches('\u{feff}').trim();
+    if trimmed.starts_with('{') || trimmed.starts_with('[') {
+        let value: serde_json::Value = serde_json::from_str(trimmed).map_err(|why| format!("invalid json: {}", why))?;
+        return match value {
+            serde_json::Value::Object(entries) => Ok(entries.into_iter()
+                .map(|(user, riot_id)| (user, riot_id.as_str().unwrap_or("").to_string()))
+                .collect()),
+            serde_json::Value::Array(entries) => Ok(entries.iter()
+                .map(|entry| {
+                    let field = |name: &str| match &entry[name] {
+                        serde_json::Value::String(value) => value.clone(),
+                        serde_json::Value::Number(value) => value.to_string(),
+                        _ => String::new(),
+                    };
+                    (field("discord_id"), field("riot_id"))
+                })
+                .collect()),
+            _ => Err(String::from("the json must be an object or an array")),
+        };
+    }
+    Ok(trimmed.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(',').or_else(|| line.split_once(';')))
+        .map(|(user, riot_id)| (user.trim().trim_matches('"').to_string(), riot_id.trim().trim_matches('"').to_string()))
+        .collect())
+}
+
+pub(crate) async fn handle_import_riot_ids(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let attachment = msg.attachments.first()
+        .ok_or_else(|| BotError::Usage(String::from(" attach a csv or json file with Discord IDs and Riot IDs i.e. a line `123456789012345678,Martige#NA1` per player")))?;
+    let content = String::from_utf8_lossy(&attachment.download().await?).to_string();
+    let entries = parse_riot_id_import(&content).map_err(|why| BotError::Usage(format!(" cannot read `{}`: {}", attachment.filename, why)))?;
+    let mut imported: Vec<(u64, String)> = Vec::new();
+    let mut skipped: Vec<String> = Vec::new();
+    for (user, riot_id) in entries {
+        let user_id = user.parse::<u64>().ok().or_else(|| parse_username(&user));
+        match user_id {
+            Some(user_id) if is_valid_riot_id(&riot_id) => imported.push((user_id, riot_id)),
+            // a header row like `discord_id,riot_id` isn't worth reporting
+            None if !user.chars().any(|c| c.is_ascii_digit()) && !riot_id.contains('#') => {}
+            Some(_) => skipped.push(format!("`{}`: invalid Riot ID `{}`", user, riot_id)),
+            None => skipped.push(format!("`{}`: not a Discord ID or mention", user)),
+        }
+    }
+    if imported.is_empty() && skipped.is_empty() {
+        return Err(BotError::Usage(format!(" no Riot IDs found in `{}`", attachment.filename)));
+    }
+    let mut data = context.data.write().await;
+    let mut updated = 0;
+    for (user_id, riot_id) in &imported {
+        if replace_active_riot_id(&mut data, *user_id, riot_id).is_some() {
+            updated += 1;
+        }
+    }
+    save_riot_ids(&data).await;
+    let mut response = format!(" imported {} Riot ID(s), {} new and {} updated.", imported.len(), imported.len() - updated, updated);
+    if !skipped.is_empty() {
+        response.push_str(&format!(" Skipped {} entr{}:\n- {}", skipped.len(), if skipped.len() == 1 { "y" } else { "ies" },
+                                   skipped.iter().take(10).cloned().collect::<Vec<_>>().join("\n- ")));
+        if skipped.len() > 10 {
+            response.push_str(&format!("\n- and {} more", skipped.len() - 10));
+        }
+    }
+    send_simple_tagged_msg(&context, &msg, &response, &msg.author).await;
+    audit_log(&context, &data, "Riot ID import", format!("<@{}> imported {} Riot ID(s) from `{}`, {} skipped", msg.author.id, imported.len(), attachment.filename, skipped.len())).await;
+    Ok(())
+}
+
+pub(crate) async fn handle_map_list(context: Context, msg: Message) -> BotResult {
+    let data = context.data.read().await;
+    let maps: &Vec<String> = data.get::<Maps>().unwrap();
+    let map_str: String = maps.iter().map(|map| format!("- `{}`\n", map)).collect();
+    let response = MessageBuilder::new()
+        .push_line(i18n::translate("Current map pool:"))
+        .push(map_str)
+        .build();
+    delivery::say(&context.http, msg.channel_id, &response).await;
+    Ok(())
+}
+
+pub(crate) async fn handle_kick(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let outbox = Outbox::default();
+    let mut data = context.data.write().await;
+    let state: &mut StateContainer = data.get_mut::<BotState>().unwrap();
+    if state.state != State::Queue {
+        return Err(BotError::Usage(String::from(" cannot `.kick` the queue after `.start`, use `.cancel` to start over if needed.")));
+    }
+    let user_queue: &mut Vec<User> = data.get_mut::<UserQueue>().unwrap();
+    let user = msg.mentions.first().ok_or_else(|| BotError::Usage(String::from(" please mention a discord user in your message.")))?;
+    if !user_queue.contains(user) {
+        drop(data);
+        send_simple_tagged_msg(&context, &msg, " is not in the queue.", user).await;
+        return Ok(());
+    }
+    let queue_len = remove_from_queue(&mut data, std::slice::from_ref(user));
+    record_queue_event(&mut data, QueueEventKind::Kick, Some(*user.id.as_u64()));
+    announce_tagged(&outbox, msg.channel_id, &format!(" has been kicked{}", queue_size_note(&data, queue_len)), user).await;
+    audit_log(&outbox, &data, "Kick", format!("<@{}> kicked <@{}> from the queue ({}/{})", msg.author.id, user.id, queue_len, queue_size(&data))).await;
+    promote_waitlist(&outbox, msg.channel_id, msg.guild_id, &mut data).await;
+    drop(data);
+    outbox.send(&context).await;
+    Ok(())
+}
+
+pub(crate) async fn handle_add_map(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let mut data = context.data.write().await;
+    let split_content = msg.content.trim().split(' ').filter(|s| !s.is_empty()).collect::<Vec<_>>();
+    if split_content.len() < 2 {
+        return Err(BotError::Usage(String::from(" invalid message formatting. Example: `.addmap mapname` or `.addmap mapname https://image.url`")));
+    }
+    let map_name: String = String::from(split_content[1]);
+    let image_url: Option<String> = split_content.get(2).map(|url| String::from(*url));
+    if let Some(url) = &image_url {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(BotError::Usage(String::from(" invalid image url, it must start with `http://` or `https://`")));
+        }
+    }
+    let maps: &Vec<String> = data.get::<Maps>().unwrap();
+    if maps.contains(&map_name) {
+        if let Some(url) = image_url {
+            let map_images: &mut HashMap<String, String> = data.get_mut::<MapImages>().unwrap();
+            map_images.insert(String::from(&map_name), url);
+            write_to_file(String::from("map_images.json"), serde_json::to_string(map_images).unwrap()).await;
+            send_simple_tagged_msg(&context, &msg, &format!(" updated image for map: `{}`", &map_name), &msg.author).await;
+            return Ok(());
+        }
+        send_simple_tagged_msg(&context, &msg, " unable to add map, already exists.", &msg.author).await;
+        return Ok(());
+    }
+    if maps.len() >= 26 {
+        send_simple_tagged_msg(&context, &msg, " unable to add map, max amount reached.", &msg.author).await;
+        return Ok(());
+    }
+    let maps: &mut Vec<String> = data.get_mut::<Maps>().unwrap();
+    maps.push(String::from(&map_name));
+    write_to_file(String::from("maps.json"), serde_json::to_string(maps).unwrap()).await;
+    if let Some(url) = image_url {
+        let map_images: &mut HashMap<String, String> = data.get_mut::<MapImages>().unwrap();
+        map_images.insert(String::from(&map_name), url);
+        write_to_file(String::from("map_images.json"), serde_json::to_string(map_images).unwrap()).await;
+    }
+    send_simple_tagged_msg(&context, &msg, &format!(" added map: `{}`", map_name), &msg.author).await;
+    Ok(())
+}
+
+pub(crate) async fn handle_remove_map(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let mut data = context.data.write().await;
+    let maps: &mut Vec<String> = data.get_mut::<Maps>().unwrap();
+    let map_name: String = msg.content.split_whitespace().nth(1)
+        .ok_or_else(|| BotError::Usage(String::from(" invalid message formatting. Example: `.removemap mapname`")))?
+        .to_string();
+    if !maps.contains(&map_name) {
+        send_simple_tagged_msg(&context, &msg, " this map doesn't exist in the list.", &msg.author).await;
+        return Ok(());
+    }
+    let index = maps.iter().position(|m| m == &map_name).unwrap();
+    maps.remove(index);
+    write_to_file(String::from("maps.json"), serde_json::to_string(maps).unwrap()).await;
+    let map_images: &mut HashMap<String, String> = data.get_mut::<MapImages>().unwrap();
+    if map_images.remove(&map_name).is_some() {
+        write_to_file(String::from("map_images.json"), serde_json::to_string(map_images).unwrap()).await;
+    }
+    send_simple_tagged_msg(&context, &msg, &format!(" removed map: `{}`", map_name), &msg.author).await;
+    Ok(())
+}
+
+pub(crate) async fn handle_map_pool(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let mut data = context.data.write().await;
+    let split_content = msg.content.trim().split(' ').filter(|s| !s.is_empty()).collect::<Vec<_>>();
+    if split_content.len() == 1 {
+        let map_pools: &HashMap<String, Vec<String>> = data.get::<MapPools>().unwrap();
+        let mut pool_names: Vec<&String> = map_pools.keys().collect();
+        pool_names.sort();
+        let pool_str: String = pool_names.iter()
+            .map(|name| format!("- `{}`: {}\n", name, map_pools.get(*name).unwrap().join(", ")))
+            .collect();
+        let response = MessageBuilder::new()
+            .push_line(i18n::translate("Map pools:"))
+            .push(pool_str)
+            .build();
+        delivery::say(&context.http, msg.channel_id, &response).await;
+        return Ok(());
+    }
+    let pool_name: String = match split_content[1].to_lowercase().as_str() {
+        "competitive" | "all" => split_content[1].to_lowercase(),
+        "custom" | "save" if split_content.len() > 2 => split_content[2].to_lowercase(),
+        _ => {
+            send_simple_tagged_msg(&context, &msg, " invalid message formatting. \
+            Example: `.mappool competitive`, `.mappool all`, `.mappool custom <name>` or `.mappool save <name>`", &msg.author).await;
+            return Ok(());
+        }
+    };
+    if split_content[1].to_lowercase() == "save" {
+        let maps: Vec<String> = data.get::<Maps>().unwrap().clone();
+        let map_pools: &mut HashMap<String, Vec<String>> = data.get_mut::<MapPools>().unwrap();
+        map_pools.insert(String::from(&pool_name), maps);
+        write_to_file(String::from("map_pools.json"), serde_json::to_string(map_pools).unwrap()).await;
+        send_simple_tagged_msg(&context, &msg, &format!(" saved current map list as map pool `{}`", &pool_name), &msg.author).await;
+        return Ok(());
+    }
+    let map_pools: &HashMap<String, Vec<String>> = data.get::<MapPools>().unwrap();
+    let pool: Vec<String> = match map_pools.get(&pool_name) {
+        Some(pool) => pool.clone(),
+        None => {
+            send_simple_tagged_msg(&context, &msg, &format!(" map pool `{}` doesn't exist. \
+            Use `.mappool save {}` to save the current map list under this name.", &pool_name, &pool_name), &msg.author).await;
+            return Ok(());
+        }
+    };
+    let maps: &mut Vec<String> = data.get_mut::<Maps>().unwrap();
+    *maps = pool;
+    write_to_file(String::from("maps.json"), serde_json::to_string(maps).unwrap()).await;
+    let map_str: String = maps.iter().map(|map| format!("- `{}`\n", map)).collect();
+    send_simple_tagged_msg(&context, &msg, &format!(" switched to map pool `{}`:\n{}", pool_name, map_str), &msg.author).await;
+    Ok(())
+}
+
+/// Replies to a failed command, usage errors are shown to the user as is and anything else is reported to the ops channel
+pub(crate) async fn handle_command_error(context: &Context, msg: &Message, error: BotError) {
+    match error {
+        BotError::Usage(text) => {
+            send_simple_tagged_msg(context, msg, &text, &msg.author).await;
+        }
+        error => {
+            ops::report(context, format!("`{}` from {} failed: {}", msg.content, msg.author.tag(), error)).await;
+            send_simple_tagged_msg(context, msg, " something went wrong running that command, the admins have been notified.", &msg.author).await;
+        }
+    }
+}
+
+pub(crate) async fn handle_unknown(context: Context, msg: Message) -> BotResult {
+    let response = MessageBuilder::new()
+        .push("Unknown command, type `.help` for list of commands.")
+        .build();
+    delivery::say(&context.http, msg.channel_id, &response).await;
+    Ok(())
+}
+
+pub(crate) async fn write_to_file(path: String, content: String) {
+    let mut error_string = String::from("Error writing to ");
+    error_string.push_str(&path);
+    std::fs::write(path, content)
+        .expect(&error_string);
+}
+
+/// Writes every persistent cache to its json file
+pub(crate) async fn save_caches(data: &TypeMap) {
+    save_riot_ids(data).await;
+    write_to_file(String::from("teamnames.json"), serde_json::to_string(data.get::<TeamNameCache>().unwrap()).unwrap()).await;
+    write_to_file(String::from("maps.json"), serde_json::to_string(data.get::<Maps>().unwrap()).unwrap()).await;
+    write_to_file(String::from("map_pools.json"), serde_json::to_string(data.get::<MapPools>().unwrap()).unwrap()).await;
+    write_to_file(String::from("map_images.json"), serde_json::to_string(data.get::<MapImages>().unwrap()).unwrap()).await;
+    write_to_file(String::from("player_stats.json"), serde_json::to_string(data.get::<PlayerStatsCache>().unwrap()).unwrap()).await;
+    write_to_file(String::from("match_history.json"), serde_json::to_string(data.get::<MatchHistory>().unwrap()).unwrap()).await;
+    write_to_file(String::from("notify_subscriptions.json"), serde_json::to_string(data.get::<NotifySubscriptions>().unwrap()).unwrap()).await;
+    write_to_file(String::from("captain_pool.json"), serde_json::to_string(data.get::<CaptainPool>().unwrap()).unwrap()).await;
+    write_to_file(String::from("last_match.json"), serde_json::to_string(data.get::<LastMatch>().unwrap()).unwrap()).await;
+    write_to_file(String::from("scheduled_scrims.json"), serde_json::to_string(data.get::<ScheduledScrims>().unwrap()).unwrap()).await;
+    write_to_file(String::from("aliases.json"), serde_json::to_string(data.get::<CommandAliases>().unwrap()).unwrap()).await;
+    write_to_file(String::from("queue_events.json"), serde_json::to_string(data.get::<QueueEvents>().unwrap()).unwrap()).await;
+}
+
+/// Writes every cache to disk and tells the queue channel the bot is going offline
+pub(crate) async fn shutdown(http: &Http, data: &Arc<RwLock<TypeMap>>) {
+    let data = match tokio::time::timeout(Duration::from_secs(5), data.read()).await {
+        Ok(data) => data,
+        Err(_) => {
+            eprintln!("Cannot save state on shutdown, a command is still holding the bot data");
+            return;
+        }
+    };
+    save_caches(&data).await;
+    let session = Session {
+        state: data.get::<BotState>().unwrap().state.clone(),
+        queue: data.get::<UserQueue>().unwrap().clone(),
+        queue_messages: data.get::<QueueMessages>().unwrap().clone(),
+        parties: data.get::<Parties>().unwrap().clone(),
+        waitlist: data.get::<Waitlist>().unwrap().clone(),
+        spectators: data.get::<Spectators>().unwrap().clone(),
+        priority_players: data.get::<PriorityPlayers>().unwrap().clone(),
+        current_match: data.get::<CurrentMatch>().unwrap().clone(),
+        last_match: data.get::<LastMatch>().unwrap().clone(),
+        active_match: data.get::<ActiveMatch>().unwrap().clone(),
+        queue_join_times: data.get::<QueueJoinTimes>().unwrap().iter().map(|(user_id, joined)| (*user_id, joined.timestamp())).collect(),
+        queue_expiry: data.get::<QueueExpiry>().unwrap().iter().map(|(user_id, expires)| (*user_id, expires.timestamp())).collect(),
+        queue_channel: data.get::<QueueActivity>().unwrap().as_ref().map(|activity| (activity.channel_id, activity.guild_id)),
+    };
+    write_to_file(String::from("session.json"), serde_json::to_string(&session).unwrap()).await;
+    if let Some(channel_id) = queue_channel(&data) {
+        delivery::post(http, channel_id, i18n::translate("The bot is going offline for a moment, the queue will be restored when it's back.")).await;
+    }
+}
+
+pub(crate) async fn create_temp_voice_channel(context: &Context, guild_id: GuildId, category_id: u64, team_name: &str) -> Option<ChannelId> {
+    match guild_id.create_channel(&context.http, |c| c
+        .name(format!("Team {}", team_name))
+        .kind(ChannelType::Voice)
+        .category(category_id))
+        .await {
+        Ok(channel) => Some(channel.id),
+        Err(why) => {
+            ops::report(context, format!("Cannot create team channel: {:?}", why)).await;
+            None
+        }
+    }
+}
+
+/// Creates a temporary "Team <name>" role in the team's color and assigns it to the team's players and coach
+pub(crate) async fn create_team_role(context: &Context, guild_id: GuildId, team_name: &str, color: Option<u32>, members: impl Iterator<Item=&User>) -> Option<RoleId> {
+    let role = match guild_id.create_role(&context.http, |r| {
+        if let Some(color) = color {
+            r.colour(color as u64);
+        }
+        r.name(format!("Team {}", team_name))
+    }).await {
+        Ok(role) => role,
+        Err(why) => {
+            ops::report(context, format!("Cannot create team role, check bot permissions: {:?}", why)).await;
+            return None;
+        }
+    };
+    for user in members {
+        if let Err(why) = context.http.add_member_role(*guild_id.as_u64(), *user.id.as_u64(), *role.id.as_u64()).await {
+            ops::report(context, format!("Cannot add team role to user: {:?}", why)).await;
+        }
+    }
+    Some(role.id)
+}
+
+/// Limits a team channel to the team's role, admins and spectators. Returns the channel's previous
+/// overwrites for the roles and members that were changed so they can be restored after the match
+pub(crate) async fn lock_team_channel(context: &Context, guild_id: GuildId, channel_id: ChannelId, role_id: RoleId, admin_role_id: Option<u64>,
+                                      spectators: &[User]) -> Vec<PermissionOverwrite> {
+    let connect = Permissions::CONNECT | Permissions::SPEAK;
+    // the @everyone role shares its id with the guild
+    let mut overwrites = vec![
+        PermissionOverwrite { allow: Permissions::empty(), deny: connect, kind: PermissionOverwriteType::Role(RoleId(*guild_id.as_u64())) },
+        PermissionOverwrite { allow: connect, deny: Permissions::empty(), kind: PermissionOverwriteType::Role(role_id) },
+    ];
+    if let Some(admin_role_id) = admin_role_id {
+        overwrites.push(PermissionOverwrite { allow: connect, deny: Permissions::empty(), kind: PermissionOverwriteType::Role(RoleId(admin_role_id)) });
+    }
+    for spectator in spectators {
+        overwrites.push(PermissionOverwrite { allow: Permissions::CONNECT, deny: Permissions::SPEAK, kind: PermissionOverwriteType::Member(spectator.id) });
+    }
+    let existing = context.cache.guild_channel_field(channel_id, |channel| channel.permission_overwrites.clone()).await.unwrap_or_default();
+    let mut original_overwrites = Vec::new();
+    for overwrite in overwrites {
+        match existing.iter().find(|existing| existing.kind == overwrite.kind) {
+            Some(existing) => original_overwrites.push(existing.clone()),
+            None => original_overwrites.push(PermissionOverwrite { allow: Permissions::empty(), deny: Permissions::empty(), kind: overwrite.kind }),
+        }
+        if let Err(why) = channel_id.create_permission(&context.http, &overwrite).await {
+            ops::report(context, format!("Cannot update team channel permissions: {:?}", why)).await;
+        }
+    }
+    original_overwrites
+}
+
+/// Creates a private thread for a team with the setup summary, only the team's players and coach are added
+pub(crate) async fn create_team_thread(context: &Context, channel_id: ChannelId, team_name: &str, summary: &str, members: &[&User]) -> Option<ChannelId> {
+    let thread = match channel_id.create_private_thread(&context.http, |t| t
+        .name(format!("Team {}", team_name))
+        .auto_archive_duration(1440))
+        .await {
+        Ok(thread) => thread,
+        Err(why) => {
+            ops::report(context, format!("Cannot create team thread, check bot permissions: {:?}", why)).await;
+            return None;
+        }
+    };
+    for user in members {
+        if let Err(why) = context.http.add_thread_channel_member(*thread.id.as_u64(), *user.id.as_u64()).await {
+            ops::report(context, format!("Cannot add user to team thread: {:?}", why)).await;
+        }
+    }
+    delivery::say(&context.http, thread.id, summary).await;
+    Some(thread.id)
+}
+
+/// Creates a guild scheduled event for a match and starts it right away, serenity has no
+/// scheduled event support so the Discord API is called directly
+pub(crate) async fn create_scheduled_event(context: &Context, guild_id: GuildId, channel_id: u64, name: &str, description: &str) -> Option<u64> {
+    let client = reqwest::Client::new();
+    let body = serde_json::json!({
+        "name": name.chars().take(100).collect::<String>(),
+        "description": description,
+        "channel_id": channel_id.to_string(),
+        "entity_type": 2,
+        "privacy_level": 2,
+        "scheduled_start_time": (Local::now() + ChronoDuration::minutes(1)).to_rfc3339(),
+    });
+    let response = client.post(format!("https://discord.com/api/v10/guilds/{}/scheduled-events", guild_id))
+        .header("Authorization", &context.http.token)
+        .json(&body)
+        .send()
+        .await;
+    let event: serde_json::Value = match response {
+        Ok(response) if response.status().is_success() => response.json().await.unwrap_or_default(),
+        Ok(response) => {
+            ops::report(context, format!("Cannot create scheduled event, check bot permissions: {}", response.status())).await;
+            return None;
+        }
+        Err(why) => {
+            ops::report(context, format!("Cannot create scheduled event: {:?}", why)).await;
+            return None;
+        }
+    };
+    let event_id = event["id"].as_str().and_then(|id| id.parse::<u64>().ok())?;
+    set_scheduled_event_status(context, guild_id, event_id, 2).await;
+    Some(event_id)
+}
+
+/// Updates a scheduled event's status, 2 is active and 3 is completed
+pub(crate) async fn set_scheduled_event_status(context: &Context, guild_id: GuildId, event_id: u64, status: u8) {
+    let client = reqwest::Client::new();
+    let response = client.patch(format!("https://discord.com/api/v10/guilds/{}/scheduled-events/{}", guild_id, event_id))
+        .header("Authorization", &context.http.token)
+        .json(&serde_json::json!({ "status": status }))
+        .send()
+        .await;
+    match response {
+        Ok(response) if !response.status().is_success() => ops::report(context, format!("Cannot update scheduled event: {}", response.status())).await,
+        Err(why) => ops::report(context, format!("Cannot update scheduled event: {:?}", why)).await,
+        _ => {}
+    }
+}
+
+/// Discord allows two renames per channel every 10 minutes
+const RENAMES_PER_WINDOW: usize = 2;
+const RENAME_WINDOW_SECONDS: i64 = 600;
+
+/// Renames the channels in the background so the bot data isn't held while waiting on discord. Renames to team names
+/// are skipped when the channel hit the rename limit, restoring an original name waits for the limit instead
+pub(crate) fn rename_channels(context: &Context, renames: Vec<(ChannelId, String)>, restore: bool) {
+    let context = context.clone();
+    tokio::spawn(async move {
+        for (channel_id, name) in renames {
+            rename_channel(&context, channel_id, &name, restore).await;
+        }
+    });
+}
+
+async fn rename_channel(context: &Context, channel_id: ChannelId, name: &str, restore: bool) {
+    loop {
+        let current_name = context.cache.guild_channel_field(channel_id, |channel| channel.name.clone()).await;
+        if current_name.as_deref() == Some(name) { break; }
+        let wait_seconds = {
+            let mut data = context.data.write().await;
+            let now = Local::now();
+            let renames = data.get_mut::<ChannelRenames>().unwrap().entry(channel_id).or_default();
+            renames.retain(|renamed| now.signed_duration_since(*renamed).num_seconds() < RENAME_WINDOW_SECONDS);
+            if renames.len() < RENAMES_PER_WINDOW {
+                renames.push(now);
+                0
+            } else {
+                RENAME_WINDOW_SECONDS - now.signed_duration_since(renames[0]).num_seconds()
+            }
+        };
+        if wait_seconds <= 0 {
+            if let Err(why) = channel_id.edit(&context.http, |c| c.name(name)).await {
+                ops::report(context, format!("Cannot rename team channel: {:?}", why)).await;
+                return;
+            }
+            break;
+        }
+        if !restore {
+            ops::report(context, format!("Not renaming <#{}> to {}, it was renamed twice in the last 10 minutes", channel_id, name)).await;
+            return;
+        }
+        task::sleep(Duration::from_secs(wait_seconds as u64)).await;
+    }
+    if restore {
+        let mut data = context.data.write().await;
+        let original_names: &mut HashMap<u64, String> = data.get_mut::<OriginalChannelNames>().unwrap();
+        original_names.remove(channel_id.as_u64());
+        write_to_file(String::from("original_channel_names.json"), serde_json::to_string(original_names).unwrap()).await;
+    }
+}
+
+/// Restores team channel names left over from a match the bot lost track of, like after a crash
+pub(crate) async fn restore_channel_names(context: &Context) {
+    let data = context.data.read().await;
+    let in_use: Vec<ChannelId> = data.get::<ActiveMatch>().unwrap().iter()
+        .flat_map(|active_match| active_match.renamed_channels.iter().map(|(channel_id, _)| *channel_id))
+        .collect();
+    let renames: Vec<(ChannelId, String)> = data.get::<OriginalChannelNames>().unwrap().iter()
+        .map(|(channel_id, name)| (ChannelId(*channel_id), name.clone()))
+        .filter(|(channel_id, _)| !in_use.contains(channel_id))
+        .collect();
+    if !renames.is_empty() {
+        rename_channels(context, renames, true);
+    }
+}
+
+/// Cleans up everything that was set up for a match at Ready
+pub(crate) async fn close_match(context: &Context, active_match: ActiveMatchInfo) {
+    for channel_id in &active_match.temp_channels {
+        if let Err(why) = channel_id.delete(&context.http).await {
+            ops::report(context, format!("Cannot delete team channel: {:?}", why)).await;
+        }
+    }
+    if !active_match.renamed_channels.is_empty() {
+        rename_channels(context, active_match.renamed_channels, true);
+    }
+    for (channel_id, original_overwrites) in active_match.locked_channels {
+        if active_match.temp_channels.contains(&channel_id) { continue; }
+        for overwrite in original_overwrites {
+            let result = if overwrite.allow.is_empty() && overwrite.deny.is_empty() {
+                channel_id.delete_permission(&context.http, overwrite.kind).await
+            } else {
+                channel_id.create_permission(&context.http, &overwrite).await
+            };
+            if let Err(why) = result {
+                ops::report(context, format!("Cannot restore team channel permissions: {:?}", why)).await;
+            }
+        }
+    }
+    for thread_id in active_match.team_threads {
+        if let Err(why) = thread_id.edit_thread(&context.http, |t| t.archived(true).locked(true)).await {
+            ops::report(context, format!("Cannot archive team thread: {:?}", why)).await;
+        }
+    }
+    if let (Some(guild_id), Some(event_id)) = (active_match.guild_id, active_match.scheduled_event_id) {
+        set_scheduled_event_status(context, guild_id, event_id, 3).await;
+    }
+    if let Some(guild_id) = active_match.guild_id {
+        for role_id in active_match.team_a_role_id.iter().chain(active_match.team_b_role_id.iter()) {
+            if let Err(why) = guild_id.delete_role(&context.http, role_id).await {
+                ops::report(context, format!("Cannot delete team role: {:?}", why)).await;
+            }
+        }
+    }
+}
+
+/// Returns the voice channel of a team in the current match, falling back to the configured team channel
+pub(crate) fn team_channel_id(data: &TypeMap, team_a: bool) -> Option<ChannelId> {
+    if let Some(active_match) = data.get::<ActiveMatch>().unwrap() {
+        return if team_a { active_match.team_a_channel_id } else { active_match.team_b_channel_id };
+    }
+    let config: &Config = data.get::<Config>().unwrap();
+    enabled_id(data, if team_a { config.discord.team_a_channel_id } else { config.discord.team_b_channel_id }).map(ChannelId)
+}
+
+pub(crate) async fn handle_end(context: Context, msg: Message) -> BotResult {
+    let is_admin = admin_check(&context, &msg, false).await;
+    let mut data = context.data.write().await;
+    let is_captain = data.get::<LastMatch>().unwrap().as_ref()
+        .map(|last_match| last_match.draft.captain_a.as_ref() == Some(&msg.author) || last_match.draft.captain_b.as_ref() == Some(&msg.author))
+        .unwrap_or(false);
+    if !is_admin && !is_captain {
+        return Err(BotError::Usage(String::from(" only admins and captains of the last match can end it.")));
+    }
+    if data.get::<ActiveMatch>().unwrap().is_none() {
+        return Err(BotError::Usage(String::from(" there is no match in progress.")));
+    }
+    let score = match msg.content.split_whitespace().nth(1) {
+        Some(score) => match score.split_once('-').map(|(a, b)| (a.parse::<u32>(), b.parse::<u32>())) {
+            Some((Ok(score_a), Ok(score_b))) => Some((score_a, score_b)),
+            _ => {
+                return Err(BotError::Usage(String::from(" invalid score formatting, use Team A's score first. Example: `.end 13-7`")));
+            }
+        },
+        None => None,
+    };
+    let active_match = data.get_mut::<ActiveMatch>().unwrap().take().unwrap();
+    let outbox = Outbox::default();
+    let draft: &Draft = &data.get::<LastMatch>().unwrap().as_ref().unwrap().draft;
+    let team_a_name = team_name(&data, draft.captain_a.as_ref().unwrap());
+    let team_b_name = team_name(&data, draft.captain_b.as_ref().unwrap());
+    let last_match: &mut Match = data.get_mut::<LastMatch>().unwrap().as_mut().unwrap();
+    last_match.result = Some(MatchResult {
+        ended: Local::now().timestamp(),
+        team_a_name: team_a_name.clone(),
+        team_b_name: team_b_name.clone(),
+        score,
+    });
+    let last_match: Match = last_match.clone();
+    if let Some((score_a, score_b)) = score {
+        if score_a != score_b {
+            let (winners, losers) = if score_a > score_b { (Team::A, Team::B) } else { (Team::B, Team::A) };
+            let player_stats: &mut HashMap<u64, PlayerStats> = data.get_mut::<PlayerStatsCache>().unwrap();
+            for user_id in last_match.team_ids(winners) {
+                player_stats.entry(user_id).or_default().wins += 1;
+            }
+            for user_id in last_match.team_ids(losers) {
+                player_stats.entry(user_id).or_default().losses += 1;
+            }
+            write_to_file(String::from("player_stats.json"), serde_json::to_string(player_stats).unwrap()).await;
+        }
+    }
+    write_to_file(String::from("last_match.json"), serde_json::to_string(data.get::<LastMatch>().unwrap()).unwrap()).await;
+    let match_history: &mut Vec<Match> = data.get_mut::<MatchHistory>().unwrap();
+    match_history.push(last_match.clone());
+    write_to_file(String::from("match_history.json"), serde_json::to_string(match_history).unwrap()).await;
+    webhooks::send(data.get::<Config>().unwrap(), "match_result", web::match_json(&data, &last_match));
+    if let (Some(lobby_channel_id), Some(guild_id)) = (enabled_id(&data, data.get::<Config>().unwrap().discord.lobby_channel_id), msg.guild_id) {
+        let draft = &last_match.draft;
+        for user in draft.team_a.iter().chain(draft.team_b.iter()).chain(draft.coach_a.iter()).chain(draft.coach_b.iter()) {
+            outbox.move_member(guild_id, user.id, ChannelId(lobby_channel_id));
+        }
+    }
+    let response = match score {
+        Some((score_a, score_b)) => format!(" ended the match: Team {} {} - {} Team {}", team_a_name, score_a, score_b, team_b_name),
+        None => String::from(" ended the match."),
+    };
+    announce_tagged(&outbox, msg.channel_id, &response, &msg.author).await;
+    audit_log(&outbox, &data, "End", format!("<@{}>{}", msg.author.id, response)).await;
+    drop(data);
+    close_match(&context, active_match).await;
+    outbox.send(&context).await;
+    Ok(())
+}
+
+/// Returns the configured names of the attacking and defending side
+pub(crate) fn side_names(config: &Config) -> (String, String) {
+    (config.attack_side_name.clone().unwrap_or_else(|| String::from("attack")),
+     config.defense_side_name.clone().unwrap_or_else(|| String::from("defense")))
+}
+
+/// Returns the starting sides of Team A and Team B, `team_b_start_side` is stored as "ct" (defense) or "t" (attack)
+pub(crate) fn start_sides(draft: &Draft, config: &Config) -> (String, String) {
+    let (attack, defense) = side_names(config);
+    if draft.team_b_start_side == "ct" { (attack, defense) } else { (defense, attack) }
+}
+
+/// Joins the fields into a csv line, quoting fields that contain commas, quotes or line breaks
+pub(crate) fn csv_row(fields: &[&str]) -> String {
+    let mut row = fields.iter()
+        .map(|field| if field.contains(',') || field.contains('"') || field.contains('\n') {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    row.push('\n');
+    row
+}
+
+/// Writes the finished setup for stream overlays, as csv with one row per player when the path ends in `.csv`, json otherwise
+pub(crate) fn write_overlay_file(path: &str, data: &TypeMap, current_match: &Match) -> std::io::Result<()> {
+    if !path.to_lowercase().ends_with(".csv") {
+        return std::fs::write(path, serde_json::to_string_pretty(&web::match_json(data, current_match)).unwrap());
+    }
+    let draft = &current_match.draft;
+    let riot_id_cache: &HashMap<u64, String> = data.get::<RiotIdCache>().unwrap();
+    let (team_a_side, team_b_side) = start_sides(draft, data.get::<Config>().unwrap());
+    let map = draft.map.clone().unwrap_or_default();
+    let mut csv = String::from("team,team_name,side,role,player,riot_id,map\n");
+    for (team, captain, players, coach, side) in [
+        ("A", &draft.captain_a, &draft.team_a, &draft.coach_a, team_a_side),
+        ("B", &draft.captain_b, &draft.team_b, &draft.coach_b, team_b_side),
+    ] {
+        let team_name = captain.as_ref()
+            .map(|captain| team_name(data, captain))
+            .unwrap_or_default();
+        let roles = players.iter()
+            .map(|user| (if captain.as_ref() == Some(user) { "captain" } else { "player" }, user))
+            .chain(coach.iter().map(|user| ("coach", user)));
+        for (role, user) in roles {
+            let riot_id = riot_id_cache.get(user.id.as_u64()).map(String::as_str).unwrap_or("");
+            csv.push_str(&csv_row(&[team, &team_name, &side, role, &user.name, riot_id, &map]));
+        }
+    }
+    std::fs::write(path, csv)
+}
+
+/// Substitutes the `{map}`, `{team_a}`, `{team_b}`, `{team_a_side}`, `{team_b_side}` and `{riot_ids}`
+/// placeholders of post_setup_msg with the finished setup
+pub(crate) fn fill_post_setup_msg(template: &str, draft: &Draft, config: &Config, team_a_name: &str, team_b_name: &str, riot_id_cache: &HashMap<u64, String>) -> String {
+    let (team_a_side, team_b_side) = start_sides(draft, config);
+    let riot_ids = draft.team_a.iter()
+        .chain(draft.team_b.iter())
+        .filter_map(|user| riot_id_cache.get(user.id.as_u64()))
+        .map(|riot_id| riot_id.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    template
+        .replace("{map}", draft.map.as_deref().unwrap_or("unknown"))
+        .replace("{team_a}", team_a_name)
+        .replace("{team_b}", team_b_name)
+        .replace("{team_a_side}", &team_a_side)
+        .replace("{team_b_side}", &team_b_side)
+        .replace("{riot_ids}", &riot_ids)
+}
+
+pub(crate) async fn handle_ready(context: &Context, msg: &Message) {
+    let outbox = Outbox::default();
+    let mut data = context.data.write().await;
+    // the setup is saved and the queue reset first, the channels, roles and messages are set up once the lock is dropped
+    let previous_match: Option<ActiveMatchInfo> = data.get_mut::<ActiveMatch>().unwrap().take();
+    let config: Config = data.get::<Config>().unwrap().clone();
+    let draft: Draft = data.get::<CurrentMatch>().unwrap().draft.clone();
+    let riot_id_cache: HashMap<u64, String> = data.get::<RiotIdCache>().unwrap().clone();
+    let region_cache: HashMap<u64, String> = data.get::<RegionCache>().unwrap().clone();
+    let original_names: HashMap<u64, String> = data.get::<OriginalChannelNames>().unwrap().clone();
+    let spectators: Vec<User> = data.get::<Spectators>().unwrap().clone();
+    let team_a_name = &team_label(&data, draft.captain_a.as_ref().unwrap());
+    let team_b_name = &team_label(&data, draft.captain_b.as_ref().unwrap());
+    let team_a_color = team_color(&data, draft.captain_a.as_ref().unwrap());
+    let team_b_color = team_color(&data, draft.captain_b.as_ref().unwrap());
+    let temp_channel_category_id = enabled_id(&data, config.discord.temp_channel_category_id);
+    let spectator_channel_id = enabled_id(&data, config.discord.spectator_channel_id);
+    let lobby_channel_id = enabled_id(&data, config.discord.lobby_channel_id);
+    let mut active_match = ActiveMatchInfo {
+        team_a_channel_id: enabled_id(&data, config.discord.team_a_channel_id).map(ChannelId),
+        team_b_channel_id: enabled_id(&data, config.discord.team_b_channel_id).map(ChannelId),
+        ..Default::default()
+    };
+    let roster_line = |user: &User| {
+        let region = region_cache.get(user.id.as_u64()).map(|region| format!(" ({})", region.to_uppercase())).unwrap_or_default();
+        format!("- @{}: `{}`{}\n", &user.name, riot_id_cache.get(user.id.as_u64()).map(String::as_str).unwrap_or("not set"), region)
+    };
+    let mut team_a: String = draft.team_a
+        .iter()
+        .map(roster_line)
+        .collect();
+    let mut team_b: String = draft.team_b
+        .iter()
+        .map(roster_line)
+        .collect();
+    if let Some(coach) = &draft.coach_a {
+        team_a.push_str(&format!("- Coach: @{}\n", &coach.name));
+    }
+    if let Some(coach) = &draft.coach_b {
+        team_b.push_str(&format!("- Coach: @{}\n", &coach.name));
+    }
+    let mut response = MessageBuilder::new();
+    let players = draft.team_a.len() + draft.team_b.len();
+    if let Some((region, count)) = majority_region(&data, draft.team_a.iter().chain(draft.team_b.iter())) {
+        response.push_bold_line(format!("Server region: {} ({}/{} players)", region.to_uppercase(), count, players));
+    }
+    if !spectators.is_empty() {
+        let spectator_list: String = spectators
+            .iter()
+            .map(|user| format!("- @{}\n", &user.name))
+            .collect();
+        response
+            .push_bold_line("Spectators:")
+            .push_line(spectator_list);
+    }
+    let response = response.build();
+    let (team_a_side, team_b_side) = start_sides(&draft, &config);
+    let mut skipped_counts: Vec<(u64, usize)> = Vec::new();
+    for user_id in &draft.skipped_turns {
+        match skipped_counts.iter_mut().find(|(id, _)| id == user_id) {
+            Some((_, count)) => *count += 1,
+            None => skipped_counts.push((*user_id, 1)),
+        }
+    }
+    let skipped_turns = skipped_counts.iter().map(|(user_id, count)| format!("<@{}> x{}", user_id, count)).collect::<Vec<_>>().join(", ");
+    let mentions = |team: &[User]| team.iter().map(|user| format!("<@{}>", user.id)).collect::<Vec<_>>().join(" ");
+    let mut details = format!("Map: {}\nTeam {} ({}): {}\nTeam {} ({}): {}", draft.map.as_deref().unwrap_or("unknown"),
+                              team_a_name, team_a_side, mentions(&draft.team_a), team_b_name, team_b_side, mentions(&draft.team_b));
+    if !skipped_turns.is_empty() {
+        details.push_str(&format!("\nSkipped picks: {}", skipped_turns));
+    }
+    audit_log(&outbox, &data, "Setup completed", details).await;
+    let current_match: &Match = data.get::<CurrentMatch>().unwrap();
+    webhooks::send(&config, "setup_complete", web::match_json(&data, current_match));
+    let overlay_error = config.overlay_file.as_ref()
+        .and_then(|path| write_overlay_file(path, &data, current_match).err().map(|why| format!("Cannot write overlay_file {}: {:?}", path, why)));
+    // reset to queue state
+    let user_queue: &mut Vec<User> = data.get_mut::<UserQueue>().unwrap();
+    user_queue.clear();
+    let current_match: &mut Match = data.get_mut::<CurrentMatch>().unwrap();
+    current_match.ready = Some(Local::now().timestamp());
+    let last_match: Match = std::mem::take(current_match);
+    let bot_state: &mut StateContainer = data.get_mut::<BotState>().unwrap();
+    bot_state.state = State::Queue;
+    let priority_players: &mut Vec<u64> = data.get_mut::<PriorityPlayers>().unwrap();
+    priority_players.retain(|user_id| !last_match.has_player(*user_id));
+    let last_match_data: &mut Option<Match> = data.get_mut::<LastMatch>().unwrap();
+    *last_match_data = Some(last_match);
+    write_to_file(String::from("last_match.json"), serde_json::to_string(last_match_data).unwrap()).await;
+    data.get_mut::<CommendsGiven>().unwrap().clear();
+    let queue_msgs: &mut HashMap<u64, String> = data.get_mut::<QueueMessages>().unwrap();
+    queue_msgs.clear();
+    let parties: &mut Vec<Vec<User>> = data.get_mut::<Parties>().unwrap();
+    parties.clear();
+    data.get_mut::<Spectators>().unwrap().clear();
+    let queue_expiry: &mut HashMap<u64, DateTime<Local>> = data.get_mut::<QueueExpiry>().unwrap();
+    queue_expiry.clear();
+    let queue_join_times: &mut HashMap<u64, DateTime<Local>> = data.get_mut::<QueueJoinTimes>().unwrap();
+    queue_join_times.clear();
+    promote_waitlist(&outbox, msg.channel_id, msg.guild_id, &mut data).await;
+    drop(data);
+
+    if let Some(why) = overlay_error {
+        ops::report(context, why).await;
+    }
+    // a match that was never ended is closed before its channels are reused
+    if let Some(mut previous_match) = previous_match {
+        if temp_channel_category_id.is_none() {
+            // the team channels get renamed again right away, restoring them first would only use up renames
+            let team_channels = [config.discord.team_a_channel_id, config.discord.team_b_channel_id];
+            previous_match.renamed_channels.retain(|(channel_id, _)| !team_channels.contains(&Some(*channel_id.as_u64())));
+        }
+        close_match(context, previous_match).await;
+    }
+    if let Err(why) = msg.channel_id.send_message(&context.http, |m| {
+        for (team_name, roster, color) in [(team_a_name, &team_a, team_a_color), (team_b_name, &team_b, team_b_color)] {
+            m.add_embed(|e| {
+                e.title(format!("Team {}", team_name)).description(roster);
+                if let Some(color) = color {
+                    e.colour(color);
+                }
+                e
+            });
+        }
+        if !response.is_empty() {
+            m.content(&response);
+        }
+        m
+    }).await {
+        ops::report(context, format!("Cannot send the team rosters embed: {:?}", why)).await;
+    }
+    let mut renames: Vec<(ChannelId, String)> = Vec::new();
+    if let (Some(category_id), Some(guild_id)) = (temp_channel_category_id, msg.guild_id) {
+        active_match.team_a_channel_id = create_temp_voice_channel(context, guild_id, category_id, team_a_name).await;
+        active_match.team_b_channel_id = create_temp_voice_channel(context, guild_id, category_id, team_b_name).await;
+        active_match.temp_channels = active_match.team_a_channel_id.iter().chain(active_match.team_b_channel_id.iter()).copied().collect();
+    } else {
+        for (channel_id, team_name) in [(active_match.team_a_channel_id, team_a_name), (active_match.team_b_channel_id, team_b_name)] {
+            if let Some(channel_id) = channel_id {
+                // a name that was never restored is still the original one
+                let original_name = match original_names.get(channel_id.as_u64()) {
+                    Some(original_name) => Some(original_name.clone()),
+                    None => context.cache.guild_channel_field(channel_id, |channel| channel.name.clone()).await,
+                };
+                if let Some(original_name) = original_name {
+                    active_match.renamed_channels.push((channel_id, original_name));
+                    renames.push((channel_id, format!("Team {}", team_name)));
+                }
+            }
+        }
+    }
+    if let (Some(true), Some(guild_id)) = (config.team_roles, msg.guild_id) {
+        active_match.guild_id = Some(guild_id);
+        active_match.team_a_role_id = create_team_role(context, guild_id, team_a_name, team_a_color, draft.team_a.iter().chain(draft.coach_a.iter())).await;
+        active_match.team_b_role_id = create_team_role(context, guild_id, team_b_name, team_b_color, draft.team_b.iter().chain(draft.coach_b.iter())).await;
+        for (channel_id, role_id) in [(active_match.team_a_channel_id, active_match.team_a_role_id), (active_match.team_b_channel_id, active_match.team_b_role_id)] {
+            if let (Some(channel_id), Some(role_id)) = (channel_id, role_id) {
+                let original_overwrites = lock_team_channel(context, guild_id, channel_id, role_id, config.discord.admin_role_id, &spectators).await;
+                active_match.locked_channels.push((channel_id, original_overwrites));
+            }
+        }
+    }
+    if let Some(spectator_channel_id) = spectator_channel_id {
+        for user in &spectators {
+            move_user(msg, user, spectator_channel_id, context).await;
+        }
+    }
+    if let Some(team_a_channel_id) = active_match.team_a_channel_id {
+        for user in draft.team_a.iter().chain(draft.coach_a.iter()) {
+            move_user(msg, user, *team_a_channel_id.as_u64(), context).await;
+        }
+    }
+    if let Some(team_b_channel_id) = active_match.team_b_channel_id {
+        for user in draft.team_b.iter().chain(draft.coach_b.iter()) {
+            move_user(msg, user, *team_b_channel_id.as_u64(), context).await;
+        }
+    }
+    for (team_name, side, roster, members) in [
+        (team_a_name, team_a_side, &team_a, draft.team_a.iter().chain(draft.coach_a.iter()).collect::<Vec<_>>()),
+        (team_b_name, team_b_side, &team_b, draft.team_b.iter().chain(draft.coach_b.iter()).collect::<Vec<_>>()),
+    ] {
+        let mut summary = MessageBuilder::new();
+        summary
+            .push_bold_line("Setup is completed!")
+            .push_line(format!("Map: {}", draft.map.as_deref().unwrap_or("unknown")))
+            .push_line(format!("Your team: Team {}, starting on {}", team_name, side));
+        if !skipped_turns.is_empty() {
+            summary.push_line(format!("Skipped picks: {}", skipped_turns));
+        }
+        let summary = summary.push(roster.as_str()).build();
+        if config.team_threads == Some(true) {
+            if let Some(thread_id) = create_team_thread(context, msg.channel_id, team_name, &summary, &members).await {
+                active_match.team_threads.push(thread_id);
+            }
+        }
+        for user in members {
+            match user.create_dm_channel(&context.http).await {
+                Ok(channel) => {
+                    delivery::say(&context.http, channel.id, &summary).await;
+                }
+                Err(why) => ops::report(context, format!("Cannot open the setup dm with {}: {:?}", user.tag(), why)).await,
+            }
+        }
+    }
+    if let (Some(true), Some(guild_id), Some(lobby_channel_id)) = (config.scheduled_events, msg.guild_id, lobby_channel_id) {
+        active_match.guild_id = Some(guild_id);
+        active_match.scheduled_event_id = create_scheduled_event(context, guild_id, lobby_channel_id,
+                                                                 &format!("Team {} vs Team {}", team_a_name, team_b_name),
+                                                                 &format!("Map: {}", draft.map.as_deref().unwrap_or("unknown"))).await;
+    }
+    if let Some(post_start_msg) = &config.post_setup_msg {
+        let post_start_msg = fill_post_setup_msg(post_start_msg, &draft, &config, team_a_name, team_b_name, &riot_id_cache);
+        delivery::say(&context.http, msg.channel_id, &post_start_msg).await;
+    }
+    outbox.send(context).await;
+
+    let mut data = context.data.write().await;
+    if !renames.is_empty() {
+        let original_names: &mut HashMap<u64, String> = data.get_mut::<OriginalChannelNames>().unwrap();
+        for (channel_id, original_name) in &active_match.renamed_channels {
+            original_names.insert(*channel_id.as_u64(), original_name.clone());
+        }
+        write_to_file(String::from("original_channel_names.json"), serde_json::to_string(original_names).unwrap()).await;
+        rename_channels(context, renames, false);
+    }
+    let active_match_data: &mut Option<ActiveMatchInfo> = data.get_mut::<ActiveMatch>().unwrap();
+    *active_match_data = Some(active_match);
+}
+
+pub(crate) async fn handle_cancel(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let mut data = context.data.write().await;
+    let bot_state: &StateContainer = data.get::<BotState>().unwrap();
+    if bot_state.state == State::Queue {
+        return Err(BotError::Usage(String::from(" command only valid during `.start` process")));
+    }
+    reset_setup(&mut data);
+    send_simple_tagged_msg(&context, &msg, " `.start` process cancelled.", &msg.author).await;
+    let dodgers: Vec<User> = msg.mentions.iter().filter(|user| !user.bot).cloned().collect();
+    let dodger_mentions: String = dodgers.iter().map(|user| format!(" <@{}>", user.id)).collect();
+    audit_log(&context, &data, "Cancel", if dodgers.is_empty() {
+        format!("<@{}> cancelled the setup", msg.author.id)
+    } else {
+        format!("<@{}> cancelled the setup, dodges recorded for{}", msg.author.id, dodger_mentions)
+    }).await;
+    if dodgers.is_empty() { return Ok(()); }
+    let base_minutes = data.get::<Config>().unwrap().dodge_cooldown_minutes.unwrap_or(60);
+    let player_stats: &mut HashMap<u64, PlayerStats> = data.get_mut::<PlayerStatsCache>().unwrap();
+    let mut flagged: Vec<String> = Vec::new();
+    for dodger in &dodgers {
+        let stats = player_stats.entry(*dodger.id.as_u64()).or_default();
+        stats.dodges += 1;
+        // Each dodge doubles the cooldown, capped at 16x the configured base
+        let cooldown = ChronoDuration::minutes(base_minutes * 2_i64.pow(stats.dodges.min(5) - 1));
+        let until = Local::now() + cooldown;
+        stats.cooldown_until = Some(until.timestamp());
+        flagged.push(format!("<@{}> flagged for dodging ({} total), on queue cooldown until {}", dodger.id, stats.dodges, until.format("%m/%d %H:%M")));
+    }
+    write_to_file(String::from("player_stats.json"), serde_json::to_string(player_stats).unwrap()).await;
+    let queue_len = remove_from_queue(&mut data, &dodgers);
+    for dodger in &dodgers {
+        record_queue_event(&mut data, QueueEventKind::Dodge, Some(*dodger.id.as_u64()));
+    }
+    let response = format!("{}{}", flagged.join("\n"), queue_size_note(&data, queue_len));
+    delivery::say(&context.http, msg.channel_id, response).await;
+    let outbox = Outbox::default();
+    promote_waitlist(&outbox, msg.channel_id, msg.guild_id, &mut data).await;
+    drop(data);
+    outbox.send(&context).await;
+    Ok(())
+}
+
+/// Clears the captains, teams, coaches and spectators of the setup and returns to the queue, the queue itself is kept.
+/// Pending setup timers like the map vote are stopped
+fn reset_setup(data: &mut TypeMap) {
+    data.get::<SetupCancel>().unwrap().cancel();
+    let draft: &mut Draft = &mut data.get_mut::<CurrentMatch>().unwrap().draft;
+    draft.team_a = vec![];
+    draft.team_b = vec![];
+    draft.captain_a = None;
+    draft.captain_b = None;
+    draft.coach_a = None;
+    draft.coach_b = None;
+    draft.skipped_turns = Vec::new();
+    data.get_mut::<Spectators>().unwrap().clear();
+    let bot_state: &mut StateContainer = data.get_mut::<BotState>().unwrap();
+    bot_state.state = State::Queue;
+}
+
+pub(crate) async fn handle_votecancel(context: Context, msg: Message) -> BotResult {
+    let (eligible, seconds) = {
+        let mut data = context.data.write().await;
+        let state = data.get::<BotState>().unwrap().state.clone();
+        if state == State::Queue || state == State::Ready {
+            return Err(BotError::Usage(String::from(" command only valid during `.start` process")));
+        }
+        let user_queue: &Vec<User> = data.get::<UserQueue>().unwrap();
+        if !user_queue.contains(&msg.author) {
+            return Err(BotError::Usage(String::from(" only players in the setup can vote to cancel it.")));
+        }
+        let eligible: Vec<UserId> = user_queue.iter().map(|user| user.id).collect();
+        let seconds = data.get::<Config>().unwrap().vote_seconds.unwrap_or(60).max(10);
+        let active_votes: &mut Vec<Vote> = data.get_mut::<ActiveVotes>().unwrap();
+        if active_votes.contains(&Vote::Cancel) {
+            return Err(BotError::Usage(String::from(" there is already a vote to cancel the setup.")));
+        }
+        active_votes.push(Vote::Cancel);
+        (eligible, seconds)
+    };
+    let needed = eligible.len() / 2 + 1;
+    let prompt = MessageBuilder::new()
+        .mention(&msg.author)
+        .push(format!(" started a vote to cancel the setup. Players react with ✅ within {} seconds, {} votes needed.", seconds, needed))
+        .build();
+    let votes = run_vote(&context, msg.channel_id, &prompt, &eligible, seconds).await;
+    let mut data = context.data.write().await;
+    data.get_mut::<ActiveVotes>().unwrap().retain(|vote| vote != &Vote::Cancel);
+    if votes < needed {
+        send_simple_msg(&context, &msg, &format!("Vote to cancel failed with {}/{} votes.", votes, needed)).await;
+        return Ok(());
+    }
+    let state = data.get::<BotState>().unwrap().state.clone();
+    if state == State::Queue || state == State::Ready { return Ok(()); }
+    reset_setup(&mut data);
+    audit_log(&context, &data, "Cancel", format!("The setup was cancelled by vote ({}/{} votes), vote started by <@{}>", votes, needed, msg.author.id)).await;
+    send_simple_msg(&context, &msg, &format!("Setup cancelled by vote ({}/{} votes), the queue is kept. Type `.start` to begin a new setup.", votes, needed)).await;
+    Ok(())
+}
+
+pub(crate) async fn handle_set_state(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let args: Vec<&str> = msg.content.split_whitespace().skip(1).collect();
+    let (target, name) = match args.first().map(|arg| arg.to_lowercase()).as_deref() {
+        Some("queue") => ("queue", "Queue"),
+        Some("captainpick") => ("captainpick", "Captain Pick"),
+        Some("draft") => ("draft", "Draft"),
+        Some("sidepick") => ("sidepick", "Side Pick"),
+        _ => {
+            return Err(BotError::Usage(String::from(" invalid message formatting. Example: `.setstate queue`, `.setstate captainpick`, `.setstate draft` or `.setstate sidepick`")));
+        }
+    };
+    let mut data = context.data.write().await;
+    let current_state = data.get::<BotState>().unwrap().state.clone();
+    let queue_len = data.get::<UserQueue>().unwrap().len();
+    if target != "queue" && queue_len != queue_size(&data) {
+        return Err(BotError::Usage(format!(" the queue must be full to move to {}, it has {}/{} players.", name, queue_len, queue_size(&data))));
+    }
+    let captains = match setup_captains(&data) {
+        (Some(a), Some(b)) => Some(Captains { a, b }),
+        _ => None,
+    };
+    let state = match (target, captains) {
+        ("queue", _) => State::Queue,
+        ("captainpick", _) => State::captain_pick(),
+        ("draft", Some(captains)) => State::draft(captains),
+        (_, Some(captains)) => State::side_pick(captains),
+        (_, None) => return Err(BotError::Usage(format!(" both captains must be set to move to {}, use `.setstate captainpick` instead.", name))),
+    };
+    if state.is_side_pick() {
+        let draft: &Draft = &data.get::<CurrentMatch>().unwrap().draft;
+        if draft.team_a.len() + draft.team_b.len() != queue_len {
+            return Err(BotError::Usage(String::from(" the draft must be completed to move to Side Pick, use `.setstate draft` instead.")));
+        }
+    }
+    if args.get(1).map(|arg| arg.to_lowercase()).as_deref() != Some("confirm") {
+        let reset = match state {
+            State::Queue => "the map, captains and teams are cleared and the queue is kept",
+            State::CaptainPick { .. } => "the captains and teams are cleared, the map is kept",
+            State::Draft { .. } => "the teams are reset to just the captains and Captain A picks first",
+            _ => "the picked starting side is cleared and Captain B picks again",
+        };
+        send_simple_tagged_msg(&context, &msg, &format!(" this moves the bot from {} to {}, {}. Type `.setstate {} confirm` to continue.", current_state, state, reset, target), &msg.author).await;
+        return Ok(());
+    }
+    // a setup moved out of the queue skips `.start`, so the match starts here
+    if current_state == State::Queue && state != State::Queue {
+        let players = data.get::<UserQueue>().unwrap().clone();
+        data.get_mut::<CurrentMatch>().unwrap().start(players);
+    }
+    let maps: Vec<String> = data.get::<Maps>().unwrap().clone();
+    let draft: &mut Draft = &mut data.get_mut::<CurrentMatch>().unwrap().draft;
+    draft.team_b_start_side = String::new();
+    // the state holds the captains until the setup is ready again
+    draft.captain_a = None;
+    draft.captain_b = None;
+    match &state {
+        State::Queue | State::CaptainPick { .. } => {
+            if state == State::Queue {
+                draft.map = None;
+            } else if draft.map.is_none() {
+                draft.map = maps.get(rand::thread_rng().gen_range(0, maps.len().max(1))).cloned();
+            }
+            draft.team_a = vec![];
+            draft.team_b = vec![];
+            draft.coach_a = None;
+            draft.coach_b = None;
+        }
+        State::Draft { captains, .. } => {
+            draft.team_a = vec![captains.a.clone()];
+            draft.team_b = vec![captains.b.clone()];
+            draft.skipped_turns = Vec::new();
+        }
+        _ => {}
+    }
+    let next_step = match &state {
+        State::Queue => String::from("Type `.start` to begin a new setup."),
+        State::CaptainPick { .. } => format!("The map is `{}`. Two users type `.captain` to start picking teams.", draft.map.as_deref().unwrap_or("-")),
+        State::Draft { captains, .. } => format!("<@{}> gets first `.pick @<user>`", captains.a.id),
+        _ => String::new(),
+    };
+    data.get_mut::<BotState>().unwrap().state = state.clone();
+    *data.get_mut::<ActiveAfkCheck>().unwrap() = None;
+    let response = MessageBuilder::new()
+        .mention(&msg.author)
+        .push_line(format!(" moved the bot from {} to {}.", current_state, state))
+        .push(next_step)
+        .build();
+    delivery::say(&context.http, msg.channel_id, &response).await;
+    audit_log(&context, &data, "Set state", format!("<@{}> moved the bot from {} to {}", msg.author.id, current_state, state)).await;
+    if let State::SidePick { captains, .. } = &state {
+        prompt_side_pick(&context, msg.channel_id, &mut data, &captains.b).await;
+    }
+    Ok(())
+}
+
+pub(crate) async fn handle_force_teams(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let mut data = context.data.write().await;
+    if data.get::<BotState>().unwrap().state == State::MapPick {
+        return Err(BotError::Usage(String::from(" wait for the map vote to finish before forcing teams.")));
+    }
+    // msg.mentions isn't in message order, so the teams are read from the message text
+    let teams: Vec<Vec<User>> = msg.content
+        .split_once(' ')
+        .map(|(_, args)| args)
+        .unwrap_or("")
+        .split('|')
+        .map(|team| team
+            .split_whitespace()
+            .filter_map(parse_username)
+            .filter_map(|id| msg.mentions.iter().find(|user| *user.id.as_u64() == id).cloned())
+            .collect())
+        .collect();
+    let team_size = queue_size(&data) / 2;
+    if teams.len() != 2 || teams.iter().any(|team| team.len() != team_size) {
+        return Err(BotError::Usage(format!(" invalid message formatting, tag {} players per team with the captains first. Example: `.forceteams @a @b @c @d @e | @f @g @h @i @j`", team_size)));
+    }
+    let user_queue: &Vec<User> = data.get::<UserQueue>().unwrap();
+    let players: Vec<&User> = teams.iter().flatten().collect();
+    let not_queued: String = players.iter()
+        .filter(|user| !user_queue.contains(user))
+        .map(|user| format!(" <@{}>", user.id))
+        .collect();
+    if !not_queued.is_empty() {
+        return Err(BotError::Usage(format!(" all players must be in the queue, use `.recoverqueue` first. Not in the queue:{}", not_queued)));
+    }
+    if players.iter().enumerate().any(|(i, user)| players[..i].contains(user)) {
+        return Err(BotError::Usage(String::from(" a player can only be tagged once.")));
+    }
+    // teams forced from the queue skip `.start`, so the match starts here
+    if data.get::<BotState>().unwrap().state == State::Queue {
+        let players = data.get::<UserQueue>().unwrap().clone();
+        data.get_mut::<CurrentMatch>().unwrap().start(players);
+    }
+    let maps: Vec<String> = data.get::<Maps>().unwrap().clone();
+    let draft: &mut Draft = &mut data.get_mut::<CurrentMatch>().unwrap().draft;
+    if draft.map.is_none() {
+        draft.map = maps.get(rand::thread_rng().gen_range(0, maps.len().max(1))).cloned();
+    }
+    draft.captain_a = None;
+    draft.captain_b = None;
+    draft.team_a = teams[0].clone();
+    draft.team_b = teams[1].clone();
+    draft.team_b_start_side = String::new();
+    let response = MessageBuilder::new()
+        .push_bold_line(format!("Teams have been set by an admin, the map is `{}`", draft.map.as_deref().unwrap_or("-")))
+        .push_line(format!("Team A: {}", teams[0].iter().map(|user| format!("<@{}>", user.id)).collect::<Vec<_>>().join(" ")))
+        .push_line(format!("Team B: {}", teams[1].iter().map(|user| format!("<@{}>", user.id)).collect::<Vec<_>>().join(" ")))
+        .build();
+    *data.get_mut::<ActiveAfkCheck>().unwrap() = None;
+    delivery::say(&context.http, msg.channel_id, &response).await;
+    audit_log(&context, &data, "Force teams", format!("<@{}> set the teams\n{}", msg.author.id, response)).await;
+    let captains = Captains { a: teams[0][0].clone(), b: teams[1][0].clone() };
+    if begin_side_pick(&context, msg.channel_id, &mut data, captains).await {
+        drop(data);
+        handle_ready(&context, &msg).await;
+    }
+    Ok(())
+}
+
+pub(crate) async fn handle_votekick(context: Context, msg: Message) -> BotResult {
+    let (target, eligible, needed, seconds) = {
+        let mut data = context.data.write().await;
+        if data.get::<BotState>().unwrap().state != State::Queue {
+            return Err(BotError::Usage(String::from(" cannot `.votekick` the queue after `.start`, use `.cancel` to start over if needed.")));
+        }
+        let user_queue: &Vec<User> = data.get::<UserQueue>().unwrap();
+        if !user_queue.contains(&msg.author) {
+            return Err(BotError::Usage(String::from(" only players in the queue can start a vote kick.")));
+        }
+        let target = match msg.mentions.first() {
+            Some(target) if target != &msg.author => target.clone(),
+            _ => {
+                return Err(BotError::Usage(String::from(" invalid message formatting. Example: `.votekick @user`")));
+            }
+        };
+        if !user_queue.contains(&target) {
+            return Err(BotError::Usage(String::from(" this user is not in the queue")));
+        }
+        let eligible: Vec<UserId> = user_queue.iter().filter(|user| *user != &target).map(|user| user.id).collect();
+        let config: &Config = data.get::<Config>().unwrap();
+        // more than votekick_percent of the other queued players have to vote yes
+        let needed = (eligible.len() * config.votekick_percent.unwrap_or(50).min(100) as usize / 100 + 1).min(eligible.len());
+        let seconds = config.vote_seconds.unwrap_or(60).max(10);
+        let active_votes: &mut Vec<Vote> = data.get_mut::<ActiveVotes>().unwrap();
+        if active_votes.contains(&Vote::Kick(*target.id.as_u64())) {
+            return Err(BotError::Usage(String::from(" there is already a vote to kick this player.")));
+        }
+        active_votes.push(Vote::Kick(*target.id.as_u64()));
+        (target, eligible, needed, seconds)
+    };
+    let prompt = MessageBuilder::new()
+        .mention(&msg.author)
+        .push(" started a vote to kick ")
+        .mention(&target)
+        .push(format!(" from the queue. Queued players react with ✅ within {} seconds, {} votes needed.", seconds, needed))
+        .build();
+    let votes = run_vote(&context, msg.channel_id, &prompt, &eligible, seconds).await;
+    let mut data = context.data.write().await;
+    data.get_mut::<ActiveVotes>().unwrap().retain(|vote| vote != &Vote::Kick(*target.id.as_u64()));
+    if votes < needed {
+        send_simple_tagged_msg(&context, &msg, &format!(" vote kick failed with {}/{} votes.", votes, needed), &target).await;
+        return Ok(());
+    }
+    if data.get::<BotState>().unwrap().state != State::Queue || !data.get::<UserQueue>().unwrap().contains(&target) {
+        return Ok(());
+    }
+    let cooldown_minutes = data.get::<Config>().unwrap().votekick_cooldown_minutes.unwrap_or(15);
+    let until = Local::now() + ChronoDuration::minutes(cooldown_minutes);
+    let player_stats: &mut HashMap<u64, PlayerStats> = data.get_mut::<PlayerStatsCache>().unwrap();
+    let stats = player_stats.entry(*target.id.as_u64()).or_default();
+    // never shorten a longer dodge or no-show cooldown
+    stats.cooldown_until = Some(stats.cooldown_until.unwrap_or(0).max(until.timestamp()));
+    write_to_file(String::from("player_stats.json"), serde_json::to_string(player_stats).unwrap()).await;
+    let queue_len = remove_from_queue(&mut data, std::slice::from_ref(&target));
+    record_queue_event(&mut data, QueueEventKind::Kick, Some(*target.id.as_u64()));
+    let response = MessageBuilder::new()
+        .mention(&target)
+        .push(format!(" was vote kicked from the queue ({}/{} votes) and can rejoin at {}", votes, needed, until.format("%H:%M")))
+        .push(queue_size_note(&data, queue_len))
+        .build();
+    delivery::say(&context.http, msg.channel_id, &response).await;
+    audit_log(&context, &data, "Kick", format!("<@{}> was vote kicked from the queue ({}/{} votes), vote started by <@{}>", target.id, votes, needed, msg.author.id)).await;
+    let outbox = Outbox::default();
+    promote_waitlist(&outbox, msg.channel_id, msg.guild_id, &mut data).await;
+    drop(data);
+    outbox.send(&context).await;
+    Ok(())
+}
+
+/// Posts a vote and waits for it to end without holding the data lock. Returns the number of ✅ reactions from `eligible` users
+pub(crate) async fn run_vote(context: &Context, channel_id: ChannelId, prompt: &str, eligible: &[UserId], seconds: u64) -> usize {
+    let vote_msg = match delivery::post(&context.http, channel_id, prompt).await {
+        Some(vote_msg) => vote_msg,
+        None => return 0,
+    };
+    let yes = ReactionType::Unicode(String::from("✅"));
+    if let Err(why) = delivery::react(&context.http, vote_msg.channel_id, vote_msg.id, yes.clone()).await {
+        ops::report(context, format!("Cannot add the vote reaction: {:?}", why)).await;
+    }
+    task::sleep(Duration::from_secs(seconds)).await;
+    match vote_msg.reaction_users(&context.http, yes, Some(100), None).await {
+        Ok(voters) => voters.iter().filter(|voter| eligible.contains(&voter.id)).count(),
+        Err(why) => {
+            ops::report(context, format!("Cannot fetch vote reactions: {:?}", why)).await;
+            0
+        }
+    }
+}
+
+pub(crate) async fn handle_noshow(context: Context, msg: Message) -> BotResult {
+    let is_admin = admin_check(&context, &msg, false).await;
+    let mut data = context.data.write().await;
+    let last_match: &Draft = match data.get::<LastMatch>().unwrap() {
+        Some(last_match) => &last_match.draft,
+        None => {
+            return Err(BotError::Usage(String::from(" there is no completed setup to report a no-show for.")));
+        }
+    };
+    let is_captain = last_match.captain_a.as_ref() == Some(&msg.author) || last_match.captain_b.as_ref() == Some(&msg.author);
+    if !is_admin && !is_captain {
+        return Err(BotError::Usage(String::from(" only admins and captains of the last match can report a no-show.")));
+    }
+    let user = match msg.mentions.first() {
+        Some(user) => user.clone(),
+        None => {
+            return Err(BotError::Usage(String::from(" invalid message formatting. Example: `.noshow @user`")));
+        }
+    };
+    if !last_match.team_a.contains(&user) && !last_match.team_b.contains(&user) {
+        return Err(BotError::Usage(String::from(" that player was not in the last match.")));
+    }
+    let last_match: &mut Match = data.get_mut::<LastMatch>().unwrap().as_mut().unwrap();
+    if last_match.no_shows.contains(user.id.as_u64()) {
+        return Err(BotError::Usage(String::from(" that player was already reported as a no-show for the last match.")));
+    }
+    last_match.no_shows.push(*user.id.as_u64());
+    write_to_file(String::from("last_match.json"), serde_json::to_string(data.get::<LastMatch>().unwrap()).unwrap()).await;
+    let config: &Config = data.get::<Config>().unwrap();
+    let noshow_limit = config.noshow_limit.unwrap_or(3);
+    let cooldown = ChronoDuration::minutes(config.noshow_cooldown_minutes.unwrap_or(24 * 60));
+    let player_stats: &mut HashMap<u64, PlayerStats> = data.get_mut::<PlayerStatsCache>().unwrap();
+    let stats = player_stats.entry(*user.id.as_u64()).or_default();
+    stats.no_shows += 1;
+    let on_cooldown = stats.no_shows >= noshow_limit;
+    let mut response = MessageBuilder::new();
+    response
+        .mention(&user)
+        .push(format!(" was reported as a no-show ({} total)", stats.no_shows));
+    if on_cooldown {
+        let until = Local::now() + cooldown;
+        stats.cooldown_until = Some(until.timestamp());
+        response.push(format!(", on queue cooldown until {}", until.format("%m/%d %H:%M")));
+    }
+    write_to_file(String::from("player_stats.json"), serde_json::to_string(player_stats).unwrap()).await;
+    delivery::say(&context.http, msg.channel_id, response.build()).await;
+    if on_cooldown && data.get::<BotState>().unwrap().state == State::Queue && data.get::<UserQueue>().unwrap().contains(&user) {
+        remove_from_queue(&mut data, std::slice::from_ref(&user));
+        let outbox = Outbox::default();
+        promote_waitlist(&outbox, msg.channel_id, msg.guild_id, &mut data).await;
+        drop(data);
+        outbox.send(&context).await;
+    }
+    Ok(())
+}
+
+/// Records an infraction. From `strike_threshold` strikes on every strike bans the player from the queue,
+/// starting at `strike_ban_minutes` and doubling with each further strike up to 16x
+pub(crate) async fn handle_strike(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let mut data = context.data.write().await;
+    let user = match msg.mentions.first() {
+        Some(user) => user.clone(),
+        None => return Err(BotError::Usage(String::from(" invalid message formatting. Example: `.strike @user toxic in voice chat`"))),
+    };
+    let mention_regex = Regex::new("^<@!?\\d+>$").unwrap();
+    let reason = msg.content.split_whitespace().skip(1).filter(|word| !mention_regex.is_match(word)).collect::<Vec<_>>().join(" ");
+    if reason.is_empty() {
+        return Err(BotError::Usage(String::from(" please add a reason i.e. `.strike @user toxic in voice chat`")));
+    }
+    let config: &Config = data.get::<Config>().unwrap();
+    let threshold = config.strike_threshold.unwrap_or(3).max(1);
+    let base_minutes = config.strike_ban_minutes.unwrap_or(24 * 60);
+    let player_stats: &mut HashMap<u64, PlayerStats> = data.get_mut::<PlayerStatsCache>().unwrap();
+    let stats = player_stats.entry(*user.id.as_u64()).or_default();
+    stats.strikes.push(Strike { reason: reason.clone(), issued_by: *msg.author.id.as_u64(), created: Local::now().timestamp() });
+    let strikes = stats.strikes.len();
+    let ban = if strikes >= threshold {
+        let cooldown = ChronoDuration::minutes(base_minutes * 2_i64.pow((strikes - threshold).min(4) as u32));
+        let until = Local::now() + cooldown;
+        // never shorten a longer cooldown
+        stats.cooldown_until = Some(stats.cooldown_until.unwrap_or(0).max(until.timestamp()));
+        Some(until)
+    } else {
+        None
+    };
+    write_to_file(String::from("player_stats.json"), serde_json::to_string(player_stats).unwrap()).await;
+    let mut response = MessageBuilder::new();
+    response.mention(&user).push(format!(" received a strike ({}/{}): {}", strikes, threshold, reason));
+    if let Some(until) = ban {
+        response.push(format!(", banned from the queue until {}", until.format("%m/%d %H:%M")));
+    }
+    delivery::say(&context.http, msg.channel_id, response.build()).await;
+    let ban_text = ban.map(|until| format!(", queue ban until {}", until.format("%m/%d %H:%M"))).unwrap_or_default();
+    audit_log(&context, &data, "Strike", format!("<@{}> gave <@{}> strike {}: {}{}", msg.author.id, user.id, strikes, reason, ban_text)).await;
+    let outbox = Outbox::default();
+    outbox.dm(user.id, format!("You received a strike in the scrim queue ({}/{}): {}{}", strikes, threshold, reason, ban_text));
+    if ban.is_some() && data.get::<BotState>().unwrap().state == State::Queue && data.get::<UserQueue>().unwrap().contains(&user) {
+        remove_from_queue(&mut data, std::slice::from_ref(&user));
+        promote_waitlist(&outbox, msg.channel_id, msg.guild_id, &mut data).await;
+    }
+    drop(data);
+    outbox.send(&context).await;
+    Ok(())
+}
+
+/// Lists a player's strikes, `.strikes @user remove 2` takes one back
+pub(crate) async fn handle_strikes(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let mut data = context.data.write().await;
+    let user = match msg.mentions.first() {
+        Some(user) => user.clone(),
+        None => return Err(BotError::Usage(String::from(" invalid message formatting. Example: `.strikes @user` or `.strikes @user remove 1`"))),
+    };
+    let args: Vec<&str> = msg.content.split_whitespace().skip(2).collect();
+    if let ["remove", index] = args.as_slice() {
+        let player_stats: &mut HashMap<u64, PlayerStats> = data.get_mut::<PlayerStatsCache>().unwrap();
+        let strikes = &mut player_stats.entry(*user.id.as_u64()).or_default().strikes;
+        let strike = match index.parse::<usize>() {
+            Ok(index) if index >= 1 && index <= strikes.len() => strikes.remove(index - 1),
+            _ => return Err(BotError::Usage(format!(" pick a strike between 1 and {} to remove", strikes.len()))),
+        };
+        write_to_file(String::from("player_stats.json"), serde_json::to_string(player_stats).unwrap()).await;
+        send_simple_tagged_msg(&context, &msg, &format!(" strike `{}` removed, queue bans already given are kept.", strike.reason), &user).await;
+        audit_log(&context, &data, "Strike removed", format!("<@{}> removed a strike from <@{}>: {}", msg.author.id, user.id, strike.reason)).await;
+        return Ok(());
+    }
+    let strikes = data.get::<PlayerStatsCache>().unwrap()
+        .get(user.id.as_u64())
+        .map(|stats| stats.strikes.clone())
+        .unwrap_or_default();
+    let mut description: String = strikes.iter()
+        .enumerate()
+        .map(|(i, strike)| format!("{}. {} - {} (by <@{}>)\n", i + 1,
+                                   Local.timestamp_opt(strike.created, 0).single().map(|time| time.format("%Y-%m-%d").to_string()).unwrap_or_default(),
+                                   strike.reason, strike.issued_by))
+        .collect();
+    if description.is_empty() {
+        description = String::from("No strikes");
+    }
+    if let Some(until) = queue_cooldown(&data, &user) {
+        description.push_str(&format!("\n**Queue ban until:** {}", until.format("%m/%d %H:%M")));
+    }
+    if let Err(why) = msg.channel_id.send_message(&context.http, |m| m
+        .embed(|e| e
+            .title(format!("Strikes for {}", user.name))
+            .description(description)))
+        .await {
+        ops::report(&context, format!("Cannot send the strikes embed: {:?}", why)).await;
+    }
+    Ok(())
+}
+
+pub(crate) async fn handle_stats(context: Context, msg: Message) -> BotResult {
+    let data = context.data.read().await;
+    let user = msg.mentions.first().unwrap_or(&msg.author);
+    let player_stats: &HashMap<u64, PlayerStats> = data.get::<PlayerStatsCache>().unwrap();
+    let stats = player_stats.get(user.id.as_u64()).cloned().unwrap_or_default();
+    let mut response = MessageBuilder::new();
+    response
+        .mention(user)
+        .push(format!(" stats:\nWins: {}\nLosses: {}\nDodges: {}\nNo-shows: {}\nCommends: {}", stats.wins, stats.losses, stats.dodges, stats.no_shows, stats.commends));
+    if let Some(until) = queue_cooldown(&data, user) {
+        response.push(format!("\nQueue cooldown until {}", until.format("%m/%d %H:%M")));
+    }
+    delivery::say(&context.http, msg.channel_id, response.build()).await;
+    Ok(())
+}
+
+/// Lets players of the last match commend their teammates, up to `commends_per_match` each
+pub(crate) async fn handle_commend(context: Context, msg: Message) -> BotResult {
+    let mut data = context.data.write().await;
+    let user = msg.mentions.first()
+        .ok_or_else(|| BotError::Usage(String::from(" please mention a teammate in your message i.e. `.commend @user`")))?;
+    if *user == msg.author {
+        return Err(BotError::Usage(String::from(" you can't commend yourself")));
+    }
+    let last_match = data.get::<LastMatch>().unwrap().as_ref().map(|last_match| &last_match.draft)
+        .ok_or_else(|| BotError::Usage(String::from(" there is no match to commend players for yet")))?;
+    let teammates = [&last_match.team_a, &last_match.team_b].iter()
+        .any(|team| team.contains(&msg.author) && team.contains(user));
+    if !teammates {
+        return Err(BotError::Usage(String::from(" you can only commend your teammates from the last match")));
+    }
+    let limit = data.get::<Config>().unwrap().commends_per_match.unwrap_or(2);
+    let given: &mut Vec<u64> = data.get_mut::<CommendsGiven>().unwrap().entry(*msg.author.id.as_u64()).or_default();
+    if given.contains(user.id.as_u64()) {
+        return Err(BotError::Usage(String::from(" you already commended this player for the last match")));
+    }
+    if given.len() >= limit {
+        return Err(BotError::Usage(format!(" you've used all {} of your commends for the last match", limit)));
+    }
+    given.push(*user.id.as_u64());
+    let player_stats: &mut HashMap<u64, PlayerStats> = data.get_mut::<PlayerStatsCache>().unwrap();
+    player_stats.entry(*user.id.as_u64()).or_default().commends += 1;
+    write_to_file(String::from("player_stats.json"), serde_json::to_string(player_stats).unwrap()).await;
+    send_simple_tagged_msg(&context, &msg, &format!(" commended <@{}>, thanks for keeping the scrims friendly!", user.id), &msg.author).await;
+    Ok(())
+}
+
+/// The most commended players
+pub(crate) async fn handle_commends(context: Context, msg: Message) -> BotResult {
+    let data = context.data.read().await;
+    let mut commended: Vec<(&u64, u32)> = data.get::<PlayerStatsCache>().unwrap()
+        .iter()
+        .filter(|(_, stats)| stats.commends > 0)
+        .map(|(user_id, stats)| (user_id, stats.commends))
+        .collect();
+    commended.sort_by_key(|(_, commends)| std::cmp::Reverse(*commends));
+    let leaderboard: String = commended.iter()
+        .take(10)
+        .enumerate()
+        .map(|(i, (user_id, commends))| format!("{}. <@{}> - {} commend{}\n", i + 1, user_id, commends, if *commends == 1 { "" } else { "s" }))
+        .collect();
+    let description = if leaderboard.is_empty() { String::from("No commends yet, use `.commend @teammate` after a match") } else { leaderboard };
+    if let Err(why) = msg.channel_id.send_message(&context.http, |m| m
+        .embed(|e| e
+            .title("Commendations")
+            .description(description)))
+        .await {
+        ops::report(&context, format!("Cannot send the commends embed: {:?}", why)).await;
+    }
+    Ok(())
+}
+
+/// The last completed setup with its teams, Riot IDs, sides and the `.end` result once reported
+pub(crate) async fn handle_last(context: Context, msg: Message) -> BotResult {
+    let data = context.data.read().await;
+    let last_match = data.get::<LastMatch>().unwrap().as_ref()
+        .ok_or_else(|| BotError::Usage(String::from(" no setup has been completed yet")))?;
+    let (team_a_name, team_b_name) = match (&last_match.draft.captain_a, &last_match.draft.captain_b) {
+        (Some(captain_a), Some(captain_b)) => (team_label(&data, captain_a), team_label(&data, captain_b)),
+        _ => (String::from("A"), String::from("B")),
+    };
+    let result = match last_match.result.as_ref().map(|result| result.score) {
+        Some(Some((score_a, score_b))) if score_a > score_b => format!("{}-{}, Team {} won", score_a, score_b, team_a_name),
+        Some(Some((score_a, score_b))) if score_a < score_b => format!("{}-{}, Team {} won", score_a, score_b, team_b_name),
+        Some(Some((score_a, score_b))) => format!("{}-{}, draw", score_a, score_b),
+        Some(None) => String::from("not reported"),
+        None if data.get::<ActiveMatch>().unwrap().is_some() => String::from("in progress"),
+        None => String::from("not reported"),
+    };
+    let last_match = &last_match.draft;
+    let (team_a_side, team_b_side) = start_sides(last_match, data.get::<Config>().unwrap());
+    let riot_id_cache: &HashMap<u64, String> = data.get::<RiotIdCache>().unwrap();
+    let roster = |team: &[User], coach: &Option<User>| {
+        let mut roster: String = team.iter()
+            .map(|user| format!("- <@{}>: `{}`\n", user.id, riot_id_cache.get(user.id.as_u64()).map(String::as_str).unwrap_or("not set")))
+            .collect();
+        if let Some(coach) = coach {
+            roster.push_str(&format!("- Coach: <@{}>\n", coach.id));
+        }
+        roster
+    };
+    let team_a = roster(&last_match.team_a, &last_match.coach_a);
+    let team_b = roster(&last_match.team_b, &last_match.coach_b);
+    if let Err(why) = msg.channel_id.send_message(&context.http, |m| m
+        .embed(|e| e
+            .title("Last Match")
+            .description(format!("**Map:** {}\n**Result:** {}", last_match.map.as_deref().unwrap_or("unknown"), result))
+            .field(format!("Team {} ({})", team_a_name, team_a_side), team_a, true)
+            .field(format!("Team {} ({})", team_b_name, team_b_side), team_b, true)))
+        .await {
+        ops::report(&context, format!("Cannot send the last match embed: {:?}", why)).await;
+    }
+    Ok(())
+}
+
+pub(crate) async fn handle_whois(context: Context, msg: Message) -> BotResult {
+    let user = msg.mentions.first()
+        .ok_or_else(|| BotError::Usage(String::from(" please mention a discord user in your message i.e. `.whois @user`")))?;
+    let data = context.data.read().await;
+    let user_id = *user.id.as_u64();
+    let accounts = riot_accounts(&data, user_id);
+    let riot_id = data.get::<RiotIdCache>().unwrap().get(&user_id).cloned().unwrap_or_else(|| String::from("not set"));
+    let alts = accounts.iter().filter(|account| **account != riot_id).map(|account| format!("`{}`", account)).collect::<Vec<_>>();
+    let stats = data.get::<PlayerStatsCache>().unwrap().get(&user_id).cloned().unwrap_or_default();
+    let played = stats.wins + stats.losses;
+    let record = if played == 0 {
+        String::from("no reported results yet")
+    } else {
+        format!("{}W - {}L ({:.0}% win rate)", stats.wins, stats.losses, stats.wins as f64 * 100.0 / played as f64)
+    };
+    let month_ago = (Local::now() - ChronoDuration::days(30)).timestamp();
+    let matches: Vec<&MatchResult> = data.get::<MatchHistory>().unwrap()
+        .iter()
+        .filter(|record| record.has_player(user_id))
+        .filter_map(|record| record.result.as_ref())
+        .collect();
+    let recent = matches.iter().filter(|result| result.ended >= month_ago).count();
+    let mut description = format!("**Riot ID:** `{}`\n", riot_id);
+    if !alts.is_empty() {
+        description.push_str(&format!("**Other accounts:** {}\n", alts.join(", ")));
+    }
+    if let Some(region) = data.get::<RegionCache>().unwrap().get(&user_id) {
+        description.push_str(&format!("**Region:** {}\n", region.to_uppercase()));
+    }
+    let team = data.get::<TeamNameCache>().unwrap().get(&user_id)
+        .and_then(|profile| profile.name.is_some().then(|| team_label(&data, user)))
+        .unwrap_or_else(|| String::from("not set"));
+    description.push_str(&format!("**Team name:** {}\n**Record:** {}\n**Matches:** {} in the last 30 days, {} total\n",
+                                  team, record, recent, matches.len()));
+    if stats.dodges > 0 || stats.no_shows > 0 {
+        description.push_str(&format!("**Dodges / no-shows:** {} / {}\n", stats.dodges, stats.no_shows));
+    }
+    if data.get::<UserQueue>().unwrap().contains(user) {
+        description.push_str("**In the queue:** yes\n");
+    }
+    if let Err(why) = msg.channel_id.send_message(&context.http, |m| m
+        .embed(|e| e
+            .title(format!("Who is {}", user.name))
+            .thumbnail(user.face())
+            .description(description)))
+        .await {
+        ops::report(&context, format!("Cannot send the whois embed: {:?}", why)).await;
+    }
+    Ok(())
+}
+
+pub(crate) async fn handle_export(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let args: Vec<String> = msg.content.split_whitespace().skip(1).map(|arg| arg.to_lowercase()).collect();
+    let dm = args.iter().any(|arg| arg == "dm");
+    let data = context.data.read().await;
+    let user_name = |user_id: u64| {
+        let context = context.clone();
+        async move {
+            context.cache.user(UserId(user_id)).await.map(|user| user.name).unwrap_or_else(|| user_id.to_string())
+        }
+    };
+    let (filename, csv) = match args.first().map(String::as_str) {
+        Some("matches") => {
+            let mut csv = String::from("ended,map,team_a,team_b,team_a_score,team_b_score,team_a_players,team_b_players\n");
+            for (record, result) in data.get::<MatchHistory>().unwrap().iter().filter_map(|record| Some((record, record.result.as_ref()?))) {
+                let ended = Local.timestamp_opt(result.ended, 0).single()
+                    .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_default();
+                let mut team_a = Vec::new();
+                for user_id in record.team_ids(Team::A) {
+                    team_a.push(user_name(user_id).await);
+                }
+                let mut team_b = Vec::new();
+                for user_id in record.team_ids(Team::B) {
+                    team_b.push(user_name(user_id).await);
+                }
+                let (score_a, score_b) = result.score
+                    .map(|(score_a, score_b)| (score_a.to_string(), score_b.to_string()))
+                    .unwrap_or_default();
+                csv.push_str(&csv_row(&[&ended, record.draft.map.as_deref().unwrap_or(""), &result.team_a_name, &result.team_b_name,
+                    &score_a, &score_b, &team_a.join("; "), &team_b.join("; ")]));
+            }
+            ("matches.csv", csv)
+        }
+        Some("stats") => {
+            let mut csv = String::from("user_id,name,wins,losses,win_rate,dodges,no_shows,cooldown_until\n");
+            let player_stats: &HashMap<u64, PlayerStats> = data.get::<PlayerStatsCache>().unwrap();
+            let mut players: Vec<(&u64, &PlayerStats)> = player_stats.iter().collect();
+            players.sort_by_key(|(user_id, _)| **user_id);
+            for (user_id, stats) in players {
+                let played = stats.wins + stats.losses;
+                let win_rate = if played == 0 { String::new() } else { format!("{:.1}", stats.wins as f64 * 100.0 / played as f64) };
+                let cooldown_until = stats.cooldown_until
+                    .and_then(|timestamp| Local.timestamp_opt(timestamp, 0).single())
+                    .filter(|until| until > &Local::now())
+                    .map(|until| until.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_default();
+                csv.push_str(&csv_row(&[&user_id.to_string(), &user_name(*user_id).await, &stats.wins.to_string(), &stats.losses.to_string(),
+                    &win_rate, &stats.dodges.to_string(), &stats.no_shows.to_string(), &cooldown_until]));
+            }
+            ("stats.csv", csv)
+        }
+        _ => return Err(BotError::Usage(String::from(" invalid message formatting. Example: `.export matches`, `.export stats` or `.export stats dm`"))),
+    };
+    drop(data);
+    let channel_id = if dm { msg.author.create_dm_channel(&context.http).await?.id } else { msg.channel_id };
+    channel_id.send_message(&context.http, |m| m
+        .content(format!("Exported `{}`", filename))
+        .add_file(AttachmentType::Bytes { data: Cow::from(csv.into_bytes()), filename: String::from(filename) }))
+        .await?;
+    if dm {
+        send_simple_tagged_msg(&context, &msg, " sent you the export in a direct message.", &msg.author).await;
+    }
+    Ok(())
+}
+
+const BACKUP_VERSION: u32 = 1;
+
+pub(crate) const QUEUE_EVENT_DAYS: i64 = 90;
+
+/// Busiest hours and days, the average time to fill the queue and how often queues fall apart, over the last 30 days by default
+pub(crate) async fn handle_analytics(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let days = match msg.content.split_whitespace().nth(1).map(|days| days.parse::<i64>()) {
+        None => 30,
+        Some(Ok(days)) if (1..=QUEUE_EVENT_DAYS).contains(&days) => days,
+        Some(_) => return Err(BotError::Usage(format!(" invalid message formatting. Example: `.analytics` or `.analytics 7`, up to {} days", QUEUE_EVENT_DAYS))),
+    };
+    let data = context.data.read().await;
+    let since = (Local::now() - ChronoDuration::days(days)).timestamp();
+    let events: Vec<&QueueEvent> = data.get::<QueueEvents>().unwrap().iter().filter(|event| event.time >= since).collect();
+    let joins: Vec<DateTime<Local>> = events.iter()
+        .filter(|event| event.kind == QueueEventKind::Join)
+        .filter_map(|event| Local.timestamp_opt(event.time, 0).single())
+        .collect();
+    if joins.is_empty() {
+        send_simple_tagged_msg(&context, &msg, &format!(" no queue activity was recorded in the last {} days.", days), &msg.author).await;
+        return Ok(());
+    }
+    let mut hours = [0usize; 24];
+    let mut weekdays = [0usize; 7];
+    for time in &joins {
+        hours[time.hour() as usize] += 1;
+        weekdays[time.weekday().num_days_from_monday() as usize] += 1;
+    }
+    let mut busiest_hours: Vec<usize> = (0..24).filter(|hour| hours[*hour] > 0).collect();
+    busiest_hours.sort_by_key(|hour| std::cmp::Reverse(hours[*hour]));
+    let busiest_hours: String = busiest_hours.iter()
+        .take(3)
+        .map(|hour| format!("{:02}:00-{:02}:00 ({} joins)\n", hour, (hour + 1) % 24, hours[*hour]))
+        .collect();
+    let day_names = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+    let mut busiest_days: Vec<usize> = (0..7).filter(|day| weekdays[*day] > 0).collect();
+    busiest_days.sort_by_key(|day| std::cmp::Reverse(weekdays[*day]));
+    let busiest_days: String = busiest_days.iter()
+        .take(3)
+        .map(|day| format!("{} ({} joins)\n", day_names[*day], weekdays[*day]))
+        .collect();
+    let QueueSessions { sessions, abandoned, .. } = QueueSessions::replay(events.iter().copied());
+    let fill_times: Vec<(i64, i64)> = events.iter().filter_map(|event| event.fill_seconds.map(|seconds| (event.time, seconds))).collect();
+    let leaves = events.iter().filter(|event| event.kind == QueueEventKind::Leave).count();
+    let average_fill = if fill_times.is_empty() {
+        String::from("-")
+    } else {
+        let average = fill_times.iter().map(|(_, seconds)| seconds).sum::<i64>() / fill_times.len() as i64;
+        format!("{} over {} full queues", format_fill_time(average), fill_times.len())
+    };
+    let recent_fills: String = fill_times.iter()
+        .rev()
+        .take(5)
+        .filter_map(|(time, seconds)| Local.timestamp_opt(*time, 0).single().map(|time| format!("{} - {}\n", time.format("%m/%d %H:%M"), format_fill_time(*seconds))))
+        .collect();
+    let finished = fill_times.len() + abandoned;
+    let abandonment = if finished == 0 {
+        String::from("-")
+    } else {
+        format!("{:.0}% of {} queues emptied or were cleared before filling", abandoned as f64 * 100.0 / finished as f64, finished)
+    };
+    let description = format!("**Joins:** {} ({} queues started)\n**Leaves:** {} ({:.0}% of joins)\n**Average time to fill:** {}\n**Abandonment:** {}",
+                              joins.len(), sessions, leaves, leaves as f64 * 100.0 / joins.len() as f64, average_fill, abandonment);
+    if let Err(why) = msg.channel_id.send_message(&context.http, |m| m
+        .embed(|e| e
+            .title(format!("Queue Analytics (last {} days)", days))
+            .description(description)
+            .field("Busiest hours", busiest_hours, true)
+            .field("Busiest days", busiest_days, true)
+            .field("Recent fill times", if recent_fills.is_empty() { String::from("-") } else { recent_fills }, true)))
+        .await {
+        ops::report(&context, format!("Cannot send the analytics embed: {:?}", why)).await;
+    }
+    Ok(())
+}
+
+/// A member's queue joins, matches, dodges, kicks and strikes over the last 30 and 90 days
+pub(crate) async fn handle_activity(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let user = msg.mentions.first()
+        .ok_or_else(|| BotError::Usage(String::from(" please mention a discord user in your message i.e. `.activity @user`")))?;
+    let data = context.data.read().await;
+    let user_id = *user.id.as_u64();
+    let queue_events: Vec<&QueueEvent> = data.get::<QueueEvents>().unwrap().iter().filter(|event| event.user_id == Some(user_id)).collect();
+    let stats = data.get::<PlayerStatsCache>().unwrap().get(&user_id).cloned().unwrap_or_default();
+    let match_history: &Vec<Match> = data.get::<MatchHistory>().unwrap();
+    let summary = |days: i64| {
+        let since = (Local::now() - ChronoDuration::days(days)).timestamp();
+        let count = |kind: QueueEventKind| queue_events.iter().filter(|event| event.kind == kind && event.time >= since).count();
+        let played = match_history.iter()
+            .filter(|record| record.result.as_ref().is_some_and(|result| result.ended >= since) && record.has_player(user_id))
+            .count();
+        let strikes = stats.strikes.iter().filter(|strike| strike.created >= since).count();
+        format!("Queue joins: {}\nQueue leaves: {}\nMatches played: {}\nDodges: {}\nKicks: {}\nStrikes: {}",
+                count(QueueEventKind::Join), count(QueueEventKind::Leave), played, count(QueueEventKind::Dodge), count(QueueEventKind::Kick), strikes)
+    };
+    let mut description = format!("**All time:** {} dodges, {} no-shows, {} strikes", stats.dodges, stats.no_shows, stats.strikes.len());
+    if let Some(until) = queue_cooldown(&data, user) {
+        description.push_str(&format!("\n**Queue cooldown until:** {}", until.format("%m/%d %H:%M")));
+    }
+    if let Err(why) = msg.channel_id.send_message(&context.http, |m| m
+        .embed(|e| e
+            .title(format!("Activity for {}", user.name))
+            .description(description)
+            .field("Last 30 days", summary(30), true)
+            .field("Last 90 days", summary(QUEUE_EVENT_DAYS), true)))
+        .await {
+        ops::report(&context, format!("Cannot send the activity embed: {:?}", why)).await;
+    }
+    Ok(())
+}
+
+pub(crate) async fn handle_backup(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let data = context.data.read().await;
+    let backup = Backup {
+        version: BACKUP_VERSION,
+        created: Local::now().timestamp(),
+        riot_ids: data.get::<RiotIdCache>().unwrap().clone(),
+        riot_accounts: data.get::<RiotAccounts>().unwrap().clone(),
+        regions: data.get::<RegionCache>().unwrap().clone(),
+        teamnames: data.get::<TeamNameCache>().unwrap().clone(),
+        maps: data.get::<Maps>().unwrap().clone(),
+        map_pools: data.get::<MapPools>().unwrap().clone(),
+        map_images: data.get::<MapImages>().unwrap().clone(),
+        player_stats: data.get::<PlayerStatsCache>().unwrap().clone(),
+        match_history: data.get::<MatchHistory>().unwrap().clone(),
+        aliases: data.get::<CommandAliases>().unwrap().clone(),
+    };
+    let filename = format!("scrimbot-backup-{}.json", Local::now().format("%Y%m%d-%H%M"));
+    let content = serde_json::to_vec(&backup)?;
+    let dm_channel = msg.author.create_dm_channel(&context.http).await?;
+    dm_channel.send_message(&context.http, |m| m
+        .content(format!("Backup with {} Riot IDs, {} players' stats and {} matches. Restore it with `.restore` and this file attached.",
+                         backup.riot_ids.len(), backup.player_stats.len(), backup.match_history.len()))
+        .add_file(AttachmentType::Bytes { data: Cow::from(content), filename }))
+        .await?;
+    send_simple_tagged_msg(&context, &msg, " sent you the backup in a direct message.", &msg.author).await;
+    audit_log(&context, &data, "Backup", format!("<@{}> downloaded a backup", msg.author.id)).await;
+    Ok(())
+}
+
+pub(crate) async fn handle_restore(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    let attachment = msg.attachments.first()
+        .ok_or_else(|| BotError::Usage(String::from(" attach a backup file from `.backup` to the message i.e. `.restore` with the file attached")))?;
+    let backup: Backup = match serde_json::from_slice(&attachment.download().await?) {
+        Ok(backup) => backup,
+        Err(why) => return Err(BotError::Usage(format!(" this file is not a valid backup: {}", why))),
+    };
+    if backup.version != BACKUP_VERSION {
+        return Err(BotError::Usage(format!(" this backup has version {}, this bot only restores version {}", backup.version, BACKUP_VERSION)));
+    }
+    if backup.maps.len() > 26 {
+        return Err(BotError::Usage(format!(" this backup has {} maps, the map vote supports at most 26", backup.maps.len())));
+    }
+    let created = Local.timestamp_opt(backup.created, 0).single()
+        .map(|created| created.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| String::from("an unknown date"));
+    let summary = format!("the backup from {} with {} Riot IDs, {} team names, {} maps, {} players' stats and {} matches",
+                          created, backup.riot_ids.len(), backup.teamnames.len(), backup.maps.len(), backup.player_stats.len(), backup.match_history.len());
+    if msg.content.split_whitespace().nth(1).map(|arg| arg.eq_ignore_ascii_case("confirm")) != Some(true) {
+        send_simple_tagged_msg(&context, &msg, &format!(" this replaces the current data with {}. Send `.restore confirm` with the file attached to continue.", summary), &msg.author).await;
+        return Ok(());
+    }
+    let mut data = context.data.write().await;
+    data.insert::<RiotIdCache>(backup.riot_ids);
+    data.insert::<RiotAccounts>(backup.riot_accounts);
+    data.insert::<RegionCache>(backup.regions);
+    data.insert::<TeamNameCache>(backup.teamnames);
+    data.insert::<Maps>(backup.maps);
+    data.insert::<MapPools>(backup.map_pools);
+    data.insert::<MapImages>(backup.map_images);
+    data.insert::<PlayerStatsCache>(backup.player_stats);
+    data.insert::<MatchHistory>(backup.match_history);
+    data.insert::<CommandAliases>(backup.aliases);
+    save_caches(&data).await;
+    send_simple_tagged_msg(&context, &msg, &format!(" restored {}.", summary), &msg.author).await;
+    audit_log(&context, &data, "Restore", format!("<@{}> restored {}", msg.author.id, summary)).await;
+    Ok(())
+}
+
+pub(crate) async fn handle_spectate(context: Context, msg: Message) -> BotResult {
+    let mut data = context.data.write().await;
+    let bot_state: &StateContainer = data.get::<BotState>().unwrap();
+    if bot_state.state == State::Queue {
+        return Err(BotError::Usage(String::from(" you can only spectate after `.start`")));
+    }
+    let user_queue: &Vec<User> = data.get::<UserQueue>().unwrap();
+    if user_queue.contains(&msg.author) {
+        return Err(BotError::Usage(String::from(" players in the queue cannot spectate")));
+    }
+    let max_spectators = data.get::<Config>().unwrap().max_spectators;
+    let spectators: &mut Vec<User> = data.get_mut::<Spectators>().unwrap();
+    if let Some(index) = spectators.iter().position(|user| user == &msg.author) {
+        spectators.remove(index);
+        send_simple_tagged_msg(&context, &msg, " is no longer spectating.", &msg.author).await;
+        return Ok(());
+    }
+    if let Some(max_spectators) = max_spectators {
+        if spectators.len() >= max_spectators {
+            return Err(BotError::Usage(String::from(" sorry but the spectator slots are full.")));
+        }
+    }
+    spectators.push(msg.author.clone());
+    send_simple_tagged_msg(&context, &msg, &format!(" is now spectating. Spectators: {}", spectators.len()), &msg.author).await;
+    Ok(())
+}
+
+pub(crate) async fn handle_coach(context: Context, msg: Message) -> BotResult {
+    let mut data = context.data.write().await;
+    let bot_state: &StateContainer = data.get::<BotState>().unwrap();
+    if bot_state.state == State::Queue || bot_state.state == State::Ready {
+        return Err(BotError::Usage(String::from(" you can only join as a coach during the `.start` process")));
+    }
+    let split_content = msg.content.trim().split(' ').filter(|s| !s.is_empty()).collect::<Vec<_>>();
+    let team_a = match split_content.get(1).map(|team| team.to_lowercase()).as_deref() {
+        Some("a") => true,
+        Some("b") => false,
+        _ => {
+            return Err(BotError::Usage(String::from(" invalid message formatting. Example: `.coach A` or `.coach B`")));
+        }
+    };
+    let user_queue: &Vec<User> = data.get::<UserQueue>().unwrap();
+    if user_queue.contains(&msg.author) {
+        return Err(BotError::Usage(String::from(" players in the queue cannot coach")));
+    }
+    let draft: &mut Draft = &mut data.get_mut::<CurrentMatch>().unwrap().draft;
+    let (coach, other_coach) = if team_a {
+        (&mut draft.coach_a, &mut draft.coach_b)
+    } else {
+        (&mut draft.coach_b, &mut draft.coach_a)
+    };
+    if let Some(current_coach) = coach {
+        if current_coach == &msg.author {
+            send_simple_tagged_msg(&context, &msg, " you're already the coach of this team!", &msg.author).await;
+        } else {
+            send_simple_tagged_msg(&context, &msg, &format!(" this team already has a coach: @{}", current_coach.name), &msg.author).await;
+        }
+        return Ok(());
+    }
+    if other_coach.as_ref() == Some(&msg.author) {
+        *other_coach = None;
+    }
+    *coach = Some(msg.author.clone());
+    send_simple_tagged_msg(&context, &msg, &format!(" is set as the coach for Team {}", if team_a { "A" } else { "B" }), &msg.author).await;
+    Ok(())
+}
+
+pub(crate) async fn handle_needsub(context: Context, msg: Message) -> BotResult {
+    let data = context.data.write().await;
+    let last_match: &Draft = match data.get::<LastMatch>().unwrap() {
+        Some(last_match) => &last_match.draft,
+        None => {
+            return Err(BotError::Usage(String::from(" there is no completed setup to request a sub for.")));
+        }
+    };
+    let team_a = if last_match.captain_a.as_ref() == Some(&msg.author) {
+        true
+    } else if last_match.captain_b.as_ref() == Some(&msg.author) {
+        false
+    } else {
+        return Err(BotError::Usage(String::from(" only captains of the last match can request a sub.")));
+    };
+    let team = if team_a { &last_match.team_a } else { &last_match.team_b };
+    let replacing: User = msg.mentions.first().cloned()
+        .ok_or_else(|| BotError::Usage(String::from(" please tag the player to replace i.e. `.needsub @user`")))?;
+    if !team.contains(&replacing) {
+        return Err(BotError::Usage(String::from(" the player you want to replace is not on your team.")));
+    }
+    let captain = if team_a { last_match.captain_a.as_ref().unwrap() } else { last_match.captain_b.as_ref().unwrap() };
+    let team_name = team_label(&data, captain);
+    let config: &Config = data.get::<Config>().unwrap();
+    let mut response = MessageBuilder::new();
+    if let Some(sub_role_id) = enabled_id(&data, config.discord.sub_role_id) {
+        response.role(sub_role_id).push(" ");
+    }
+    response.push(format!("Team {} needs a sub for ", team_name)).mention(&replacing);
+    if let Some(map) = &last_match.map {
+        response.push(format!(" on `{}`", map));
+    }
+    response.push(". First to react with ✅ takes the spot.");
+    drop(data);
+    let sub_msg = match delivery::post(&context.http, msg.channel_id, &response.build()).await {
+        Some(sub_msg) => sub_msg,
+        None => return Ok(()),
+    };
+    if let Err(why) = delivery::react(&context.http, sub_msg.channel_id, sub_msg.id, ReactionType::Unicode(String::from("✅"))).await {
+        eprintln!("Error adding reaction: {:?}", why);
+    }
+    let mut data = context.data.write().await;
+    let sub_requests: &mut HashMap<u64, SubRequest> = data.get_mut::<SubRequests>().unwrap();
+    sub_requests.insert(*sub_msg.id.as_u64(), SubRequest { team_a, replacing });
+    Ok(())
+}
+
+pub(crate) async fn handle_sub_reaction(context: Context, reaction: Reaction) {
+    if reaction.emoji != ReactionType::Unicode(String::from("✅")) { return; }
+    if !context.data.read().await.get::<SubRequests>().unwrap().contains_key(reaction.message_id.as_u64()) { return; }
+    let user = match reaction.user(&context.http).await {
+        Ok(user) => user,
+        Err(_) => return,
+    };
+    if user.bot { return; }
+    let mut data = context.data.write().await;
+    // the request may have been taken while the user was fetched
+    if !data.get::<SubRequests>().unwrap().contains_key(reaction.message_id.as_u64()) { return; }
+    let last_match: &Draft = match data.get::<LastMatch>().unwrap() {
+        Some(last_match) => &last_match.draft,
+        None => return,
+    };
+    if last_match.team_a.contains(&user) || last_match.team_b.contains(&user) { return; }
+    let riot_id_cache: &HashMap<u64, String> = data.get::<RiotIdCache>().unwrap();
+    let riot_id = match riot_id_cache.get(user.id.as_u64()) {
+        Some(riot_id) => String::from(riot_id),
+        None => {
+            let response = MessageBuilder::new()
+                .mention(&user)
+                .push(" riotid not found for your discord user, \
+                    please use `.riotid <your riotid>` to assign one before subbing in.")
+                .build();
+            delivery::say(&context.http, reaction.channel_id, &response).await;
+            return;
+        }
+    };
+    let sub_request: SubRequest = data.get_mut::<SubRequests>().unwrap().remove(reaction.message_id.as_u64()).unwrap();
+    let last_match: &mut Draft = &mut data.get_mut::<LastMatch>().unwrap().as_mut().unwrap().draft;
+    let team: &mut Vec<User> = if sub_request.team_a { &mut last_match.team_a } else { &mut last_match.team_b };
+    team.retain(|member| member != &sub_request.replacing);
+    team.push(user.clone());
+    let captain = if sub_request.team_a { last_match.captain_a.clone().unwrap() } else { last_match.captain_b.clone().unwrap() };
+    let team_name = team_label(&data, &captain);
+    let mut response = MessageBuilder::new();
+    response.mention(&user)
+        .push(format!(" (`{}`) has subbed in for Team {}, replacing ", riot_id, team_name))
+        .mention(&sub_request.replacing);
+    let team_role_id = data.get::<ActiveMatch>().unwrap().as_ref()
+        .and_then(|active_match| if sub_request.team_a { active_match.team_a_role_id } else { active_match.team_b_role_id });
+    let team_channel_id = team_channel_id(&data, sub_request.team_a);
+    drop(data);
+    delivery::say(&context.http, reaction.channel_id, &response.build()).await;
+    if let (Some(role_id), Some(guild_id)) = (team_role_id, reaction.guild_id) {
+        if let Err(why) = context.http.add_member_role(*guild_id.as_u64(), *user.id.as_u64(), *role_id.as_u64()).await {
+            ops::report(&context, format!("Cannot add team role to user: {:?}", why)).await;
+        }
+        if let Err(why) = context.http.remove_member_role(*guild_id.as_u64(), *sub_request.replacing.id.as_u64(), *role_id.as_u64()).await {
+            ops::report(&context, format!("Cannot remove team role from user: {:?}", why)).await;
+        }
+    }
+    if let (Some(channel_id), Some(guild_id)) = (team_channel_id, reaction.guild_id) {
+        let permissions = PermissionOverwrite {
+            allow: Permissions::CONNECT | Permissions::SPEAK,
+            deny: Permissions::empty(),
+            kind: PermissionOverwriteType::Member(user.id),
+        };
+        if let Err(why) = channel_id.create_permission(&context.http, &permissions).await {
+            ops::report(&context, format!("Cannot update team channel permissions: {:?}", why)).await;
+        }
+        if let Err(why) = channel_id.delete_permission(&context.http, PermissionOverwriteType::Member(sub_request.replacing.id)).await {
+            ops::report(&context, format!("Cannot update team channel permissions: {:?}", why)).await;
+        }
+        if let Err(why) = guild_id.move_member(&context.http, user.id, channel_id).await {
+            ops::report(&context, format!("Cannot move user: {:?}", why)).await;
+        }
+    }
+}
+
+pub(crate) async fn handle_replace(context: Context, msg: Message) -> BotResult {
+    if !admin_check(&context, &msg, true).await { return Ok(()); }
+    // msg.mentions isn't in message order, so the players are read from the message text
+    let mentioned: Vec<User> = msg.content
+        .split_whitespace()
+        .filter_map(parse_username)
+        .filter_map(|id| msg.mentions.iter().find(|user| *user.id.as_u64() == id).cloned())
+        .collect();
+    let (missing, substitute) = match mentioned.as_slice() {
+        [missing, substitute] if missing != substitute => (missing.clone(), substitute.clone()),
+        _ => {
+            return Err(BotError::Usage(String::from(" invalid message formatting. Example: `.replace @missing @substitute`")));
+        }
+    };
+    // the substitute joins the queue, so the same rules as `.join` apply
+    queue_join_check(&context, msg.guild_id, &[&substitute]).await?;
+    let mut data = context.data.write().await;
+    let state = data.get::<BotState>().unwrap().state.clone();
+    if state == State::Queue || state == State::Ready {
+        return Err(BotError::Usage(String::from(" command only valid during `.start` process")));
+    }
+    let user_queue: &Vec<User> = data.get::<UserQueue>().unwrap();
+    if !user_queue.contains(&missing) {
+        return Err(BotError::Usage(String::from(" this user is not in the queue")));
+    }
+    if user_queue.contains(&substitute) {
+        return Err(BotError::Usage(String::from(" the substitute is already in the queue")));
+    }
+    let riot_id = match data.get::<RiotIdCache>().unwrap().get(substitute.id.as_u64()) {
+        Some(riot_id) => riot_id.clone(),
+        None => {
+            return Err(BotError::Usage(String::from(" the substitute has no Riot ID set, they can set one with `.riotid`")));
+        }
+    };
+    let position = user_queue.iter().position(|user| user == &missing).unwrap();
+    remove_from_queue(&mut data, std::slice::from_ref(&missing));
+    data.get_mut::<UserQueue>().unwrap().insert(position, substitute.clone());
+    data.get_mut::<QueueJoinTimes>().unwrap().insert(*substitute.id.as_u64(), Local::now());
+    data.get_mut::<Waitlist>().unwrap().retain(|user| user != &substitute);
+    data.get_mut::<BotState>().unwrap().state.replace_captain(&missing, &substitute);
+    let draft: &mut Draft = &mut data.get_mut::<CurrentMatch>().unwrap().draft;
+    for user in draft.team_a.iter_mut().chain(draft.team_b.iter_mut()) {
+        if user == &missing {
+            *user = substitute.clone();
+        }
+    }
+    let team = if draft.team_a.contains(&substitute) {
+        " and takes their spot on Team A"
+    } else if draft.team_b.contains(&substitute) {
+        " and takes their spot on Team B"
+    } else {
+        ""
+    };
     let response = MessageBuilder::new()
-        .mention(&msg.author)
-        .push(" added map: `")
-        .push(&map_name)
-        .push("`")
+        .mention(&substitute)
+        .push(format!(" (`{}`) has replaced ", riot_id))
+        .mention(&missing)
+        .push(team)
         .build();
-    if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-        eprintln!("Error sending message: {:?}", why);
-    }
+    delivery::say(&context.http, msg.channel_id, &response).await;
+    audit_log(&context, &data, "Replace", format!("<@{}> replaced <@{}> with <@{}>", msg.author.id, missing.id, substitute.id)).await;
+    Ok(())
 }
 
-pub(crate) async fn handle_remove_map(context: Context, msg: Message) {
-    if !admin_check(&context, &msg, true).await { return; }
+pub(crate) async fn handle_transfer_captain(context: Context, msg: Message) -> BotResult {
+    let is_admin = admin_check(&context, &msg, false).await;
     let mut data = context.data.write().await;
-    let maps: &mut Vec<String> = data.get_mut::<Maps>().unwrap();
-    let map_name: String = String::from(msg.content.trim().split(" ").take(2).collect::<Vec<_>>()[1]);
-    if !maps.contains(&map_name) {
-        let response = MessageBuilder::new()
-            .mention(&msg.author)
-            .push(" this map doesn't exist in the list.")
-            .build();
-        if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-            eprintln!("Error sending message: {:?}", why);
+    // during the draft and side pick the captaincy changes in the setup, after the setup in the match in progress
+    let in_draft = data.get::<BotState>().unwrap().state.captains().is_some();
+    if !in_draft && data.get::<ActiveMatch>().unwrap().is_none() {
+        return Err(BotError::Usage(String::from(" captaincy can only be transferred during the draft, the side pick or while a match is in progress.")));
+    }
+    let user = match msg.mentions.first() {
+        Some(user) => user.clone(),
+        None => {
+            return Err(BotError::Usage(String::from(" invalid message formatting. Example: `.transfercaptain @user`")));
         }
-        return;
+    };
+    let draft: &Draft = if in_draft {
+        &data.get::<CurrentMatch>().unwrap().draft
+    } else {
+        &data.get::<LastMatch>().unwrap().as_ref().unwrap().draft
+    };
+    let team_a = draft.team_a.contains(&user);
+    if !team_a && !draft.team_b.contains(&user) {
+        return Err(BotError::Usage(String::from(" this player is not on a team")));
     }
-    let index = maps.iter().position(|m| m == &map_name).unwrap();
-    maps.remove(index);
-    write_to_file(String::from("maps.json"), serde_json::to_string(maps).unwrap()).await;
-    let response = MessageBuilder::new()
-        .mention(&msg.author)
-        .push(" removed map: `")
-        .push(&map_name)
-        .push("`")
-        .build();
-    if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-        eprintln!("Error sending message: {:?}", why);
+    let team = if team_a { Team::A } else { Team::B };
+    let captain = match data.get::<BotState>().unwrap().state.captains() {
+        Some(captains) => Some(captains.get(team)),
+        None => draft.captain(team),
+    };
+    if captain == Some(&user) {
+        return Err(BotError::Usage(String::from(" this player is already the captain")));
+    }
+    if !is_admin && captain != Some(&msg.author) {
+        return Err(BotError::Usage(String::from(" only admins and the team's captain can transfer captaincy to a teammate.")));
+    }
+    if in_draft {
+        let state = &mut data.get_mut::<BotState>().unwrap().state;
+        if let Some(captains) = state.captains_mut() {
+            *captains.get_mut(team) = user.clone();
+        }
+        // the new captain gets a full turn before they can be skipped
+        if let State::Draft { turn, .. } = state {
+            if turn.team == team {
+                *turn = Turn::start(team);
+            }
+        }
+    } else {
+        let draft: &mut Draft = &mut data.get_mut::<LastMatch>().unwrap().as_mut().unwrap().draft;
+        *if team_a { &mut draft.captain_a } else { &mut draft.captain_b } = Some(user.clone());
     }
-}
-
-pub(crate) async fn handle_unknown(context: Context, msg: Message) {
     let response = MessageBuilder::new()
-        .push("Unknown command, type `.help` for list of commands.")
+        .mention(&user)
+        .push(format!(" is now the captain of Team {}", if team_a { "A" } else { "B" }))
+        .push(format!(", the team is now called Team {}", team_label(&data, &user)))
         .build();
-    if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-        eprintln!("Error sending message: {:?}", why);
+    delivery::say(&context.http, msg.channel_id, &response).await;
+    audit_log(&context, &data, "Transfer captain", format!("<@{}> made <@{}> captain of Team {}", msg.author.id, user.id, if team_a { "A" } else { "B" })).await;
+    if data.get::<BotState>().unwrap().state.is_draft() {
+        update_draft_embed(&context, msg.channel_id, &mut data).await;
     }
+    Ok(())
 }
 
-pub(crate) async fn write_to_file(path: String, content: String) {
-    let mut error_string = String::from("Error writing to ");
-    error_string.push_str(&path);
-    std::fs::write(path, content)
-        .expect(&error_string);
-}
-
-pub(crate) async fn handle_ready(context: &Context, msg: &Message) {
+pub(crate) async fn handle_swap(context: Context, msg: Message) -> BotResult {
+    let is_admin = admin_check(&context, &msg, false).await;
     let mut data = context.data.write().await;
-    let draft: &Draft = &data.get::<Draft>().unwrap().clone();
-    let riot_id_cache: &HashMap<u64, String> = &data.get::<RiotIdCache>().unwrap().clone();
-    let teamname_cache = data.get::<TeamNameCache>().unwrap();
-    let team_a_name = teamname_cache.get(draft.captain_a.as_ref().unwrap().id.as_u64())
-        .unwrap_or(&draft.captain_a.as_ref().unwrap().name);
-    let team_b_name = teamname_cache.get(draft.captain_b.as_ref().unwrap().id.as_u64())
-        .unwrap_or(&draft.captain_b.as_ref().unwrap().name);
-    let team_a: String = draft.team_a
-        .iter()
-        .map(|user| format!("- @{}: `{}`\n", &user.name, riot_id_cache.get(user.id.as_u64()).unwrap()))
-        .collect();
-    let team_b: String = draft.team_b
-        .iter()
-        .map(|user| format!("- @{}: `{}`\n", &user.name, riot_id_cache.get(user.id.as_u64()).unwrap()))
-        .collect();
-    let response = MessageBuilder::new()
-        .push_bold_line(format!("Team {}:", team_a_name))
-        .push_line(team_a)
-        .push_bold_line(format!("Team {}:", team_b_name))
-        .push_line(team_b)
-        .build();
-
-    if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-        eprintln!("Error sending message: {:?}", why);
+    // during the side pick the swap changes the draft, after the setup it changes the match in progress
+    let in_side_pick = data.get::<BotState>().unwrap().state.is_side_pick();
+    if !in_side_pick && data.get::<ActiveMatch>().unwrap().is_none() {
+        return Err(BotError::Usage(String::from(" players can only be swapped during the side pick or while a match is in progress.")));
     }
-    let config: &Config = &data.get::<Config>().unwrap();
-    if let Some(team_a_channel_id) = config.discord.team_a_channel_id {
-        for user in &draft.team_a {
-            move_user(msg, user, team_a_channel_id, &context).await;
+    let draft: &Draft = if in_side_pick {
+        &data.get::<CurrentMatch>().unwrap().draft
+    } else {
+        &data.get::<LastMatch>().unwrap().as_ref().unwrap().draft
+    };
+    let captains = match data.get::<BotState>().unwrap().state.captains() {
+        Some(captains) => [captains.a.clone(), captains.b.clone()],
+        None => [draft.captain_a.clone().unwrap(), draft.captain_b.clone().unwrap()],
+    };
+    if !is_admin && !captains.contains(&msg.author) {
+        return Err(BotError::Usage(String::from(" only admins and captains can swap players.")));
+    }
+    let (player_a, player_b) = match msg.mentions.as_slice() {
+        [first, second] if draft.team_a.contains(first) && draft.team_b.contains(second) => (first.clone(), second.clone()),
+        [first, second] if draft.team_b.contains(first) && draft.team_a.contains(second) => (second.clone(), first.clone()),
+        _ => {
+            return Err(BotError::Usage(String::from(" tag one player from each team. Example: `.swap @playerA @playerB`")));
         }
+    };
+    if captains.contains(&player_a) || captains.contains(&player_b) {
+        return Err(BotError::Usage(String::from(" captains cannot be swapped.")));
     }
-    if let Some(team_b_channel_id) = config.discord.team_b_channel_id {
-        for user in &draft.team_b {
-            move_user(msg, user, team_b_channel_id, &context).await;
+    if !is_admin {
+        let mut players = vec![*player_a.id.as_u64(), *player_b.id.as_u64()];
+        players.sort_unstable();
+        let pending_swap: &mut Option<SwapRequest> = data.get_mut::<PendingSwap>().unwrap();
+        match pending_swap {
+            Some(request) if request.players == players => {
+                if !request.confirmed.contains(msg.author.id.as_u64()) {
+                    request.confirmed.push(*msg.author.id.as_u64());
+                }
+            }
+            _ => *pending_swap = Some(SwapRequest { players, confirmed: vec![*msg.author.id.as_u64()] }),
+        }
+        if pending_swap.as_ref().unwrap().confirmed.len() < 2 {
+            let other_captain = if captains[0] == msg.author { &captains[1] } else { &captains[0] };
+            let response = MessageBuilder::new()
+                .mention(other_captain)
+                .push(" type `.swap ")
+                .mention(&player_a)
+                .push(" ")
+                .mention(&player_b)
+                .push("` to confirm the swap.")
+                .build();
+            delivery::say(&context.http, msg.channel_id, &response).await;
+            return Ok(());
         }
     }
-    if let Some(post_start_msg) = &config.post_setup_msg {
-        if let Err(why) = msg.channel_id.say(&context.http, &post_start_msg).await {
-            eprintln!("Error sending message: {:?}", why);
+    *data.get_mut::<PendingSwap>().unwrap() = None;
+    let draft: &mut Draft = if in_side_pick {
+        &mut data.get_mut::<CurrentMatch>().unwrap().draft
+    } else {
+        &mut data.get_mut::<LastMatch>().unwrap().as_mut().unwrap().draft
+    };
+    draft.team_a.retain(|user| user != &player_a);
+    draft.team_b.retain(|user| user != &player_b);
+    draft.team_a.push(player_b.clone());
+    draft.team_b.push(player_a.clone());
+    let team_a: String = draft.team_a.iter().map(|user| format!("<@{}> ", user.id)).collect();
+    let team_b: String = draft.team_b.iter().map(|user| format!("<@{}> ", user.id)).collect();
+    let team_a_name = team_label(&data, &captains[0]);
+    let team_b_name = team_label(&data, &captains[1]);
+    let response = MessageBuilder::new()
+        .mention(&player_a)
+        .push(" and ")
+        .mention(&player_b)
+        .push_line(" have been swapped.")
+        .push_line(format!("Team {}: {}", team_a_name, team_a))
+        .push_line(format!("Team {}: {}", team_b_name, team_b))
+        .build();
+    delivery::say(&context.http, msg.channel_id, &response).await;
+    audit_log(&context, &data, "Swap", format!("<@{}> swapped <@{}> and <@{}>", msg.author.id, player_a.id, player_b.id)).await;
+    if !in_side_pick {
+        if let Some(guild_id) = msg.guild_id {
+            let active_match = data.get::<ActiveMatch>().unwrap().clone();
+            let team_channel_ids = (team_channel_id(&data, true), team_channel_id(&data, false));
+            drop(data);
+            switch_team(&context, active_match.as_ref(), team_channel_ids.1, guild_id, &player_a, false).await;
+            switch_team(&context, active_match.as_ref(), team_channel_ids.0, guild_id, &player_b, true).await;
         }
     }
-    // reset to queue state
-    let user_queue: &mut Vec<User> = data.get_mut::<UserQueue>().unwrap();
-    user_queue.clear();
-    let draft: &mut Draft = &mut data.get_mut::<Draft>().unwrap();
-    draft.team_a = vec![];
-    draft.team_b = vec![];
-    draft.captain_a = None;
-    draft.captain_b = None;
-    draft.current_picker = None;
-    let bot_state: &mut StateContainer = &mut data.get_mut::<BotState>().unwrap();
-    bot_state.state = State::Queue;
-    let queue_msgs: &mut HashMap<u64, String> = &mut data.get_mut::<QueueMessages>().unwrap();
-    queue_msgs.clear();
+    Ok(())
 }
 
-pub(crate) async fn handle_cancel(context: Context, msg: Message) {
-    if !admin_check(&context, &msg, true).await { return; }
-    let mut data = context.data.write().await;
-    let bot_state: &StateContainer = &data.get::<BotState>().unwrap();
-    if bot_state.state == State::Queue {
-        send_simple_tagged_msg(&context, &msg, " command only valid during `.start` process", &msg.author).await;
-        return;
+/// Moves a player of the match in progress to the other team's role, voice channel and thread
+async fn switch_team(context: &Context, active_match: Option<&ActiveMatchInfo>, team_channel_id: Option<ChannelId>, guild_id: GuildId, user: &User, team_a: bool) {
+    if let Some(active_match) = active_match {
+        let (new_role_id, old_role_id) = if team_a {
+            (active_match.team_a_role_id, active_match.team_b_role_id)
+        } else {
+            (active_match.team_b_role_id, active_match.team_a_role_id)
+        };
+        if let Some(role_id) = old_role_id {
+            if let Err(why) = context.http.remove_member_role(*guild_id.as_u64(), *user.id.as_u64(), *role_id.as_u64()).await {
+                ops::report(context, format!("Cannot remove team role from user: {:?}", why)).await;
+            }
+        }
+        if let Some(role_id) = new_role_id {
+            if let Err(why) = context.http.add_member_role(*guild_id.as_u64(), *user.id.as_u64(), *role_id.as_u64()).await {
+                ops::report(context, format!("Cannot add team role to user: {:?}", why)).await;
+            }
+        }
+        // threads are created for Team A first, Team B second
+        if let Some(thread_id) = active_match.team_threads.get(if team_a { 0 } else { 1 }) {
+            if let Err(why) = context.http.add_thread_channel_member(*thread_id.as_u64(), *user.id.as_u64()).await {
+                ops::report(context, format!("Cannot add user to team thread: {:?}", why)).await;
+            }
+        }
+    }
+    if let Some(channel_id) = team_channel_id {
+        if let Err(why) = guild_id.move_member(&context.http, user.id, channel_id).await {
+            ops::report(context, format!("Cannot move user: {:?}", why)).await;
+        }
     }
-    let draft: &mut Draft = &mut data.get_mut::<Draft>().unwrap();
-    draft.team_a = vec![];
-    draft.team_b = vec![];
-    draft.captain_a = None;
-    draft.captain_b = None;
-    draft.current_picker = None;
-    let bot_state: &mut StateContainer = &mut data.get_mut::<BotState>().unwrap();
-    bot_state.state = State::Queue;
-    send_simple_tagged_msg(&context, &msg, " `.start` process cancelled.", &msg.author).await;
 }
 
-
-pub(crate) async fn handle_teamname(context: Context, msg: Message) {
+pub(crate) async fn handle_teamname(context: Context, msg: Message) -> BotResult {
     let mut data = context.data.write().await;
-    let teamname_cache: &mut HashMap<u64, String> = &mut data.get_mut::<TeamNameCache>().unwrap();
-    let split_content = msg.content.trim().split(' ').collect::<Vec<_>>();
-    if split_content.len() < 2 {
-        send_simple_tagged_msg(&context, &msg, " invalid message formatting. Example: `.teamname TeamName`", &msg.author).await;
-        return;
+    let teamname = match msg.content.trim().split_once(' ') {
+        Some((_, teamname)) if !teamname.trim().is_empty() => String::from(teamname.trim()),
+        _ => {
+            return Err(BotError::Usage(String::from(" invalid message formatting. Example: `.teamname TeamName`")));
+        }
+    };
+    let user_id = *msg.author.id.as_u64();
+    // `emoji` and `color` are only options when followed by `none` or a valid value, so `.teamname Color Blind` stays a team name
+    let (option, value) = teamname.split_once(' ').map(|(option, value)| (option, value.trim())).unwrap_or((&teamname, ""));
+    let remove = value.eq_ignore_ascii_case("none");
+    let emoji = option.eq_ignore_ascii_case("emoji") && (remove || team_emoji_valid(value));
+    let color = (option.eq_ignore_ascii_case("color") || option.eq_ignore_ascii_case("colour")) && (remove || parse_team_color(value).is_some());
+    if emoji || color {
+        let profile = data.get_mut::<TeamNameCache>().unwrap().entry(user_id).or_default();
+        let response = match (emoji, parse_team_color(value)) {
+            (true, _) if remove => {
+                profile.emoji = None;
+                String::from(" team emoji removed")
+            }
+            (true, _) => {
+                profile.emoji = Some(String::from(value));
+                format!(" team emoji set to {}", value)
+            }
+            (false, Some(color)) => {
+                profile.color = Some(color);
+                format!(" team color set to `#{:06x}`", color)
+            }
+            (false, None) => {
+                profile.color = None;
+                String::from(" team color removed")
+            }
+        };
+        write_to_file(String::from("teamnames.json"), serde_json::to_string(data.get::<TeamNameCache>().unwrap()).unwrap()).await;
+        send_simple_tagged_msg(&context, &msg, &response, &msg.author).await;
+        return Ok(());
     }
-    let teamname = String::from(&msg.content[10..msg.content.len()]);
     if teamname.len() > 18 {
-        send_simple_tagged_msg(&context, &msg, &format!(" team name is over the character limit by {}.", teamname.len() - 18), &msg.author).await;
-        return;
+        return Err(BotError::Usage(format!(" team name is over the character limit by {}.", teamname.len() - 18)));
+    }
+    if !teamname_allowed(data.get::<Config>().unwrap(), &teamname) {
+        audit_log(&context, &data, "Team name rejected", format!("<@{}> tried `{}`", msg.author.id, teamname)).await;
+        return Err(BotError::Usage(String::from(" that team name is not allowed, please pick another one")));
     }
-    teamname_cache.insert(*msg.author.id.as_u64(), String::from(&teamname));
+    let teamname_cache: &mut HashMap<u64, TeamProfile> = data.get_mut::<TeamNameCache>().unwrap();
+    teamname_cache.entry(user_id).or_default().name = Some(String::from(&teamname));
     write_to_file(String::from("teamnames.json"), serde_json::to_string(teamname_cache).unwrap()).await;
     send_simple_tagged_msg(&context, &msg, &format!(" custom team name successfully set to `{}`", &teamname), &msg.author).await;
+    Ok(())
+}
+
+/// A `.teamname emoji` value has to be a single standard emoji
+fn team_emoji_valid(value: &str) -> bool {
+    !value.is_empty() && value.chars().count() <= 8 && !value.chars().any(|c| c.is_ascii())
+}
+
+/// A `.teamname color` value, a six digit hex color with or without `#`
+fn parse_team_color(value: &str) -> Option<u32> {
+    let hex = value.trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok()
+}
+
+/// A captain's `.teamname`, falling back to their discord name
+pub(crate) fn team_name(data: &TypeMap, captain: &User) -> String {
+    data.get::<TeamNameCache>().unwrap()
+        .get(captain.id.as_u64())
+        .and_then(|profile| profile.name.clone())
+        .unwrap_or_else(|| captain.name.clone())
+}
+
+/// The team name followed by the team emoji, used in announcements and channel names
+pub(crate) fn team_label(data: &TypeMap, captain: &User) -> String {
+    match data.get::<TeamNameCache>().unwrap().get(captain.id.as_u64()).and_then(|profile| profile.emoji.as_ref()) {
+        Some(emoji) => format!("{} {}", team_name(data, captain), emoji),
+        None => team_name(data, captain),
+    }
+}
+
+pub(crate) fn team_color(data: &TypeMap, captain: &User) -> Option<u32> {
+    data.get::<TeamNameCache>().unwrap().get(captain.id.as_u64()).and_then(|profile| profile.color)
+}
+
+/// Checks a team name against `teamname_banned_words` and the `teamname_filters` regexes, case-insensitive.
+/// Banned words are also matched with spaces and punctuation stripped so `b.a.d w0rd` style spacing doesn't slip through
+pub(crate) fn teamname_allowed(config: &Config, teamname: &str) -> bool {
+    let lowercase = teamname.to_lowercase();
+    let squashed: String = lowercase.chars().filter(|c| c.is_alphanumeric()).collect();
+    let banned = config.teamname_banned_words.iter()
+        .flatten()
+        .map(|word| word.trim().to_lowercase())
+        .filter(|word| !word.is_empty())
+        .any(|word| lowercase.contains(&word) || squashed.contains(&word));
+    if banned {
+        return false;
+    }
+    !config.teamname_filters.iter()
+        .flatten()
+        .filter_map(|filter| Regex::new(&format!("(?i){}", filter)).ok())
+        .any(|filter| filter.is_match(teamname))
 }
 
 pub(crate) async fn send_simple_msg(context: &Context, msg: &Message, text: &str) {
+    announce(context, msg.channel_id, text).await;
+}
+
+pub(crate) async fn announce(messenger: &impl Messenger, channel_id: ChannelId, text: &str) {
+    messenger.say(channel_id, i18n::translate(text)).await;
+}
+
+pub(crate) async fn announce_tagged(messenger: &impl Messenger, channel_id: ChannelId, text: &str, mentioned: &User) {
     let response = MessageBuilder::new()
-        .push(text)
+        .mention(mentioned)
+        .push(i18n::translate(text))
         .build();
-    if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-        eprintln!("Error sending message: {:?}", why);
-    }
+    messenger.say(channel_id, response).await;
 }
 
 pub(crate) async fn send_simple_tagged_msg(context: &Context, msg: &Message, text: &str, mentioned: &User) -> Option<Message> {
     let response = MessageBuilder::new()
         .mention(mentioned)
-        .push(text)
+        .push(i18n::translate(text))
         .build();
-    if let Ok(m) = msg.channel_id.say(&context.http, &response).await {
-        Some(m)
+    delivery::say(&context.http, msg.channel_id, &response).await
+}
+
+pub(crate) async fn command_channel_check(context: &Context, msg: &Message) -> bool {
+    let command_channel_ids: Vec<u64> = {
+        let data = context.data.read().await;
+        let configured = data.get::<Config>().unwrap().discord.command_channel_ids.iter().flatten();
+        configured.filter_map(|id| enabled_id(&data, Some(*id))).collect()
+    };
+    let command_channel_ids = match command_channel_ids {
+        command_channel_ids if !command_channel_ids.is_empty() => command_channel_ids,
+        _ => return true,
+    };
+    if command_channel_ids.contains(msg.channel_id.as_u64()) {
+        return true;
+    }
+    let channels: Vec<String> = command_channel_ids.iter().map(|id| format!("<#{}>", id)).collect();
+    send_simple_tagged_msg(context, msg, &format!(" queue commands only work in {}", channels.join(", ")), &msg.author).await;
+    false
+}
+
+/// Last use of a command per user, kept out of the TypeMap so spam doesn't queue up behind the data lock
+static COMMAND_USES: Mutex<BTreeMap<(u64, String), i64>> = Mutex::new(BTreeMap::new());
+
+/// Returns true if the user ran the command within its `command_cooldowns` seconds, the blocked message gets a ⏳ reaction
+pub(crate) async fn command_on_cooldown(context: &Context, msg: &Message, command_name: &str) -> bool {
+    let (cooldown, longest_cooldown) = {
+        let data = context.data.read().await;
+        let cooldowns = match data.get::<Config>().unwrap().command_cooldowns.as_ref() {
+            Some(cooldowns) => cooldowns,
+            None => return false,
+        };
+        match cooldowns.get(command_name) {
+            Some(cooldown) if *cooldown > 0 => (*cooldown, cooldowns.values().copied().max().unwrap_or(0)),
+            _ => return false,
+        }
+    };
+    let now = Local::now().timestamp();
+    {
+        let mut uses = COMMAND_USES.lock().unwrap();
+        // forget old uses now and then, none of them can still be in a cooldown
+        if uses.len() > 1000 {
+            uses.retain(|_, used| now - *used < longest_cooldown);
+        }
+        let used = uses.entry((*msg.author.id.as_u64(), command_name.to_string())).or_insert(0);
+        if now - *used >= cooldown {
+            *used = now;
+            return false;
+        }
+    }
+    // a reaction instead of a reply, so spamming a command doesn't flood the channel with replies
+    if let Err(why) = delivery::react(&context.http, msg.channel_id, msg.id, ReactionType::Unicode(String::from("⏳"))).await {
+        eprintln!("Error adding reaction: {:?}", why);
+    }
+    true
+}
+
+/// Checks the configured role and channel ids against the guild caches, ids that don't exist are reported and
+/// added to `DisabledConfigIds` so the features using them stay off instead of failing mid-setup. Other invalid
+/// options are reported, the code using them already falls back to the default. The config itself is left as loaded
+pub(crate) async fn validate_config(context: &Context, guilds: &[GuildId]) {
+    let config: Config = context.data.read().await.get::<Config>().unwrap().clone();
+    let mut problems: Vec<String> = Vec::new();
+    let mut disabled: HashSet<u64> = HashSet::new();
+    let role_exists = |role_id: u64| async move {
+        let mut exists = false;
+        for guild_id in guilds {
+            exists |= context.cache.role(*guild_id, role_id).await.is_some();
+        }
+        exists
+    };
+    if let Some(role_id) = config.discord.admin_role_id {
+        if !role_exists(role_id).await {
+            problems.push(format!("discord.admin_role_id: role {} not found, admin commands are disabled", role_id));
+        }
+    }
+    let roles = [
+        ("discord.assign_role_id", config.discord.assign_role_id),
+        ("discord.sub_role_id", config.discord.sub_role_id),
+        ("discord.queue_ping_role_id", config.discord.queue_ping_role_id),
+        ("discord.moderator_role_id", config.discord.moderator_role_id),
+        ("discord.captain_role_id", config.discord.captain_role_id),
+    ];
+    for (key, role_id) in roles {
+        if let Some(role_id) = role_id {
+            if !role_exists(role_id).await {
+                problems.push(format!("{}: role {} not found, feature disabled", key, role_id));
+                disabled.insert(role_id);
+            }
+        }
+    }
+    let channels = [
+        ("discord.audit_channel_id", config.discord.audit_channel_id),
+        ("discord.ops_channel_id", config.discord.ops_channel_id),
+        ("discord.team_a_channel_id", config.discord.team_a_channel_id),
+        ("discord.team_b_channel_id", config.discord.team_b_channel_id),
+        ("discord.spectator_channel_id", config.discord.spectator_channel_id),
+        ("discord.lobby_channel_id", config.discord.lobby_channel_id),
+        ("discord.queue_channel_id", config.discord.queue_channel_id),
+    ];
+    for (key, channel_id) in channels {
+        if let Some(id) = channel_id {
+            if context.cache.guild_channel(id).await.is_none() {
+                problems.push(format!("{}: channel {} not found, feature disabled", key, id));
+                disabled.insert(id);
+            }
+        }
+    }
+    if let Some(category_id) = config.discord.temp_channel_category_id {
+        if context.cache.category(category_id).await.is_none() {
+            problems.push(format!("discord.temp_channel_category_id: category {} not found, feature disabled", category_id));
+            disabled.insert(category_id);
+        }
+    }
+    for id in config.discord.command_channel_ids.iter().flatten() {
+        if context.cache.guild_channel(*id).await.is_none() {
+            problems.push(format!("discord.command_channel_ids: channel {} not found, ignoring it", id));
+            disabled.insert(*id);
+        }
+    }
+    if config.autoclear_hour.map(|hour| hour > 23).unwrap_or(false) {
+        problems.push(format!("autoclear_hour: {} is not between 0-23, autoclear disabled", config.autoclear_hour.unwrap()));
+    }
+    for expression in config.autoclear_schedule.iter().flatten() {
+        if let Err(why) = cron::Schedule::parse(expression) {
+            problems.push(format!("autoclear_schedule: {}, ignoring it", why));
+        }
+    }
+    if let Some(skip_dates) = &config.autoclear_skip_dates {
+        for date in skip_dates {
+            if NaiveDate::parse_from_str(date, "%Y-%m-%d").is_err() {
+                problems.push(format!("autoclear_skip_dates: `{}` is not a YYYY-MM-DD date", date));
+            }
+        }
+    }
+    if (config.queue_open_time.is_some() || config.queue_close_time.is_some()) && queue_window(&config).is_none() {
+        problems.push(String::from("queue_open_time/queue_close_time: both must be set as HH:MM i.e. 18:00, queue hours disabled"));
+    }
+    if let Some(size) = config.queue_size.filter(|size| !valid_queue_size(*size)) {
+        problems.push(format!("queue_size: {} must be an even number from 2 to 10, using 10", size));
+    }
+    if let Some(side_selection) = &config.side_selection {
+        if !["captain_b", "random", "default_defense"].contains(&side_selection.as_str()) {
+            problems.push(format!("side_selection: `{}` must be captain_b, random or default_defense, using captain_b", side_selection));
+        }
+    }
+    if let Some(selection) = &config.captain_selection {
+        if !["manual", "rating"].contains(&selection.as_str()) {
+            problems.push(format!("captain_selection: `{}` must be manual or rating, using manual", selection));
+        }
+    }
+    if let Some(selection) = &config.captain_pool_selection {
+        if !["random", "rating"].contains(&selection.as_str()) {
+            problems.push(format!("captain_pool_selection: `{}` must be random or rating, using random", selection));
+        }
+    }
+    if let Some(team) = &config.default_defense_team {
+        if !team.eq_ignore_ascii_case("A") && !team.eq_ignore_ascii_case("B") {
+            problems.push(format!("default_defense_team: `{}` must be A or B, using B", team));
+        }
+    }
+    for url in config.webhook_urls.iter().flatten().filter(|url| !webhooks::valid_url(url)) {
+        problems.push(format!("webhook_urls: `{}` must start with http:// or https://, skipping it", url));
+    }
+    for (command, role_id) in config.command_roles.iter().flatten() {
+        if Command::from_str(command).is_err() {
+            problems.push(format!("command_roles: `{}` is not a command, skipping it", command));
+        } else if !role_exists(*role_id).await {
+            problems.push(format!("command_roles: role {} for `{}` not found, it needs the admin role instead", role_id, command));
+            disabled.insert(*role_id);
+        }
+    }
+    for command in config.command_cooldowns.iter().flatten().map(|(command, _)| command) {
+        if Command::from_str(command).is_err() {
+            problems.push(format!("command_cooldowns: `{}` is not a command, skipping it", command));
+        }
+    }
+    for filter in config.teamname_filters.iter().flatten() {
+        if let Err(why) = Regex::new(filter) {
+            problems.push(format!("teamname_filters: `{}` is not a valid regex, skipping it: {}", filter, why));
+        }
+    }
+    if let Some(language) = &config.language {
+        if !i18n::LANGUAGES.contains(&language.to_lowercase().as_str()) {
+            problems.push(format!("language: `{}` is not supported, using English. Supported: {}", language, i18n::LANGUAGES.join(", ")));
+        }
+    }
+    ops::set_channel(config.discord.ops_channel_id.filter(|id| !disabled.contains(id)));
+    context.data.write().await.insert::<DisabledConfigIds>(disabled);
+    if problems.is_empty() {
+        println!("Config validated");
     } else {
-        eprintln!("Error sending message");
-        None
+        ops::report(context, format!("Found {} config problem(s):\n- {}", problems.len(), problems.join("\n- "))).await;
     }
 }
 
+/// Commands `moderator_role_id` members can use next to the admins, unless `command_roles` says otherwise
+pub(crate) const MODERATOR_COMMANDS: [&str; 3] = [".kick", ".clear", ".cancel"];
+
+/// Checks that the author may run the command in `msg`. Commands in `command_roles` need their configured role, every
+/// other privileged command needs `admin_role_id`, or `moderator_role_id` for `MODERATOR_COMMANDS`. The admin role
+/// can run everything
 pub(crate) async fn admin_check(context: &Context, msg: &Message, print_msg: bool) -> bool {
-    let data = context.data.write().await;
-    let config: &Config = data.get::<Config>().unwrap();
-    if let Some(admin_role_id) = config.discord.admin_role_id {
-        let role_name = context.cache.role(msg.guild_id.unwrap(), admin_role_id).await.unwrap().name;
-        return if msg.author.has_role(&context.http, GuildContainer::from(msg.guild_id.unwrap()), admin_role_id).await.unwrap_or_else(|_| false) {
-            true
-        } else {
+    // the role lookup goes to discord, so the bot data isn't held while waiting on it
+    let (command_name, command_role_id, admin_role_id, moderator_role_id) = {
+        let data = context.data.read().await;
+        let command_name = resolve_alias(&data, msg.content.to_lowercase().split_whitespace().next().unwrap_or("").to_string());
+        let config: &Config = data.get::<Config>().unwrap();
+        let command_role_id = enabled_id(&data, config.command_roles.as_ref().and_then(|roles| roles.get(&command_name)).copied());
+        let moderator_role_id = enabled_id(&data, config.discord.moderator_role_id)
+            .filter(|_| command_role_id.is_none() && MODERATOR_COMMANDS.contains(&command_name.as_str()));
+        (command_name, command_role_id, config.discord.admin_role_id, moderator_role_id)
+    };
+    let required_role_id = match command_role_id.or(admin_role_id) {
+        Some(required_role_id) => required_role_id,
+        None => return true,
+    };
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return false,
+    };
+    let mut role_name = match context.cache.role(guild_id, required_role_id).await {
+        Some(role) => role.name,
+        None => {
             if print_msg {
-                let response = MessageBuilder::new()
-                    .mention(&msg.author)
-                    .push(" this command requires the '")
-                    .push(role_name)
-                    .push("' role.")
-                    .build();
-                if let Err(why) = msg.channel_id.say(&context.http, &response).await {
-                    eprintln!("Error sending message: {:?}", why);
-                }
+                let problem = if command_role_id.is_some() {
+                    format!(" the role configured for `{}` in `command_roles` doesn't exist in this server.", command_name)
+                } else {
+                    String::from(" the configured admin role doesn't exist in this server, admin commands are disabled until `admin_role_id` is fixed.")
+                };
+                send_simple_tagged_msg(context, msg, &problem, &msg.author).await;
             }
-            false
-        };
+            return false;
+        }
+    };
+    if has_any_role(context, guild_id, &msg.author, command_role_id.into_iter().chain(admin_role_id).chain(moderator_role_id)).await {
+        return true;
     }
-    true
+    if let Some(moderator_role_id) = moderator_role_id {
+        if let Some(moderator_role) = context.cache.role(guild_id, moderator_role_id).await {
+            role_name = format!("{}' or '{}", role_name, moderator_role.name);
+        }
+    }
+    if print_msg {
+        let response = MessageBuilder::new()
+            .mention(&msg.author)
+            .push(" this command requires the '")
+            .push(role_name)
+            .push("' role.")
+            .build();
+        delivery::say(&context.http, msg.channel_id, &response).await;
+    }
+    false
+}
+
+/// Whether the author has the `moderator_role_id` role
+async fn moderator_check(context: &Context, msg: &Message) -> bool {
+    let moderator_role_id = {
+        let data = context.data.read().await;
+        enabled_id(&data, data.get::<Config>().unwrap().discord.moderator_role_id)
+    };
+    match (moderator_role_id, msg.guild_id) {
+        (Some(moderator_role_id), Some(guild_id)) => has_any_role(context, guild_id, &msg.author, [moderator_role_id]).await,
+        _ => false,
+    }
+}
+
+/// Whether the user has at least one of the roles, checked one at a time in the given order
+async fn has_any_role(guild: &impl Guild, guild_id: GuildId, user: &User, role_ids: impl IntoIterator<Item = u64>) -> bool {
+    for role_id in role_ids {
+        if guild.has_role(guild_id, user, role_id).await {
+            return true;
+        }
+    }
+    false
 }
 
 pub(crate) async fn move_user(msg: &Message, user: &User, channel_id: u64, context: &Context) {
     if let Some(guild) = &msg.guild(&context.cache).await {
         if let Err(why) = guild.move_member(&context.http, user.id, channel_id).await {
-            println!("Cannot move user: {:?}", why);
+            ops::report(context, format!("Cannot move user: {:?}", why)).await;
         }
     }
 }
@@ -855,3 +5595,365 @@ pub(crate) async fn populate_unicode_emojis() -> HashMap<char, String> {
     map.insert('z', String::from("🇿"));
     map
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messenger::mock::MockDiscord;
+    use crate::{read_session_state, PlayerStats};
+
+    const CHANNEL: ChannelId = ChannelId(1);
+
+    fn player(id: u64) -> User {
+        let mut user = User::default();
+        user.id = UserId(id);
+        user.name = format!("player{}", id);
+        user
+    }
+
+    /// Bot data for a full queue of players 1 to 10 in `state`, `config` is the yaml below `discord.token`
+    fn setup_data(config: &str, state: State) -> TypeMap {
+        let config: Config = serde_yaml::from_str(&format!("discord:\n  token: test\n{}", config)).unwrap();
+        let mut data = TypeMap::new();
+        data.insert::<Config>(config);
+        data.insert::<DisabledConfigIds>(HashSet::new());
+        data.insert::<UserQueue>((1..=10).map(player).collect());
+        data.insert::<BotState>(StateContainer { state });
+        data.insert::<CurrentMatch>(Match::default());
+        data.insert::<CaptainPool>(Vec::new());
+        data.insert::<PlayerStatsCache>(HashMap::new());
+        data.insert::<TeamNameCache>(HashMap::new());
+        data.insert::<Waitlist>(Vec::new());
+        data.insert::<PriorityPlayers>(Vec::new());
+        data.insert::<Parties>(Vec::new());
+        data.insert::<QueueMessages>(HashMap::new());
+        data.insert::<QueueExpiry>(HashMap::new());
+        data.insert::<QueueJoinTimes>(HashMap::new());
+        data.insert::<QueueEvents>(Vec::new());
+        data.insert::<QueueSessionState>(QueueSessions::default());
+        data.insert::<QueueEventsUnsaved>(false);
+        data.insert::<Maps>(vec![String::from("Ascent"), String::from("Bind"), String::from("Haven")]);
+        data.insert::<MapImages>(HashMap::new());
+        data
+    }
+
+    fn state(data: &TypeMap) -> &State {
+        &data.get::<BotState>().unwrap().state
+    }
+
+    fn draft(data: &TypeMap) -> &Draft {
+        &data.get::<CurrentMatch>().unwrap().draft
+    }
+
+    fn usage_error<T>(result: BotResult<T>) -> String {
+        match result {
+            Err(BotError::Usage(text)) => text,
+            Err(why) => panic!("expected a usage error, got {}", why),
+            Ok(_) => panic!("expected a usage error"),
+        }
+    }
+
+    /// Runs the captain pick with players 1 and 2 claiming captain
+    async fn start_manual_draft(discord: &MockDiscord, data: &mut TypeMap) {
+        begin_captain_pick(discord, CHANNEL, data, String::from("Ascent"), &[]).await;
+        assert!(claim_captain(discord, CHANNEL, data, &player(1)).await.is_ok());
+        assert!(claim_captain(discord, CHANNEL, data, &player(2)).await.is_ok());
+    }
+
+    /// Lets the captain whose turn it is pick the first unpicked player, returns whether the setup completed
+    async fn pick_next(discord: &MockDiscord, data: &mut TypeMap) -> bool {
+        let draft = draft(data);
+        let picker = current_picker(data).unwrap();
+        let picked = data.get::<UserQueue>().unwrap().iter()
+            .find(|user| !draft.team_a.contains(user) && !draft.team_b.contains(user))
+            .cloned()
+            .unwrap();
+        match pick_player(discord, CHANNEL, data, &picker, picked, false).await {
+            Ok(complete) => complete,
+            Err(why) => panic!("pick failed: {}", why),
+        }
+    }
+
+    #[tokio::test]
+    async fn setup_runs_from_captain_pick_to_ready() {
+        let discord = MockDiscord::default();
+        let mut data = setup_data("", State::MapPick);
+        begin_captain_pick(&discord, CHANNEL, &mut data, String::from("Ascent"), &[]).await;
+        assert!(*state(&data) == State::captain_pick());
+
+        assert!(claim_captain(&discord, CHANNEL, &mut data, &player(1)).await.is_ok());
+        assert!(*state(&data) == State::CaptainPick { captain_a: Some(player(1)), captain_b: None });
+        assert_eq!(usage_error(claim_captain(&discord, CHANNEL, &mut data, &player(1)).await), " you're already a captain!");
+        assert!(claim_captain(&discord, CHANNEL, &mut data, &player(2)).await.is_ok());
+        assert!(state(&data).is_draft());
+        assert!(current_picker(&data) == setup_captains(&data).0);
+        assert!(draft(&data).captain_a.is_none());
+        assert!(matches!(state(&data), State::Draft { message_id: Some(_), .. }));
+
+        for _ in 0..7 {
+            assert!(!pick_next(&discord, &mut data).await);
+            assert!(state(&data).is_draft());
+        }
+        assert!(!pick_next(&discord, &mut data).await);
+        assert!(state(&data).is_side_pick());
+        assert_eq!(draft(&data).team_a.len(), 5);
+        assert_eq!(draft(&data).team_b.len(), 5);
+        assert!(matches!(state(&data), State::SidePick { prompt_id: Some(_), .. }));
+        assert_eq!(discord.reactions.lock().unwrap().len(), 2);
+
+        let (captain_a, captain_b) = setup_captains(&data);
+        let (captain_a, captain_b) = (captain_a.unwrap(), captain_b.unwrap());
+        assert_eq!(usage_error(pick_side(&discord, CHANNEL, &mut data, &captain_a, "t").await), " you are not Captain B");
+        assert!(pick_side(&discord, CHANNEL, &mut data, &captain_b, "t").await.is_ok());
+        assert!(*state(&data) == State::Ready);
+        assert_eq!(draft(&data).team_b_start_side, "t");
+        assert!(draft(&data).captain_a == Some(captain_a) && draft(&data).captain_b == Some(captain_b));
+        assert_eq!(discord.said().last().unwrap(), "Setup is completed.");
+    }
+
+    #[tokio::test]
+    async fn picks_follow_the_turn_order() {
+        let discord = MockDiscord::default();
+        let mut data = setup_data("", State::MapPick);
+        start_manual_draft(&discord, &mut data).await;
+        let picker = current_picker(&data).unwrap();
+        let other_captain = if picker == player(1) { player(2) } else { player(1) };
+
+        assert_eq!(usage_error(pick_player(&discord, CHANNEL, &mut data, &other_captain, player(3), false).await), " it is not your turn to pick");
+        assert_eq!(usage_error(pick_player(&discord, CHANNEL, &mut data, &player(4), player(3), false).await), " you are not a captain");
+        assert_eq!(usage_error(pick_player(&discord, CHANNEL, &mut data, &picker, player(11), false).await), " this user is not in the queue");
+        assert_eq!(usage_error(pick_player(&discord, CHANNEL, &mut data, &picker, other_captain.clone(), false).await), " this player is already on a team");
+
+        assert!(matches!(pick_player(&discord, CHANNEL, &mut data, &picker, player(3), false).await, Ok(false)));
+        assert!(current_picker(&data) == Some(other_captain));
+        // an admin's forced pick goes to the captain whose turn it is
+        assert!(matches!(pick_player(&discord, CHANNEL, &mut data, &player(4), player(5), true).await, Ok(false)));
+        assert!(current_picker(&data) == Some(picker.clone()));
+        assert!(draft(&data).team_a.contains(&player(3)) || draft(&data).team_b.contains(&player(3)));
+        assert_eq!(draft(&data).team_a.len() + draft(&data).team_b.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn picks_are_only_taken_during_the_draft() {
+        let discord = MockDiscord::default();
+        let mut data = setup_data("", State::captain_pick());
+        assert_eq!(usage_error(pick_player(&discord, CHANNEL, &mut data, &player(1), player(3), false).await), " it is not currently the draft phase");
+        assert_eq!(usage_error(pick_side(&discord, CHANNEL, &mut data, &player(1), "ct").await), " it is not currently the side pick phase");
+        let mut data = setup_data("", State::draft(Captains { a: player(1), b: player(2) }));
+        assert_eq!(usage_error(claim_captain(&discord, CHANNEL, &mut data, &player(1)).await), " command ignored, not in the captain pick phase");
+        assert!(discord.said().is_empty());
+    }
+
+    #[tokio::test]
+    async fn skip_waits_for_the_turn_timer_unless_admin() {
+        let discord = MockDiscord::default();
+        let mut data = setup_data("pick_skip_seconds: 30\n", State::MapPick);
+        start_manual_draft(&discord, &mut data).await;
+        let picker = current_picker(&data).unwrap();
+        let other_captain = if picker == player(1) { player(2) } else { player(1) };
+
+        assert!(usage_error(skip_turn(&discord, CHANNEL, &mut data, &other_captain, false).await).contains("seconds to pick"));
+        assert_eq!(usage_error(skip_turn(&discord, CHANNEL, &mut data, &player(5), false).await),
+                   " only the other captain or an admin can skip a captain's turn");
+        if let State::Draft { turn, .. } = &mut data.get_mut::<BotState>().unwrap().state {
+            turn.started -= 30;
+        }
+        assert!(skip_turn(&discord, CHANNEL, &mut data, &other_captain, false).await.is_ok());
+        assert!(current_picker(&data) == Some(other_captain.clone()));
+        assert!(skip_turn(&discord, CHANNEL, &mut data, &player(5), true).await.is_ok());
+        assert!(current_picker(&data) == Some(picker.clone()));
+        assert_eq!(draft(&data).skipped_turns, vec![*picker.id.as_u64(), *other_captain.id.as_u64()]);
+    }
+
+    #[tokio::test]
+    async fn rating_selection_picks_the_highest_rated_captains() {
+        let discord = MockDiscord::default();
+        let mut data = setup_data("captain_selection: rating\n", State::MapPick);
+        let stats = data.get_mut::<PlayerStatsCache>().unwrap();
+        stats.insert(7, PlayerStats { wins: 10, ..Default::default() });
+        stats.insert(8, PlayerStats { wins: 5, ..Default::default() });
+        begin_captain_pick(&discord, CHANNEL, &mut data, String::from("Ascent"), &[]).await;
+        assert!(state(&data).is_draft());
+        // the lower rated captain picks first
+        assert!(setup_captains(&data) == (Some(player(8)), Some(player(7))));
+        assert!(current_picker(&data) == Some(player(8)));
+    }
+
+    #[tokio::test]
+    async fn captain_role_limits_who_can_be_captain() {
+        let discord = MockDiscord::default();
+        let guild_id = Some(GuildId(1));
+        let mut data = setup_data("captain_selection: rating\n", State::MapPick);
+        data.get_mut::<PlayerStatsCache>().unwrap().insert(7, PlayerStats { wins: 10, ..Default::default() });
+        discord.grant(&player(3), 77);
+        // with only one eligible player the captains are left to `.captain`
+        let players = data.get::<UserQueue>().unwrap().clone();
+        let ineligible = ineligible_captains(&discord, guild_id, Some(77), &players).await;
+        begin_captain_pick(&discord, CHANNEL, &mut data, String::from("Ascent"), &ineligible).await;
+        assert!(*state(&data) == State::captain_pick());
+
+        discord.grant(&player(4), 77);
+        let ineligible = ineligible_captains(&discord, guild_id, Some(77), &players).await;
+        begin_captain_pick(&discord, CHANNEL, &mut data, String::from("Ascent"), &ineligible).await;
+        assert!(state(&data).is_draft());
+        let (captain_a, captain_b) = setup_captains(&data);
+        let captains = [captain_a.unwrap(), captain_b.unwrap()];
+        assert!(captains.contains(&player(3)) && captains.contains(&player(4)));
+        assert!(!captain_eligible(&discord, guild_id, Some(77), &player(7)).await);
+        assert!(captain_eligible(&discord, guild_id, None, &player(7)).await);
+    }
+
+    #[tokio::test]
+    async fn configured_side_selection_completes_the_setup() {
+        let discord = MockDiscord::default();
+        let captains = Captains { a: player(1), b: player(2) };
+        let mut data = setup_data("side_selection: default_defense\ndefault_defense_team: A\n", State::draft(captains.clone()));
+        assert!(begin_side_pick(&discord, CHANNEL, &mut data, captains).await);
+        assert!(*state(&data) == State::Ready);
+        assert_eq!(draft(&data).team_b_start_side, "t");
+        assert!(draft(&data).captain_b == Some(player(2)));
+    }
+
+    #[test]
+    fn matches_load_from_the_old_files() {
+        // match_history.json kept the players' ids and the result
+        let record: Match = serde_json::from_str(r#"{"ended":1700000000,"map":"Bind","team_a_name":"Red","team_b_name":"Blue",
+            "team_a":[1,2,3,4,5],"team_b":[6,7,8,9,10],"score":[13,7]}"#).unwrap();
+        assert_eq!(record.team_ids(Team::B), vec![6, 7, 8, 9, 10]);
+        assert_eq!(record.draft.map.as_deref(), Some("Bind"));
+        let result = record.result.as_ref().unwrap();
+        assert_eq!((result.ended, result.team_a_name.as_str(), result.score), (1700000000, "Red", Some((13, 7))));
+
+        // last_match.json held the draft of the last setup
+        let mut last_match = Match::default();
+        last_match.draft.team_a = (1..=5).map(player).collect();
+        last_match.draft.team_b = (6..=10).map(player).collect();
+        let old = serde_json::to_string(&last_match.draft).unwrap();
+        let last_match: Match = serde_json::from_str(&old).unwrap();
+        assert!(last_match.has_player(10) && last_match.result.is_none());
+        assert_eq!(last_match.players.len(), 10);
+
+        let saved = serde_json::to_string(&last_match).unwrap();
+        assert!(serde_json::from_str::<Match>(&saved).unwrap().has_player(1));
+    }
+
+    #[test]
+    fn sessions_saved_in_the_old_setup_states_restart_the_captain_pick() {
+        let read = |json: &str| read_session_state(&mut serde_json::Deserializer::from_str(json));
+        for old in ["\"CaptainPick\"", "\"Draft\"", "\"SidePick\""].iter() {
+            assert!(read(old).unwrap() == State::captain_pick());
+        }
+        assert!(read("\"Queue\"").unwrap() == State::Queue);
+        let draft = State::draft(Captains { a: player(1), b: player(2) });
+        assert!(read(&serde_json::to_string(&draft).unwrap()).unwrap() == draft);
+        assert!(read("\"Lobby\"").is_err());
+    }
+
+    #[test]
+    fn invalid_queue_size_uses_ten() {
+        assert_eq!(queue_size(&setup_data("queue_size: 8\n", State::Queue)), 8);
+        assert_eq!(queue_size(&setup_data("queue_size: 7\n", State::Queue)), 10);
+        assert_eq!(queue_size(&setup_data("queue_size: 12\n", State::Queue)), 10);
+        assert_eq!(queue_size(&setup_data("", State::Queue)), 10);
+    }
+
+    #[test]
+    fn config_changes_keep_a_valid_queue_size() {
+        let config = |queue_size: &str| -> Config { serde_yaml::from_str(&format!("discord:\n  token: test\n{}", queue_size)).unwrap() };
+        assert_eq!(config_change_error(&config(""), &config("queue_size: 8\n"), false), None);
+        assert!(config_change_error(&config(""), &config("queue_size: 7\n"), false).is_some());
+        assert!(config_change_error(&config(""), &config("queue_size: 8\n"), true).is_some());
+        assert_eq!(config_change_error(&config("queue_size: 8\n"), &config("queue_size: 8\n"), true), None);
+    }
+
+    #[test]
+    fn joins_fill_the_queue_then_the_waitlist() {
+        let mut data = setup_data("", State::Queue);
+        data.get_mut::<UserQueue>().unwrap().pop();
+        assert_eq!(enqueue(&mut data, &player(10)), QueueJoin::Queued(10));
+        assert_eq!(enqueue(&mut data, &player(10)), QueueJoin::AlreadyQueued);
+        assert_eq!(enqueue(&mut data, &player(11)), QueueJoin::Waitlisted(0));
+        // players left out of the last match go ahead of the waitlist
+        data.get_mut::<PriorityPlayers>().unwrap().push(12);
+        assert_eq!(enqueue(&mut data, &player(12)), QueueJoin::Waitlisted(0));
+        assert_eq!(enqueue(&mut data, &player(11)), QueueJoin::AlreadyWaitlisted(1));
+        assert!(data.get::<QueueJoinTimes>().unwrap().contains_key(&10));
+        assert_eq!(data.get::<QueueEvents>().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn leaving_removes_the_player_from_their_party() {
+        let mut data = setup_data("", State::Queue);
+        data.get_mut::<Parties>().unwrap().push(vec![player(1), player(2), player(3)]);
+        data.get_mut::<QueueJoinTimes>().unwrap().insert(1, Local::now());
+        assert_eq!(remove_from_queue(&mut data, &[player(1)]), 9);
+        assert_eq!(data.get::<Parties>().unwrap(), &vec![vec![player(2), player(3)]]);
+        assert!(!data.get::<QueueJoinTimes>().unwrap().contains_key(&1));
+        assert_eq!(remove_from_queue(&mut data, &[player(2)]), 8);
+        assert!(data.get::<Parties>().unwrap().is_empty());
+        assert_eq!(data.get::<QueueEvents>().unwrap().iter().filter(|event| event.kind == QueueEventKind::Leave).count(), 2);
+    }
+
+    #[tokio::test]
+    async fn promotion_skips_waitlisted_players_already_queued() {
+        let mut data = setup_data("", State::Queue);
+        remove_from_queue(&mut data, &[player(9), player(10)]);
+        *data.get_mut::<Waitlist>().unwrap() = vec![player(1), player(11), player(12), player(13)];
+        data.insert::<RiotIdCache>(HashMap::new());
+        data.insert::<RegionCache>(HashMap::new());
+        promote_waitlist(&Outbox::default(), ChannelId(1), None, &mut data).await;
+        let user_queue: &Vec<User> = data.get::<UserQueue>().unwrap();
+        assert_eq!(user_queue.len(), 10);
+        assert_eq!(user_queue.iter().filter(|user| **user == player(1)).count(), 1);
+        assert_eq!(data.get::<Waitlist>().unwrap(), &vec![player(13)]);
+    }
+
+    #[test]
+    fn a_filled_queue_gets_a_fill_time() {
+        let mut data = setup_data("", State::Queue);
+        data.get_mut::<UserQueue>().unwrap().clear();
+        for id in 1..=10 {
+            assert_eq!(enqueue(&mut data, &player(id)), QueueJoin::Queued(id as usize));
+        }
+        record_queue_event(&mut data, QueueEventKind::Full, None);
+        assert!(data.get::<QueueEvents>().unwrap().last().unwrap().fill_seconds.is_some());
+        assert!(fill_times(&data).is_some());
+        assert!(*data.get::<QueueEventsUnsaved>().unwrap());
+    }
+
+    #[tokio::test]
+    async fn map_vote_winner_starts_the_captain_pick() {
+        let discord = MockDiscord::default();
+        let mut data = setup_data("", State::MapPick);
+        let results = vec![
+            ReactionResult { count: 2, map: String::from("Ascent") },
+            ReactionResult { count: 4, map: String::from("Bind") },
+        ];
+        conclude_map_vote(&discord, CHANNEL, &mut data, results, &[]).await;
+        assert!(*state(&data) == State::captain_pick());
+        assert_eq!(draft(&data).map.as_deref(), Some("Bind"));
+        assert_eq!(discord.embeds.lock().unwrap()[0].1.0.get("title"), Some(&serde_json::json!("Bind")));
+
+        let maps = data.get::<Maps>().unwrap().clone();
+        let tied = vec![
+            ReactionResult { count: 3, map: String::from("Ascent") },
+            ReactionResult { count: 3, map: String::from("Haven") },
+        ];
+        let (map, description) = tally_map_vote(&maps, tied);
+        assert!(map == "Ascent" || map == "Haven");
+        assert!(description.starts_with("Maps were tied"));
+        let (map, description) = tally_map_vote(&maps, Vec::new());
+        assert!(maps.contains(&map));
+        assert!(description.starts_with("No votes were counted"));
+    }
+
+    #[test]
+    fn teamname_options_need_a_valid_value() {
+        assert!(team_emoji_valid("🔥"));
+        assert!(!team_emoji_valid("Squad"));
+        assert!(!team_emoji_valid(""));
+        assert_eq!(parse_team_color("#ff4655"), Some(0xff4655));
+        assert_eq!(parse_team_color("ff4655"), Some(0xff4655));
+        assert_eq!(parse_team_color("Blind"), None);
+    }
+}
@@ -0,0 +1,129 @@
+use chrono::{DateTime, Datelike, Local, Timelike};
+
+/// A cron expression with the standard five fields: minute, hour, day of month, month and day of week.
+/// Fields support `*`, numbers, ranges `1-5`, lists `1,3,5` and steps `*/15`. Day of week 0 and 7 are Sunday.
+/// Like cron, when both day of month and day of week are restricted a time matches if either of them does
+pub(crate) struct Schedule {
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days: Vec<u32>,
+    months: Vec<u32>,
+    weekdays: Vec<u32>,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl Schedule {
+    pub(crate) fn parse(expression: &str) -> Result<Schedule, String> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!("`{}` must have 5 fields: minute hour day month weekday", expression));
+        }
+        let mut weekdays = parse_field(fields[4], 0, 7)?;
+        if weekdays.contains(&7) {
+            weekdays.push(0);
+        }
+        Ok(Schedule {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            weekdays,
+            days_restricted: !fields[2].starts_with('*'),
+            weekdays_restricted: !fields[4].starts_with('*'),
+        })
+    }
+
+    pub(crate) fn matches(&self, time: &DateTime<Local>) -> bool {
+        let day = self.days.contains(&time.day());
+        let weekday = self.weekdays.contains(&time.weekday().num_days_from_sunday());
+        let day_matches = if self.days_restricted && self.weekdays_restricted { day || weekday } else { day && weekday };
+        self.minutes.contains(&time.minute())
+            && self.hours.contains(&time.hour())
+            && self.months.contains(&time.month())
+            && day_matches
+    }
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<Vec<u32>, String> {
+    let mut values = Vec::new();
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| format!("invalid step in `{}`", part))?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(format!("invalid step in `{}`", part));
+        }
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_number(start, min, max)?, parse_number(end, min, max)?)
+        } else {
+            let value = parse_number(range, min, max)?;
+            (value, if part.contains('/') { max } else { value })
+        };
+        values.extend((start..=end).step_by(step as usize));
+    }
+    Ok(values)
+}
+
+fn parse_number(value: &str, min: u32, max: u32) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(number) if number >= min && number <= max => Ok(number),
+        _ => Err(format!("`{}` must be a number between {} and {}", value, min, max)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn parses_ranges_lists_and_steps() {
+        let schedule = Schedule::parse("*/15 3 * * 1-5").unwrap();
+        assert_eq!(schedule.minutes, vec![0, 15, 30, 45]);
+        assert_eq!(schedule.weekdays, vec![1, 2, 3, 4, 5]);
+        assert_eq!(Schedule::parse("0 1,3,5 * * *").unwrap().hours, vec![1, 3, 5]);
+        assert_eq!(Schedule::parse("10/20 * * * *").unwrap().minutes, vec![10, 30, 50]);
+        assert!(Schedule::parse("0 0 * * 7").unwrap().weekdays.contains(&0));
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        assert!(Schedule::parse("0 3 * *").is_err());
+        assert!(Schedule::parse("60 3 * * *").is_err());
+        assert!(Schedule::parse("0 24 * * *").is_err());
+        assert!(Schedule::parse("0 3 0 * *").is_err());
+        assert!(Schedule::parse("*/0 3 * * *").is_err());
+        assert!(Schedule::parse("a 3 * * *").is_err());
+    }
+
+    #[test]
+    fn matches_weekdays() {
+        // 2024-07-01 is a Monday
+        let schedule = Schedule::parse("0 3 * * 1-5").unwrap();
+        assert!(schedule.matches(&at(2024, 7, 1, 3, 0)));
+        assert!(!schedule.matches(&at(2024, 7, 1, 3, 1)));
+        assert!(!schedule.matches(&at(2024, 7, 6, 3, 0)));
+    }
+
+    #[test]
+    fn day_of_month_or_day_of_week() {
+        // the 1st of the month or any Friday, 2024-07-05 is a Friday and 2024-07-01 a Monday
+        let schedule = Schedule::parse("0 4 1 * 5").unwrap();
+        assert!(schedule.matches(&at(2024, 7, 1, 4, 0)));
+        assert!(schedule.matches(&at(2024, 7, 5, 4, 0)));
+        assert!(!schedule.matches(&at(2024, 7, 2, 4, 0)));
+        // with only one of them restricted both have to match
+        let schedule = Schedule::parse("0 4 1 * *").unwrap();
+        assert!(schedule.matches(&at(2024, 7, 1, 4, 0)));
+        assert!(!schedule.matches(&at(2024, 7, 5, 4, 0)));
+    }
+}
@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{Duration as ChronoDuration, Local, TimeZone};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use hyper::header::{CONTENT_TYPE, COOKIE, LOCATION, SET_COOKIE};
+use rand::Rng;
+use serenity::model::id::UserId;
+use serenity::model::user::User;
+use serenity::prelude::{RwLock, TypeMap};
+use serenity::utils::MessageBuilder;
+
+use crate::bot_service::{audit_log, promote_waitlist, queue_channel, queue_size, queue_size_note, record_queue_event, remove_from_queue, write_to_file};
+use crate::messenger::{Messenger, Outbox};
+use crate::web::context;
+use crate::{BotState, Config, DashboardSession, DashboardSessions, MapImages, Maps, MatchHistory, PlayerStats, PlayerStatsCache, QueueActivity, QueueEventKind, QueueMessages, RiotIdCache, State, Team, UserQueue, Waitlist};
+
+const COOKIE_NAME: &str = "scrimbot_dashboard";
+const SESSION_HOURS: i64 = 12;
+
+/// Serves everything under `/dashboard`, only reachable when `dashboard_token` is configured
+pub(crate) async fn route(request: Request<Body>, data: Arc<RwLock<TypeMap>>) -> Response<Body> {
+    let token = match tokio::time::timeout(Duration::from_secs(5), data.read()).await {
+        Ok(data) => data.get::<Config>().unwrap().dashboard_token.clone().filter(|token| !token.is_empty()),
+        Err(_) => return busy(),
+    };
+    let token = match token {
+        Some(token) => token,
+        None => return html(StatusCode::NOT_FOUND, String::from("<p>The dashboard is disabled, set dashboard_token in the config to enable it.</p>")),
+    };
+    let path = request.uri().path().to_string();
+    if path == "/dashboard/login" {
+        return login(request, &token, &data).await;
+    }
+    let logged_in = match (request_session(&request), tokio::time::timeout(Duration::from_secs(5), data.read()).await) {
+        (Some(session), Ok(data)) => data.get::<DashboardSessions>().unwrap().get(session)
+            .map(|session| session.token == token && session.expires > Local::now())
+            .unwrap_or(false),
+        (None, _) => false,
+        (_, Err(_)) => return busy(),
+    };
+    if !logged_in {
+        return redirect("/dashboard/login");
+    }
+    match (request.method().clone(), path.as_str()) {
+        (Method::GET, "/dashboard") | (Method::GET, "/dashboard/") => {
+            let notice = query_param(&request, "notice");
+            match tokio::time::timeout(Duration::from_secs(5), data.read()).await {
+                Ok(data) => html(StatusCode::OK, page(&data, notice.as_deref()).await),
+                Err(_) => busy(),
+            }
+        }
+        (Method::POST, "/dashboard/kick")
+        | (Method::POST, "/dashboard/ban")
+        | (Method::POST, "/dashboard/unban")
+        | (Method::POST, "/dashboard/maps/add")
+        | (Method::POST, "/dashboard/maps/remove") => {
+            let form = read_form(request).await;
+            let mut data = match tokio::time::timeout(Duration::from_secs(5), data.write()).await {
+                Ok(data) => data,
+                Err(_) => return busy(),
+            };
+            let outbox = Outbox::default();
+            let notice = match path.as_str() {
+                "/dashboard/kick" => kick(&outbox, &mut data, &form).await,
+                "/dashboard/ban" => ban(&outbox, &mut data, &form).await,
+                "/dashboard/unban" => unban(&outbox, &mut data, &form).await,
+                "/dashboard/maps/add" => add_map(&outbox, &mut data, &form).await,
+                _ => remove_map(&outbox, &mut data, &form).await,
+            };
+            drop(data);
+            if let Some(context) = context() {
+                outbox.send(&context).await;
+            }
+            let query = serde_urlencoded::to_string(&[("notice", notice)]).unwrap();
+            redirect(&format!("/dashboard?{}", query))
+        }
+        _ => html(StatusCode::NOT_FOUND, String::from("<p>Not found. <a href=\"/dashboard\">Back to the dashboard</a></p>")),
+    }
+}
+
+async fn login(request: Request<Body>, token: &str, data: &Arc<RwLock<TypeMap>>) -> Response<Body> {
+    if request.method() == Method::POST {
+        let form = read_form(request).await;
+        if form.get("token").map(String::as_str) == Some(token) {
+            let session = format!("{:032x}", rand::thread_rng().gen::<u128>());
+            match tokio::time::timeout(Duration::from_secs(5), data.write()).await {
+                Ok(mut data) => {
+                    let sessions: &mut HashMap<String, DashboardSession> = data.get_mut::<DashboardSessions>().unwrap();
+                    // sessions of an old token or past their expiry can't log in anymore
+                    sessions.retain(|_, session| session.token == token && session.expires > Local::now());
+                    sessions.insert(session.clone(), DashboardSession {
+                        token: String::from(token),
+                        expires: Local::now() + ChronoDuration::hours(SESSION_HOURS),
+                    });
+                }
+                Err(_) => return busy(),
+            }
+            return Response::builder()
+                .status(StatusCode::SEE_OTHER)
+                .header(LOCATION, "/dashboard")
+                .header(SET_COOKIE, format!("{}={}; Path=/dashboard; Max-Age={}; HttpOnly; Secure; SameSite=Strict", COOKIE_NAME, session, SESSION_HOURS * 3600))
+                .body(Body::empty())
+                .unwrap();
+        }
+        return html(StatusCode::UNAUTHORIZED, login_form(Some("Wrong token.")));
+    }
+    html(StatusCode::OK, login_form(None))
+}
+
+fn login_form(error: Option<&str>) -> String {
+    format!("<h1>Scrimbot dashboard</h1>{}\
+        <form method=\"post\" action=\"/dashboard/login\">\
+        <input type=\"password\" name=\"token\" placeholder=\"Dashboard token\" autofocus> <button>Log in</button>\
+        </form>",
+            error.map(|error| format!("<p class=\"notice\">{}</p>", escape(error))).unwrap_or_default())
+}
+
+fn request_session(request: &Request<Body>) -> Option<&str> {
+    request.headers()
+        .get(COOKIE)?
+        .to_str()
+        .ok()?
+        .split(';')
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == COOKIE_NAME)
+        .map(|(_, value)| value)
+}
+
+fn query_param(request: &Request<Body>, name: &str) -> Option<String> {
+    let query: HashMap<String, String> = serde_urlencoded::from_str(request.uri().query()?).ok()?;
+    query.get(name).cloned()
+}
+
+async fn read_form(request: Request<Body>) -> HashMap<String, String> {
+    match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => serde_urlencoded::from_bytes(&body).unwrap_or_default(),
+        Err(_) => HashMap::new(),
+    }
+}
+
+fn form_user_id(form: &HashMap<String, String>) -> Option<u64> {
+    form.get("user_id").and_then(|id| id.trim().parse().ok())
+}
+
+async fn kick(outbox: &Outbox, data: &mut TypeMap, form: &HashMap<String, String>) -> String {
+    if data.get::<BotState>().unwrap().state != State::Queue {
+        return String::from("Cannot kick after .start, cancel the setup first.");
+    }
+    let user = match form_user_id(form).and_then(|id| data.get::<UserQueue>().unwrap().iter().find(|user| *user.id.as_u64() == id).cloned()) {
+        Some(user) => user,
+        None => return String::from("That user is not in the queue."),
+    };
+    let queue_len = remove_from_queue(data, std::slice::from_ref(&user));
+    record_queue_event(data, QueueEventKind::Kick, Some(*user.id.as_u64()));
+    announce_removal(outbox, data, &user, " has been kicked", queue_len).await;
+    format!("Kicked {} from the queue.", user.name)
+}
+
+async fn ban(outbox: &Outbox, data: &mut TypeMap, form: &HashMap<String, String>) -> String {
+    let user_id = match form_user_id(form) {
+        Some(user_id) => user_id,
+        None => return String::from("Enter a Discord user id to ban."),
+    };
+    let minutes: i64 = match form.get("minutes").and_then(|minutes| minutes.trim().parse().ok()) {
+        Some(minutes) if minutes > 0 => minutes,
+        _ => return String::from("Enter the ban length in minutes."),
+    };
+    let player_stats: &mut HashMap<u64, PlayerStats> = data.get_mut::<PlayerStatsCache>().unwrap();
+    let stats = player_stats.entry(user_id).or_default();
+    let until = (Local::now() + ChronoDuration::minutes(minutes)).timestamp().max(stats.cooldown_until.unwrap_or_default());
+    stats.cooldown_until = Some(until);
+    let until = Local.timestamp_opt(until, 0).unwrap();
+    write_to_file(String::from("player_stats.json"), serde_json::to_string(player_stats).unwrap()).await;
+    let waitlist: &mut Vec<User> = data.get_mut::<Waitlist>().unwrap();
+    waitlist.retain(|user| *user.id.as_u64() != user_id);
+    let queued = data.get::<UserQueue>().unwrap().iter().find(|user| *user.id.as_u64() == user_id).cloned();
+    if let (Some(user), State::Queue) = (queued, &data.get::<BotState>().unwrap().state) {
+        let queue_len = remove_from_queue(data, std::slice::from_ref(&user));
+        announce_removal(outbox, data, &user, " has been removed by an admin", queue_len).await;
+    }
+    audit_log(outbox, data, "Dashboard ban", format!("<@{}> is on a queue cooldown until {}", user_id, until.format("%Y-%m-%d %H:%M"))).await;
+    format!("Banned {} from the queue until {}.", user_id, until.format("%Y-%m-%d %H:%M"))
+}
+
+async fn unban(outbox: &Outbox, data: &mut TypeMap, form: &HashMap<String, String>) -> String {
+    let user_id = match form_user_id(form) {
+        Some(user_id) => user_id,
+        None => return String::from("Enter a Discord user id to unban."),
+    };
+    let player_stats: &mut HashMap<u64, PlayerStats> = data.get_mut::<PlayerStatsCache>().unwrap();
+    match player_stats.get_mut(&user_id) {
+        Some(stats) if stats.cooldown_until.is_some() => stats.cooldown_until = None,
+        _ => return String::from("That user has no queue cooldown."),
+    }
+    write_to_file(String::from("player_stats.json"), serde_json::to_string(player_stats).unwrap()).await;
+    audit_log(outbox, data, "Dashboard unban", format!("<@{}>'s queue cooldown was lifted", user_id)).await;
+    format!("Lifted the queue cooldown of {}.", user_id)
+}
+
+/// Tells the queue channel about a removal, logs it and fills the free spot from the waitlist
+async fn announce_removal(outbox: &Outbox, data: &mut TypeMap, user: &User, text: &str, queue_len: usize) {
+    let channel_id = match queue_channel(data) {
+        Some(channel_id) => channel_id,
+        None => return,
+    };
+    let response = MessageBuilder::new()
+        .mention(user)
+        .push(text)
+        .push(queue_size_note(data, queue_len))
+        .build();
+    outbox.say(channel_id, response).await;
+    audit_log(outbox, data, "Dashboard kick", format!("<@{}> was removed from the queue from the dashboard ({}/{})", user.id, queue_len, queue_size(data))).await;
+    let guild_id = data.get::<QueueActivity>().unwrap().as_ref().and_then(|activity| activity.guild_id);
+    promote_waitlist(outbox, channel_id, guild_id, data).await;
+}
+
+async fn add_map(outbox: &Outbox, data: &mut TypeMap, form: &HashMap<String, String>) -> String {
+    let map_name = form.get("map").map(|map| map.trim().to_string()).unwrap_or_default();
+    if map_name.is_empty() || map_name.contains(' ') {
+        return String::from("Map names can't be empty or contain spaces.");
+    }
+    let maps: &mut Vec<String> = data.get_mut::<Maps>().unwrap();
+    if maps.contains(&map_name) {
+        return format!("{} is already in the map pool.", map_name);
+    }
+    if maps.len() >= 26 {
+        return String::from("The map vote supports at most 26 maps.");
+    }
+    maps.push(map_name.clone());
+    write_to_file(String::from("maps.json"), serde_json::to_string(maps).unwrap()).await;
+    audit_log(outbox, data, "Dashboard map pool", format!("`{}` was added", map_name)).await;
+    format!("Added {}.", map_name)
+}
+
+async fn remove_map(outbox: &Outbox, data: &mut TypeMap, form: &HashMap<String, String>) -> String {
+    let map_name = form.get("map").cloned().unwrap_or_default();
+    let maps: &mut Vec<String> = data.get_mut::<Maps>().unwrap();
+    if !maps.contains(&map_name) {
+        return format!("{} is not in the map pool.", map_name);
+    }
+    maps.retain(|map| map != &map_name);
+    write_to_file(String::from("maps.json"), serde_json::to_string(maps).unwrap()).await;
+    let map_images: &mut HashMap<String, String> = data.get_mut::<MapImages>().unwrap();
+    if map_images.remove(&map_name).is_some() {
+        write_to_file(String::from("map_images.json"), serde_json::to_string(map_images).unwrap()).await;
+    }
+    audit_log(outbox, data, "Dashboard map pool", format!("`{}` was removed", map_name)).await;
+    format!("Removed {}.", map_name)
+}
+
+async fn user_name(user_id: u64) -> String {
+    match context() {
+        Some(context) => match context.cache.user(UserId(user_id)).await {
+            Some(user) => escape(&user.name),
+            None => user_id.to_string(),
+        },
+        None => user_id.to_string(),
+    }
+}
+
+async fn page(data: &TypeMap, notice: Option<&str>) -> String {
+    let mut body = String::from("<h1>Scrimbot dashboard</h1>");
+    if let Some(notice) = notice {
+        body.push_str(&format!("<p class=\"notice\">{}</p>", escape(notice)));
+    }
+    if context().is_none() {
+        body.push_str("<p class=\"notice\">The bot is not connected to Discord yet, changes won't be announced.</p>");
+    }
+
+    let user_queue: &Vec<User> = data.get::<UserQueue>().unwrap();
+    let queue_msgs: &HashMap<u64, String> = data.get::<QueueMessages>().unwrap();
+    let riot_id_cache: &HashMap<u64, String> = data.get::<RiotIdCache>().unwrap();
+    body.push_str(&format!("<h2>Queue {}/{} &middot; {}</h2><table><tr><th>Player</th><th>Riot ID</th><th>Note</th><th></th></tr>",
+                           user_queue.len(), queue_size(data), data.get::<BotState>().unwrap().state));
+    for user in user_queue {
+        body.push_str(&format!("<tr><td>{}</td><td>{}</td><td>{}</td><td>\
+            <form method=\"post\" action=\"/dashboard/kick\"><input type=\"hidden\" name=\"user_id\" value=\"{}\"><button>Kick</button></form></td></tr>",
+                               escape(&user.name),
+                               escape(riot_id_cache.get(user.id.as_u64()).map(String::as_str).unwrap_or("-")),
+                               escape(queue_msgs.get(user.id.as_u64()).map(String::as_str).unwrap_or("")),
+                               user.id));
+    }
+    body.push_str("</table>");
+    let waitlist: &Vec<User> = data.get::<Waitlist>().unwrap();
+    if !waitlist.is_empty() {
+        let names: Vec<String> = waitlist.iter().map(|user| escape(&user.name)).collect();
+        body.push_str(&format!("<p>Waitlist: {}</p>", names.join(", ")));
+    }
+
+    body.push_str("<h2>Map pool</h2><table>");
+    for map in data.get::<Maps>().unwrap() {
+        body.push_str(&format!("<tr><td>{}</td><td><form method=\"post\" action=\"/dashboard/maps/remove\">\
+            <input type=\"hidden\" name=\"map\" value=\"{}\"><button>Remove</button></form></td></tr>", escape(map), escape(map)));
+    }
+    body.push_str("</table><form method=\"post\" action=\"/dashboard/maps/add\">\
+        <input name=\"map\" placeholder=\"Map name\"> <button>Add map</button></form>");
+
+    body.push_str("<h2>Bans</h2><form method=\"post\" action=\"/dashboard/ban\">\
+        <input name=\"user_id\" placeholder=\"Discord user id\"> <input name=\"minutes\" placeholder=\"Minutes\" size=\"8\"> <button>Ban from queue</button></form>");
+
+    let player_stats: &HashMap<u64, PlayerStats> = data.get::<PlayerStatsCache>().unwrap();
+    let mut players: Vec<(&u64, &PlayerStats)> = player_stats.iter().collect();
+    players.sort_by(|a, b| b.1.wins.cmp(&a.1.wins).then(a.1.losses.cmp(&b.1.losses)));
+    body.push_str("<h2>Player stats</h2><table><tr><th>Player</th><th>Wins</th><th>Losses</th><th>Dodges</th><th>No-shows</th><th>Queue cooldown</th></tr>");
+    for (user_id, stats) in players {
+        let cooldown = stats.cooldown_until
+            .and_then(|timestamp| Local.timestamp_opt(timestamp, 0).single())
+            .filter(|until| until > &Local::now());
+        let cooldown = match cooldown {
+            Some(until) => format!("until {} <form method=\"post\" action=\"/dashboard/unban\">\
+                <input type=\"hidden\" name=\"user_id\" value=\"{}\"><button>Lift</button></form>", until.format("%Y-%m-%d %H:%M"), user_id),
+            None => String::from("-"),
+        };
+        body.push_str(&format!("<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                               user_name(*user_id).await, stats.wins, stats.losses, stats.dodges, stats.no_shows, cooldown));
+    }
+    body.push_str("</table>");
+
+    body.push_str("<h2>Match history</h2><table><tr><th>Ended</th><th>Map</th><th>Team A</th><th>Team B</th><th>Score</th></tr>");
+    for (record, result) in data.get::<MatchHistory>().unwrap().iter().rev().take(50).filter_map(|record| Some((record, record.result.as_ref()?))) {
+        let ended = Local.timestamp_opt(result.ended, 0).single()
+            .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        let mut team_a = Vec::new();
+        for user_id in record.team_ids(Team::A) {
+            team_a.push(user_name(user_id).await);
+        }
+        let mut team_b = Vec::new();
+        for user_id in record.team_ids(Team::B) {
+            team_b.push(user_name(user_id).await);
+        }
+        body.push_str(&format!("<tr><td>{}</td><td>{}</td><td><b>{}</b>: {}</td><td><b>{}</b>: {}</td><td>{}</td></tr>",
+                               ended,
+                               escape(record.draft.map.as_deref().unwrap_or("-")),
+                               escape(&result.team_a_name), team_a.join(", "),
+                               escape(&result.team_b_name), team_b.join(", "),
+                               result.score.map(|(a, b)| format!("{}-{}", a, b)).unwrap_or_else(|| String::from("-"))));
+    }
+    body.push_str("</table>");
+    body
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn html(status: StatusCode, body: String) -> Response<Body> {
+    let document = format!("<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Scrimbot dashboard</title>\
+        <style>body{{font-family:sans-serif;max-width:960px;margin:2em auto;padding:0 1em}}\
+        table{{border-collapse:collapse;margin-bottom:1em}}td,th{{border-bottom:1px solid #ddd;padding:4px 8px;text-align:left}}\
+        form{{display:inline}}.notice{{background:#fff3cd;padding:8px}}</style></head><body>{}</body></html>", body);
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(Body::from(document))
+        .unwrap()
+}
+
+fn redirect(location: &str) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::SEE_OTHER)
+        .header(LOCATION, location)
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn busy() -> Response<Body> {
+    html(StatusCode::SERVICE_UNAVAILABLE, String::from("<p>The bot is busy, try again in a moment.</p>"))
+}
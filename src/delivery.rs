@@ -0,0 +1,90 @@
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serenity::http::{Http, HttpError};
+use serenity::model::channel::{Message, ReactionType};
+use serenity::model::id::{ChannelId, MessageId};
+
+/// Tries per message or reaction. Serenity already waits out Discord's rate limit buckets before sending, the retries
+/// cover 429s that slip through, server errors and connections that failed before the request went out
+const ATTEMPTS: u32 = 4;
+
+/// Sends a message to the channel. The first try happens right away, transient failures are retried with a growing
+/// delay in the background and `None` is returned, so callers holding the bot data never wait out the backoff.
+/// Failures are logged, a lost announcement never aborts the command that sent it
+pub(crate) async fn say(http: &Arc<Http>, channel_id: ChannelId, content: impl Display) -> Option<Message> {
+    let content = content.to_string();
+    match channel_id.say(http, &content).await {
+        Ok(message) => Some(message),
+        Err(why) if is_transient(&why) => {
+            let http = http.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                if let Err(why) = retry(ATTEMPTS - 1, || channel_id.say(&http, &content)).await {
+                    eprintln!("Error sending message to {}: {:?}", channel_id, why);
+                }
+            });
+            None
+        }
+        Err(why) => {
+            eprintln!("Error sending message to {}: {:?}", channel_id, why);
+            None
+        }
+    }
+}
+
+/// Sends a message the caller needs back, i.e. to react to it, with a single try. A late retry would leave a message
+/// nobody tracks, so failures are only logged
+pub(crate) async fn post(http: &Http, channel_id: ChannelId, content: impl Display) -> Option<Message> {
+    match channel_id.say(http, content).await {
+        Ok(message) => Some(message),
+        Err(why) => {
+            eprintln!("Error sending message to {}: {:?}", channel_id, why);
+            None
+        }
+    }
+}
+
+/// Adds a reaction to a message, retrying transient failures like `say`
+pub(crate) async fn react(http: &Http, channel_id: ChannelId, message_id: MessageId, reaction: ReactionType) -> serenity::Result<()> {
+    retry(ATTEMPTS, || channel_id.create_reaction(http, message_id, reaction.clone())).await
+}
+
+/// Adds the reactions in order, one at a time so a burst like the map vote letters queues up behind the rate limit
+/// instead of failing halfway
+pub(crate) async fn react_all(http: &Http, channel_id: ChannelId, message_id: MessageId, reactions: impl IntoIterator<Item = ReactionType>) -> serenity::Result<()> {
+    for reaction in reactions {
+        react(http, channel_id, message_id, reaction).await?;
+    }
+    Ok(())
+}
+
+async fn retry<T, F, Fut>(attempts: u32, mut request: F) -> serenity::Result<T>
+    where F: FnMut() -> Fut, Fut: Future<Output = serenity::Result<T>> {
+    let mut attempt = 1;
+    loop {
+        match request().await {
+            Err(why) if attempt < attempts && is_transient(&why) => {
+                tokio::time::sleep(Duration::from_millis(500 * 2_u64.pow(attempt - 1))).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Rate limits, Discord server errors and connections that couldn't be opened are worth another try. Other request
+/// errors like a timeout may have reached Discord already and a retry would post twice, anything else like a missing
+/// permission fails the same way every time
+fn is_transient(error: &serenity::Error) -> bool {
+    match error {
+        serenity::Error::Http(why) => match why.as_ref() {
+            HttpError::UnsuccessfulRequest(response) => response.status_code.as_u16() == 429 || response.status_code.is_server_error(),
+            HttpError::Request(why) => why.is_connect(),
+            _ => false,
+        },
+        _ => false,
+    }
+}
@@ -0,0 +1,49 @@
+use std::fmt;
+
+/// Errors returned by command handlers, `handle_command_error` turns them into a reply to the user
+pub(crate) enum BotError {
+    /// A problem with how the command was used, the text is sent to the user as is
+    Usage(String),
+    Discord(serenity::Error),
+    Http(reqwest::Error),
+    Json(serde_json::Error),
+    Yaml(serde_yaml::Error),
+}
+
+pub(crate) type BotResult<T = ()> = Result<T, BotError>;
+
+impl fmt::Display for BotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BotError::Usage(text) => write!(f, "{}", text),
+            BotError::Discord(why) => write!(f, "Discord error: {}", why),
+            BotError::Http(why) => write!(f, "HTTP error: {}", why),
+            BotError::Json(why) => write!(f, "JSON error: {}", why),
+            BotError::Yaml(why) => write!(f, "YAML error: {}", why),
+        }
+    }
+}
+
+impl From<serenity::Error> for BotError {
+    fn from(why: serenity::Error) -> Self {
+        BotError::Discord(why)
+    }
+}
+
+impl From<reqwest::Error> for BotError {
+    fn from(why: reqwest::Error) -> Self {
+        BotError::Http(why)
+    }
+}
+
+impl From<serde_json::Error> for BotError {
+    fn from(why: serde_json::Error) -> Self {
+        BotError::Json(why)
+    }
+}
+
+impl From<serde_yaml::Error> for BotError {
+    fn from(why: serde_yaml::Error) -> Self {
+        BotError::Yaml(why)
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::RwLock;
+
+static LANGUAGE: RwLock<String> = RwLock::new(String::new());
+
+pub(crate) const LANGUAGES: [&str; 2] = ["en", "es"];
+
+/// Sets the language used by `translate`, unknown languages fall back to English
+pub(crate) fn set_language(language: Option<&str>) {
+    let language = language.map(|language| language.to_lowercase()).unwrap_or_default();
+    *LANGUAGE.write().unwrap() = language;
+}
+
+/// Translates a bot message into the configured language. Messages are looked up by their English text,
+/// `{}` in a translation template matches any text, so formatted messages are translated as well.
+/// Messages without a translation are returned as is
+pub(crate) fn translate(text: &str) -> String {
+    let language = LANGUAGE.read().unwrap();
+    let translations: &[(&str, &str)] = match language.as_str() {
+        "es" => ES,
+        _ => return String::from(text),
+    };
+    for (english, translated) in translations {
+        if let Some(args) = match_template(english, text) {
+            return fill_template(translated, &args);
+        }
+    }
+    String::from(text)
+}
+
+fn match_template<'a>(template: &str, text: &'a str) -> Option<Vec<&'a str>> {
+    let pieces: Vec<&str> = template.split("{}").collect();
+    if pieces.len() == 1 {
+        return if template == text { Some(Vec::new()) } else { None };
+    }
+    let mut rest = text.strip_prefix(pieces[0])?;
+    let mut args = Vec::new();
+    for (i, piece) in pieces.iter().enumerate().skip(1) {
+        let end = if i == pieces.len() - 1 {
+            if !rest.ends_with(piece) { return None; }
+            rest.len() - piece.len()
+        } else {
+            rest.find(piece)?
+        };
+        args.push(&rest[..end]);
+        rest = &rest[end + piece.len()..];
+    }
+    Some(args)
+}
+
+fn fill_template(template: &str, args: &[&str]) -> String {
+    let mut filled = String::new();
+    for (i, piece) in template.split("{}").enumerate() {
+        if i > 0 {
+            filled.push_str(args.get(i - 1).unwrap_or(&""));
+        }
+        filled.push_str(piece);
+    }
+    filled
+}
+
+const ES: &[(&str, &str)] = &[
+    (" the join duration must be between 1 minute and 24 hours. Example: `.join 90m` or `.join 2h`", " la duración debe estar entre 1 minuto y 24 horas. Ejemplo: `.join 90m` o `.join 2h`"),
+    (" will be removed from the queue at {}", " será eliminado de la cola a las {}"),
+    (" the queue is currently locked: {}", " la cola está bloqueada: {}"),
+    (" cannot join the queue while on a queue cooldown:{}", " no se puede unir a la cola durante una penalización:{}"),
+    ("The bot is going offline for a moment, the queue will be restored when it's back.", "El bot se desconecta un momento, la cola se restaurará cuando vuelva."),
+    (" you must be in the <#{}> voice channel to join the queue. Not in the channel:{}", " debes estar en el canal de voz <#{}> para unirte a la cola. Fuera del canal:{}"),
+    (" a party can have at most {} players.", " un grupo puede tener como máximo {} jugadores."),
+    (" party cannot join, already in the queue:{}", " el grupo no puede unirse, ya están en la cola:{}"),
+    (" sorry but the queue can't fit your party of {}. Open slots: {}", " lo siento, tu grupo de {} no cabe en la cola. Plazas libres: {}"),
+    (" cannot `.votekick` the queue after `.start`, use `.cancel` to start over if needed.", " no puedes usar `.votekick` después de `.start`, usa `.cancel` para empezar de nuevo si es necesario."),
+    (" only players in the queue can start a vote kick.", " solo los jugadores de la cola pueden iniciar una votación de expulsión."),
+    (" invalid message formatting. Example: `.votekick @user`", " formato de mensaje no válido. Ejemplo: `.votekick @usuario`"),
+    (" there is already a vote to kick this player.", " ya hay una votación para expulsar a este jugador."),
+    (" vote kick failed with {}/{} votes.", " la votación de expulsión ha fallado con {}/{} votos."),
+    (" only players in the setup can vote to cancel it.", " solo los jugadores de la partida pueden votar para cancelarla."),
+    (" there is already a vote to cancel the setup.", " ya hay una votación para cancelar la preparación."),
+    ("Vote to cancel failed with {}/{} votes.", "La votación para cancelar ha fallado con {}/{} votos."),
+    ("Setup cancelled by vote ({}/{} votes), the queue is kept. Type `.start` to begin a new setup.", "Preparación cancelada por votación ({}/{} votos), la cola se mantiene. Escribe `.start` para empezar de nuevo."),
+    (". Queue size: {}/{}", ". Tamaño de la cola: {}/{}"),
+    (" is already in the queue.", " ya está en la cola."),
+    (" is already on the waitlist. Position: {}", " ya está en la lista de espera. Posición: {}"),
+    (" the queue is full, you have been added to the waitlist. Position: {}", " la cola está llena, has sido añadido a la lista de espera. Posición: {}"),
+    (" has been added to the queue{}", " ha sido añadido a la cola{}"),
+    (" is not in the queue. Type `.join` to join the queue.", " no está en la cola. Escribe `.join` para unirte a la cola."),
+    (" has left the queue{}", " ha salido de la cola{}"),
+    ("have left the queue as a party{}", "han salido de la cola como grupo{}"),
+    (" has left the waitlist.", " ha salido de la lista de espera."),
+    (" cannot `.leave` the queue after `.start`, use `.cancel` to start over if needed.", " no puedes usar `.leave` después de `.start`, usa `.cancel` para empezar de nuevo si es necesario."),
+    (" queue notifications turned off.", " notificaciones de la cola desactivadas."),
+    (" you'll get a DM when the queue reaches {}/{}.", " recibirás un mensaje privado cuando la cola llegue a {}/{}."),
+    (" invalid message formatting. Example: `.notify 8` or `.notify off`", " formato de mensaje no válido. Ejemplo: `.notify 8` o `.notify off`"),
+    (" confirmed, thanks!", " confirmado, ¡gracias!"),
+    (" there is no AFK check waiting on you.", " no hay ninguna comprobación de AFK esperándote."),
+    (" the queue is not locked", " la cola no está bloqueada"),
+    (" `.start` command has already been entered", " el comando `.start` ya se ha usado"),
+    ("Starting captain pick phase. Two users type `.captain` to start picking teams.", "Empieza la elección de capitanes. Dos usuarios escriben `.captain` para empezar a elegir equipos."),
+    (" command ignored, not in the captain pick phase", " comando ignorado, no estamos en la elección de capitanes"),
+    (" you're already a captain!", " ¡ya eres capitán!"),
+    (" is set as captain.", " es capitán."),
+    ("Randomizing captain pick order...", "Sorteando el orden de elección de los capitanes..."),
+    (" is set as the first pick captain (Team A)", " es el capitán con la primera elección (Equipo A)"),
+    (" is set as the second captain (Team B)", " es el segundo capitán (Equipo B)"),
+    (" it is not currently the draft phase", " ahora mismo no es la fase de selección"),
+    (" please mention a discord user in your message.", " menciona a un usuario de discord en tu mensaje."),
+    (" this user is not in the queue", " este usuario no está en la cola"),
+    (" you are not a captain", " no eres capitán"),
+    (" it is not your turn to pick", " no es tu turno de elegir"),
+    (" this player is already on a team", " este jugador ya está en un equipo"),
+    (" has been added to Team {}", " ha sido añadido al equipo {}"),
+    (" type `.defense` or `.attack` to pick a starting side, or `.coinflip` to pick one at random.", " escribe `.defense` o `.attack` para elegir el lado inicial, o `.coinflip` para elegirlo al azar."),
+    (" You can also react with 🛡️ for {} or ⚔️ for {}.", " También puedes reaccionar con 🛡️ para {} o ⚔️ para {}."),
+    ("Team A starts on {}, Team B starts on {}.", "El equipo A empieza en {}, el equipo B empieza en {}."),
+    ("Coin flip: Team A starts on {}, Team B starts on {}.", "Moneda lanzada: el equipo A empieza en {}, el equipo B empieza en {}."),
+    (" it is not currently the side pick phase", " ahora mismo no es la elección de lado"),
+    (" you are not Captain B", " no eres el capitán B"),
+    ("Setup is completed.", "La preparación ha terminado."),
+    (" invalid Riot id formatting. Please follow this example: `.riotid Martige#NA1`", " formato de Riot id no válido. Sigue este ejemplo: `.riotid Martige#NA1`"),
+    (" cannot `.kick` the queue after `.start`, use `.cancel` to start over if needed.", " no puedes usar `.kick` después de `.start`, usa `.cancel` para empezar de nuevo si es necesario."),
+    (" is not in the queue.", " no está en la cola."),
+    (" has been kicked{}", " ha sido expulsado{}"),
+    (" command only valid during `.start` process", " comando válido solo durante el proceso de `.start`"),
+    (" `.start` process cancelled.", " proceso de `.start` cancelado."),
+    (" there is no completed setup to report a no-show for.", " no hay ninguna partida terminada para reportar una ausencia."),
+    (" only admins and captains of the last match can report a no-show.", " solo los administradores y capitanes de la última partida pueden reportar una ausencia."),
+    (" invalid message formatting. Example: `.noshow @user`", " formato de mensaje no válido. Ejemplo: `.noshow @usuario`"),
+    (" that player was not in the last match.", " ese jugador no estaba en la última partida."),
+    (" you can only spectate after `.start`", " solo puedes ser espectador después de `.start`"),
+    (" players in the queue cannot spectate", " los jugadores de la cola no pueden ser espectadores"),
+    (" is no longer spectating.", " ya no es espectador."),
+    (" sorry but the spectator slots are full.", " lo siento, las plazas de espectador están llenas."),
+    (" is now spectating. Spectators: {}", " ahora es espectador. Espectadores: {}"),
+    (" you can only join as a coach during the `.start` process", " solo puedes unirte como entrenador durante el proceso de `.start`"),
+    (" invalid message formatting. Example: `.coach A` or `.coach B`", " formato de mensaje no válido. Ejemplo: `.coach A` o `.coach B`"),
+    (" players in the queue cannot coach", " los jugadores de la cola no pueden ser entrenadores"),
+    (" you're already the coach of this team!", " ¡ya eres el entrenador de este equipo!"),
+    (" this team already has a coach: @{}", " este equipo ya tiene entrenador: @{}"),
+    (" is set as the coach for Team {}", " es el entrenador del equipo {}"),
+    (" there is no completed setup to request a sub for.", " no hay ninguna partida terminada para pedir un suplente."),
+    (" only captains of the last match can request a sub.", " solo los capitanes de la última partida pueden pedir un suplente."),
+    (" the player you want to replace is not on your team.", " el jugador que quieres reemplazar no está en tu equipo."),
+    (" invalid message formatting. Example: `.teamname TeamName`", " formato de mensaje no válido. Ejemplo: `.teamname NombreEquipo`"),
+    (" team name is over the character limit by {}.", " el nombre del equipo supera el límite de caracteres por {}."),
+    (" custom team name successfully set to `{}`", " nombre de equipo cambiado a `{}`"),
+    ("Current map pool:", "Mapas actuales:"),
+    ("Map pools:", "Grupos de mapas:"),
+    (" unable to add map, already exists.", " no se puede añadir el mapa, ya existe."),
+    (" unable to add map, max amount reached.", " no se puede añadir el mapa, se ha alcanzado el máximo."),
+    (" added map: `{}`", " mapa añadido: `{}`"),
+    (" updated image for map: `{}`", " imagen actualizada para el mapa: `{}`"),
+    (" this map doesn't exist in the list.", " este mapa no existe en la lista."),
+    (" removed map: `{}`", " mapa eliminado: `{}`"),
+    (" switched to map pool `{}`:\n{}", " grupo de mapas cambiado a `{}`:\n{}"),
+    (" queue commands only work in {}", " los comandos de la cola solo funcionan en {}"),
+    ("Only `.riotid`, `.teamname` and `.help` can be used in direct messages.", "Solo `.riotid`, `.teamname` y `.help` se pueden usar en mensajes privados."),
+];
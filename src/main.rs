@@ -1,35 +1,313 @@
 use core::time::Duration as CoreDuration;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use async_std::task;
-use chrono::{Datelike, DateTime, Duration as ChronoDuration, Local, TimeZone};
+use chrono::{DateTime, Duration as ChronoDuration, Local, TimeZone, Timelike};
 use serde::{Deserialize, Serialize};
 use serenity::async_trait;
 use serenity::Client;
+use serenity::client::bridge::gateway::GatewayIntents;
 use serenity::client::Context;
 use serenity::framework::standard::StandardFramework;
-use serenity::model::channel::Message;
+use serenity::model::channel::{Message, PermissionOverwrite, Reaction};
+use serenity::model::event::{Event, PresenceUpdateEvent};
+use serenity::model::id::{ChannelId, GuildId, RoleId, UserId};
 use serenity::model::prelude::Ready;
 use serenity::model::user::User;
-use serenity::prelude::{EventHandler, TypeMapKey};
+use serenity::model::voice::VoiceState;
+use serenity::prelude::{EventHandler, RawEventHandler, TypeMap, TypeMapKey};
+use tokio_util::sync::CancellationToken;
+
+use crate::error::BotResult;
+use crate::messenger::{Messenger, Outbox};
 
 mod bot_service;
+mod cron;
+mod dashboard;
+mod delivery;
+mod error;
+mod i18n;
+mod messenger;
+mod ops;
+mod web;
+mod webhooks;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 struct Config {
     discord: DiscordConfig,
     autoclear_hour: Option<u32>,
+    autoclear_schedule: Option<Vec<String>>,
+    autoclear_skip_dates: Option<Vec<String>>,
+    autoclear_warning_minutes: Option<i64>,
     post_setup_msg: Option<String>,
+    max_spectators: Option<usize>,
+    queue_size: Option<usize>,
+    afk_check_queue_size: Option<usize>,
+    afk_check_idle_minutes: Option<i64>,
+    afk_check_timeout_minutes: Option<u64>,
+    offline_grace_minutes: Option<u64>,
+    lobby_auto_queue: Option<bool>,
+    queue_ping_size: Option<usize>,
+    queue_ping_cooldown_minutes: Option<i64>,
+    dodge_cooldown_minutes: Option<i64>,
+    noshow_limit: Option<u32>,
+    noshow_cooldown_minutes: Option<i64>,
+    aliases: Option<HashMap<String, String>>,
+    command_cooldowns: Option<HashMap<String, i64>>,
+    command_roles: Option<HashMap<String, u64>>,
+    map_vote_seconds: Option<u64>,
+    pick_skip_seconds: Option<i64>,
+    setup_timeout_minutes: Option<u64>,
+    language: Option<String>,
+    team_roles: Option<bool>,
+    team_threads: Option<bool>,
+    scheduled_events: Option<bool>,
+    schedule_reminder_minutes: Option<i64>,
+    queue_open_time: Option<String>,
+    queue_close_time: Option<String>,
+    side_selection: Option<String>,
+    default_defense_team: Option<String>,
+    attack_side_name: Option<String>,
+    defense_side_name: Option<String>,
+    vote_seconds: Option<u64>,
+    votekick_percent: Option<u32>,
+    votekick_cooldown_minutes: Option<i64>,
+    http_port: Option<u16>,
+    api_key: Option<String>,
+    dashboard_token: Option<String>,
+    webhook_urls: Option<Vec<String>>,
+    overlay_file: Option<String>,
+    teamname_banned_words: Option<Vec<String>>,
+    teamname_filters: Option<Vec<String>>,
+    captain_pool_selection: Option<String>,
+    captain_selection: Option<String>,
+    commends_per_match: Option<usize>,
+    strike_threshold: Option<usize>,
+    strike_ban_minutes: Option<i64>,
+    queue_status_message: Option<bool>,
+    reaction_join: Option<bool>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 struct DiscordConfig {
     token: String,
     admin_role_id: Option<u64>,
+    moderator_role_id: Option<u64>,
+    captain_role_id: Option<u64>,
     team_a_channel_id: Option<u64>,
     team_b_channel_id: Option<u64>,
     assign_role_id: Option<u64>,
+    sub_role_id: Option<u64>,
+    spectator_channel_id: Option<u64>,
+    lobby_channel_id: Option<u64>,
+    queue_channel_id: Option<u64>,
+    queue_ping_role_id: Option<u64>,
+    command_channel_ids: Option<Vec<u64>>,
+    temp_channel_category_id: Option<u64>,
+    audit_channel_id: Option<u64>,
+    ops_channel_id: Option<u64>,
+    shard_count: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+struct PlayerStats {
+    #[serde(default)]
+    dodges: u32,
+    #[serde(default)]
+    no_shows: u32,
+    #[serde(default)]
+    wins: u32,
+    #[serde(default)]
+    losses: u32,
+    #[serde(default)]
+    commends: u32,
+    cooldown_until: Option<i64>,
+    #[serde(default)]
+    strikes: Vec<Strike>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum QueueEventKind {
+    Join,
+    Leave,
+    Full,
+    Start,
+    Clear,
+    Kick,
+    Dodge,
+}
+
+/// A queue change kept for `.analytics`, `queue_size` is the size right after the event
+#[derive(Serialize, Deserialize, Clone)]
+struct QueueEvent {
+    time: i64,
+    kind: QueueEventKind,
+    user_id: Option<u64>,
+    queue_size: usize,
+    // seconds from the first join to a full queue, set on `Full` events
+    #[serde(default)]
+    fill_seconds: Option<i64>,
+}
+
+/// The pinned queue status message in the queue channel and the text it shows
+#[derive(Serialize, Deserialize, Clone)]
+struct StatusMessage {
+    channel_id: u64,
+    message_id: u64,
+    #[serde(skip)]
+    content: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Strike {
+    reason: String,
+    issued_by: u64,
+    created: i64,
+}
+
+/// A captain's team name, emoji and embed color, set with the `.teamname` variants
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(from = "TeamProfileFormat")]
+struct TeamProfile {
+    name: Option<String>,
+    emoji: Option<String>,
+    color: Option<u32>,
+}
+
+// teamnames.json used to map captains straight to their team name
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TeamProfileFormat {
+    Name(String),
+    Profile {
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(default)]
+        emoji: Option<String>,
+        #[serde(default)]
+        color: Option<u32>,
+    },
+}
+
+impl From<TeamProfileFormat> for TeamProfile {
+    fn from(format: TeamProfileFormat) -> Self {
+        match format {
+            TeamProfileFormat::Name(name) => TeamProfile { name: Some(name), ..Default::default() },
+            TeamProfileFormat::Profile { name, emoji, color } => TeamProfile { name, emoji, color },
+        }
+    }
+}
+
+/// A setup from `.start` to `.end`: the queue it started from, the draft with its map and sides, and the reported result.
+/// The one in progress is kept under `CurrentMatch`, `.ready` copies it to `LastMatch` and `.end` adds it to the history
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(from = "MatchFormat")]
+struct Match {
+    players: Vec<User>,
+    started: i64,
+    draft: Draft,
+    ready: Option<i64>,
+    result: Option<MatchResult>,
+    /// Players reported with `.noshow`, each is only counted once per match
+    no_shows: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct MatchResult {
+    ended: i64,
+    team_a_name: String,
+    team_b_name: String,
+    score: Option<(u32, u32)>,
+}
+
+impl Match {
+    /// Resets to a new setup started by the given queue
+    fn start(&mut self, players: Vec<User>) {
+        *self = Match { players, started: Local::now().timestamp(), ..Default::default() };
+    }
+
+    fn team_ids(&self, team: Team) -> Vec<u64> {
+        let players = match team {
+            Team::A => &self.draft.team_a,
+            Team::B => &self.draft.team_b,
+        };
+        players.iter().map(|user| *user.id.as_u64()).collect()
+    }
+
+    fn has_player(&self, user_id: u64) -> bool {
+        self.draft.team_a.iter().chain(self.draft.team_b.iter()).any(|user| *user.id.as_u64() == user_id)
+    }
+}
+
+// last_match.json and session.json used to hold only the draft, match_history.json a record with the players' ids
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MatchFormat {
+    Match {
+        #[serde(default)]
+        players: Vec<User>,
+        started: i64,
+        draft: Draft,
+        #[serde(default)]
+        ready: Option<i64>,
+        #[serde(default)]
+        result: Option<MatchResult>,
+        #[serde(default)]
+        no_shows: Vec<u64>,
+    },
+    Record(MatchRecord),
+    Draft(Draft),
+}
+
+#[derive(Deserialize)]
+struct MatchRecord {
+    ended: i64,
+    map: Option<String>,
+    team_a_name: String,
+    team_b_name: String,
+    team_a: Vec<u64>,
+    team_b: Vec<u64>,
+    score: Option<(u32, u32)>,
+}
+
+impl From<MatchFormat> for Match {
+    fn from(format: MatchFormat) -> Self {
+        match format {
+            MatchFormat::Match { players, started, draft, ready, result, no_shows } => Match { players, started, draft, ready, result, no_shows },
+            MatchFormat::Record(record) => {
+                // only the ids were kept, the names are filled in with them
+                let user = |user_id: &u64| {
+                    let mut user = User::default();
+                    user.id = UserId(*user_id);
+                    user.name = user_id.to_string();
+                    user
+                };
+                let draft = Draft {
+                    map: record.map,
+                    team_a: record.team_a.iter().map(user).collect(),
+                    team_b: record.team_b.iter().map(user).collect(),
+                    ..Default::default()
+                };
+                Match {
+                    players: draft.team_a.iter().chain(draft.team_b.iter()).cloned().collect(),
+                    started: record.ended,
+                    draft,
+                    ready: Some(record.ended),
+                    result: Some(MatchResult { ended: record.ended, team_a_name: record.team_a_name, team_b_name: record.team_b_name, score: record.score }),
+                    no_shows: Vec::new(),
+                }
+            }
+            MatchFormat::Draft(draft) => Match {
+                players: draft.team_a.iter().chain(draft.team_b.iter()).cloned().collect(),
+                draft,
+                ..Default::default()
+            },
+        }
+    }
 }
 
 #[derive(PartialEq)]
@@ -37,39 +315,405 @@ struct StateContainer {
     state: State,
 }
 
+#[derive(Clone, Default, Serialize, Deserialize)]
 struct Draft {
+    map: Option<String>,
+    /// Set once the setup is ready, the captain pick, draft and side pick keep the captains in their `State`
     captain_a: Option<User>,
     captain_b: Option<User>,
     team_a: Vec<User>,
     team_b: Vec<User>,
+    coach_a: Option<User>,
+    coach_b: Option<User>,
     team_b_start_side: String,
-    current_picker: Option<User>,
+    /// Captains whose turn was passed with `.skip`, once per skipped turn
+    #[serde(default)]
+    skipped_turns: Vec<u64>,
 }
 
-#[derive(PartialEq)]
+impl Draft {
+    fn captain(&self, team: Team) -> Option<&User> {
+        match team {
+            Team::A => self.captain_a.as_ref(),
+            Team::B => self.captain_b.as_ref(),
+        }
+    }
+}
+
+/// The setup phase. Data that only exists during one phase lives in its variant and is dropped with it, the captains
+/// only move to the match record once the setup is ready
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
 enum State {
     Queue,
     MapPick,
-    CaptainPick,
-    Draft,
-    SidePick,
+    /// The captains set so far, the draft starts once both are
+    CaptainPick { captain_a: Option<User>, captain_b: Option<User> },
+    /// `message_id` is the draft embed that's edited after every pick
+    Draft { captains: Captains, turn: Turn, message_id: Option<u64> },
+    /// `prompt_id` is the prompt Captain B can react to instead of typing the side
+    SidePick { captains: Captains, prompt_id: Option<u64> },
     Ready,
 }
 
+impl State {
+    /// The captain pick without any captains
+    fn captain_pick() -> State {
+        State::CaptainPick { captain_a: None, captain_b: None }
+    }
+
+    /// The draft with Captain A picking first
+    fn draft(captains: Captains) -> State {
+        State::Draft { captains, turn: Turn::start(Team::A), message_id: None }
+    }
+
+    fn side_pick(captains: Captains) -> State {
+        State::SidePick { captains, prompt_id: None }
+    }
+
+    /// Hands the pick to the other captain, outside the draft there's no turn to pass
+    fn pass_turn(&mut self) {
+        if let State::Draft { turn, .. } = self {
+            *turn = turn.pass();
+        }
+    }
+
+    /// Both captains, only during the draft and the side pick
+    fn captains(&self) -> Option<&Captains> {
+        match self {
+            State::Draft { captains, .. } | State::SidePick { captains, .. } => Some(captains),
+            _ => None,
+        }
+    }
+
+    fn captains_mut(&mut self) -> Option<&mut Captains> {
+        match self {
+            State::Draft { captains, .. } | State::SidePick { captains, .. } => Some(captains),
+            _ => None,
+        }
+    }
+
+    /// Puts `substitute` in place of `missing` if they're one of the setup's captains
+    fn replace_captain(&mut self, missing: &User, substitute: &User) {
+        let captains: Vec<&mut User> = match self {
+            State::CaptainPick { captain_a, captain_b } => captain_a.iter_mut().chain(captain_b.iter_mut()).collect(),
+            State::Draft { captains, .. } | State::SidePick { captains, .. } => vec![&mut captains.a, &mut captains.b],
+            _ => Vec::new(),
+        };
+        for captain in captains {
+            if captain == missing {
+                *captain = substitute.clone();
+            }
+        }
+    }
+
+    fn is_captain_pick(&self) -> bool {
+        matches!(self, State::CaptainPick { .. })
+    }
+
+    fn is_draft(&self) -> bool {
+        matches!(self, State::Draft { .. })
+    }
+
+    fn is_side_pick(&self) -> bool {
+        matches!(self, State::SidePick { .. })
+    }
+}
+
+/// The captains of a setup in the draft and side pick, Captain A picks first and Captain B picks the side
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
+struct Captains {
+    a: User,
+    b: User,
+}
+
+impl Captains {
+    fn get(&self, team: Team) -> &User {
+        match team {
+            Team::A => &self.a,
+            Team::B => &self.b,
+        }
+    }
+
+    fn get_mut(&mut self, team: Team) -> &mut User {
+        match team {
+            Team::A => &mut self.a,
+            Team::B => &mut self.b,
+        }
+    }
+
+    fn contains(&self, user: &User) -> bool {
+        self.a == *user || self.b == *user
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
+enum Team {
+    A,
+    B,
+}
+
+impl Team {
+    fn other(self) -> Team {
+        match self {
+            Team::A => Team::B,
+            Team::B => Team::A,
+        }
+    }
+}
+
+/// The team picking in the draft and when its captain's turn started, `.skip` goes by the start
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
+struct Turn {
+    team: Team,
+    started: i64,
+}
+
+impl Turn {
+    fn start(team: Team) -> Turn {
+        Turn { team, started: Local::now().timestamp() }
+    }
+
+    /// The other captain's turn, after a pick or a skip
+    fn pass(self) -> Turn {
+        Turn::start(self.team.other())
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            State::Queue => "Queue",
+            State::MapPick => "Map Vote",
+            State::CaptainPick { .. } => "Captain Pick",
+            State::Draft { .. } => "Draft",
+            State::SidePick { .. } => "Side Pick",
+            State::Ready => "Ready",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 struct Handler;
 
+// All bot data lives in the one serenity TypeMap below. The queue, draft, caches and config aren't behind locks of their
+// own because most handlers change several of them together (a leave touches the queue, parties, waitlist, queue events
+// and stats), separate locks would need one lock order across every handler. Instead:
+// - commands that only read take `context.data.read()`, any number of them run at once
+// - checks that need Discord, like `queue_join_check` and role lookups, run before the write lock is taken
+// - the queue handlers (join, leave, kick, waitlist, lobby voice, autoclear) collect their messages, DMs and role changes
+//   in a `messenger::Outbox` and send it after dropping the lock, `.ready`, `.end`, `.swap` and subs set up channels,
+//   roles and moves after it's dropped too
+// - other commands still post their reply and audit entry while holding the lock, and the setup prompts that need their
+//   message id back (map vote, draft, side pick) are posted under it, those are single attempts with retries in the background
 struct UserQueue;
 
 struct RiotIdCache;
 
+struct RiotAccounts;
+
+struct RegionCache;
+
 struct TeamNameCache;
 
 struct QueueMessages;
 
+struct Parties;
+
+struct Waitlist;
+
+struct CurrentMatch;
+
+struct LastMatch;
+
+struct Spectators;
+
+struct SubRequests;
+
+struct PendingSwap;
+
+struct CommendsGiven;
+
+struct QueueActivity;
+
+struct QueueActivityInfo {
+    channel_id: ChannelId,
+    guild_id: Option<GuildId>,
+    updated: DateTime<Local>,
+}
+
+struct ActiveAfkCheck;
+
+struct SetupCancel;
+
+struct ActiveVotes;
+
+#[derive(PartialEq)]
+enum Vote {
+    Kick(u64),
+    Cancel,
+}
+
+struct OfflineSince;
+
+struct QueueExpiry;
+
+struct QueueJoinTimes;
+
+struct LastQueuePing;
+
+struct QueueLock;
+
+struct ChannelRenames;
+
+struct OriginalChannelNames;
+
+/// Role, channel and category ids from the config that `validate_config` couldn't find, features using them act as if
+/// they weren't set. The config keeps them as written so `.showconfig` and `.setconfig` show the operator's values
+struct DisabledConfigIds;
+
+struct CommandAliases;
+
+struct PriorityPlayers;
+
+struct PlayerStatsCache;
+
+struct MatchHistory;
+
+struct NotifySubscriptions;
+
+struct CaptainPool;
+
+struct QueueEvents;
+
+struct QueueEventsUnsaved;
+
+struct QueueSessionState;
+
+struct QueueStatusMessage;
+
+struct DashboardSessions;
+
+struct AfkCheck {
+    message_id: u64,
+    pending: Vec<User>,
+}
+
+struct SubRequest {
+    team_a: bool,
+    replacing: User,
+}
+
+struct DashboardSession {
+    token: String,
+    expires: DateTime<Local>,
+}
+
+struct SwapRequest {
+    players: Vec<u64>,
+    confirmed: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct ScheduledScrim {
+    message_id: u64,
+    channel_id: u64,
+    guild_id: Option<u64>,
+    start: i64,
+    title: String,
+    rsvps: Vec<u64>,
+}
+
+/// Everything `.backup` saves and `.restore` loads, queue cooldowns (bans) are part of the player stats
+#[derive(Serialize, Deserialize)]
+struct Backup {
+    version: u32,
+    created: i64,
+    riot_ids: HashMap<u64, String>,
+    #[serde(default)]
+    riot_accounts: HashMap<u64, Vec<String>>,
+    #[serde(default)]
+    regions: HashMap<u64, String>,
+    teamnames: HashMap<u64, TeamProfile>,
+    maps: Vec<String>,
+    map_pools: HashMap<String, Vec<String>>,
+    map_images: HashMap<String, String>,
+    player_stats: HashMap<u64, PlayerStats>,
+    match_history: Vec<Match>,
+    aliases: HashMap<String, String>,
+}
+
+/// In-flight queue and match state, written on shutdown and restored on the next start
+#[derive(Serialize, Deserialize)]
+struct Session {
+    #[serde(deserialize_with = "read_session_state")]
+    state: State,
+    queue: Vec<User>,
+    queue_messages: HashMap<u64, String>,
+    parties: Vec<Vec<User>>,
+    waitlist: Vec<User>,
+    spectators: Vec<User>,
+    priority_players: Vec<u64>,
+    #[serde(alias = "draft")]
+    current_match: Match,
+    last_match: Option<Match>,
+    active_match: Option<ActiveMatchInfo>,
+    #[serde(default)]
+    queue_join_times: HashMap<u64, i64>,
+    #[serde(default)]
+    queue_expiry: HashMap<u64, i64>,
+    /// The channel and server of the last queue activity, `.join` durations are announced there when they run out
+    #[serde(default)]
+    queue_channel: Option<(ChannelId, Option<GuildId>)>,
+}
+
+// session.json used to hold the captain pick, draft and side pick by name only, their captains were in the draft
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SessionState {
+    State(Box<State>),
+    Legacy(LegacyState),
+}
+
+#[derive(Deserialize)]
+enum LegacyState {
+    CaptainPick,
+    Draft,
+    SidePick,
+}
+
+fn read_session_state<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<State, D::Error> {
+    Ok(match SessionState::deserialize(deserializer)? {
+        SessionState::State(state) => *state,
+        // the turn and the side pick prompt weren't saved, the setup goes back to the captain pick on the same map
+        SessionState::Legacy(LegacyState::CaptainPick | LegacyState::Draft | LegacyState::SidePick) => State::captain_pick(),
+    })
+}
+
+struct ScheduledScrims;
+
+struct ActiveMatch;
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+struct ActiveMatchInfo {
+    team_a_channel_id: Option<ChannelId>,
+    team_b_channel_id: Option<ChannelId>,
+    temp_channels: Vec<ChannelId>,
+    renamed_channels: Vec<(ChannelId, String)>,
+    guild_id: Option<GuildId>,
+    team_a_role_id: Option<RoleId>,
+    team_b_role_id: Option<RoleId>,
+    locked_channels: Vec<(ChannelId, Vec<PermissionOverwrite>)>,
+    team_threads: Vec<ChannelId>,
+    scheduled_event_id: Option<u64>,
+}
+
 struct BotState;
 
 struct Maps;
 
+struct MapPools;
+
+struct MapImages;
 
 impl TypeMapKey for UserQueue {
     type Value = Vec<User>;
@@ -83,10 +727,18 @@ impl TypeMapKey for RiotIdCache {
     type Value = HashMap<u64, String>;
 }
 
-impl TypeMapKey for TeamNameCache {
+impl TypeMapKey for RiotAccounts {
+    type Value = HashMap<u64, Vec<String>>;
+}
+
+impl TypeMapKey for RegionCache {
     type Value = HashMap<u64, String>;
 }
 
+impl TypeMapKey for TeamNameCache {
+    type Value = HashMap<u64, TeamProfile>;
+}
+
 impl TypeMapKey for BotState {
     type Value = StateContainer;
 }
@@ -95,14 +747,155 @@ impl TypeMapKey for Maps {
     type Value = Vec<String>;
 }
 
-impl TypeMapKey for Draft {
-    type Value = Draft;
+impl TypeMapKey for MapPools {
+    type Value = HashMap<String, Vec<String>>;
+}
+
+impl TypeMapKey for MapImages {
+    type Value = HashMap<String, String>;
+}
+
+impl TypeMapKey for CurrentMatch {
+    type Value = Match;
 }
 
 impl TypeMapKey for QueueMessages {
     type Value = HashMap<u64, String>;
 }
 
+impl TypeMapKey for Parties {
+    type Value = Vec<Vec<User>>;
+}
+
+impl TypeMapKey for Waitlist {
+    type Value = Vec<User>;
+}
+
+impl TypeMapKey for Spectators {
+    type Value = Vec<User>;
+}
+
+impl TypeMapKey for LastMatch {
+    type Value = Option<Match>;
+}
+
+impl TypeMapKey for SubRequests {
+    type Value = HashMap<u64, SubRequest>;
+}
+
+impl TypeMapKey for PendingSwap {
+    type Value = Option<SwapRequest>;
+}
+
+// who each player commended for the last match
+impl TypeMapKey for CommendsGiven {
+    type Value = HashMap<u64, Vec<u64>>;
+}
+
+impl TypeMapKey for QueueActivity {
+    type Value = Option<QueueActivityInfo>;
+}
+
+impl TypeMapKey for ScheduledScrims {
+    type Value = Vec<ScheduledScrim>;
+}
+
+impl TypeMapKey for ActiveMatch {
+    type Value = Option<ActiveMatchInfo>;
+}
+
+impl TypeMapKey for ActiveAfkCheck {
+    type Value = Option<AfkCheck>;
+}
+
+impl TypeMapKey for SetupCancel {
+    type Value = CancellationToken;
+}
+
+impl TypeMapKey for ActiveVotes {
+    type Value = Vec<Vote>;
+}
+
+impl TypeMapKey for QueueExpiry {
+    type Value = HashMap<u64, DateTime<Local>>;
+}
+
+impl TypeMapKey for QueueJoinTimes {
+    type Value = HashMap<u64, DateTime<Local>>;
+}
+
+impl TypeMapKey for MatchHistory {
+    type Value = Vec<Match>;
+}
+
+impl TypeMapKey for PlayerStatsCache {
+    type Value = HashMap<u64, PlayerStats>;
+}
+
+impl TypeMapKey for PriorityPlayers {
+    type Value = Vec<u64>;
+}
+
+impl TypeMapKey for CommandAliases {
+    type Value = HashMap<String, String>;
+}
+
+impl TypeMapKey for QueueLock {
+    type Value = Option<String>;
+}
+
+impl TypeMapKey for DisabledConfigIds {
+    type Value = HashSet<u64>;
+}
+
+impl TypeMapKey for ChannelRenames {
+    type Value = HashMap<ChannelId, Vec<DateTime<Local>>>;
+}
+
+impl TypeMapKey for OriginalChannelNames {
+    type Value = HashMap<u64, String>;
+}
+
+impl TypeMapKey for LastQueuePing {
+    type Value = Option<DateTime<Local>>;
+}
+
+impl TypeMapKey for NotifySubscriptions {
+    type Value = HashMap<u64, usize>;
+}
+
+impl TypeMapKey for CaptainPool {
+    type Value = Vec<u64>;
+}
+
+impl TypeMapKey for QueueEvents {
+    type Value = Vec<QueueEvent>;
+}
+
+/// Set when queue events were recorded since the last write of queue_events.json
+impl TypeMapKey for QueueEventsUnsaved {
+    type Value = bool;
+}
+
+/// The queue session the recorded queue events are in, for the fill times
+impl TypeMapKey for QueueSessionState {
+    type Value = bot_service::QueueSessions;
+}
+
+impl TypeMapKey for QueueStatusMessage {
+    type Value = Option<StatusMessage>;
+}
+
+/// Dashboard session ids mapped to the `dashboard_token` they logged in with and when they expire
+impl TypeMapKey for DashboardSessions {
+    type Value = HashMap<String, DashboardSession>;
+}
+
+impl TypeMapKey for OfflineSince {
+    type Value = HashMap<u64, DateTime<Local>>;
+}
+
+#[allow(clippy::upper_case_acronyms)]
 enum Command {
     JOIN,
     LEAVE,
@@ -113,14 +906,58 @@ enum Command {
     ADDMAP,
     CANCEL,
     REMOVEMAP,
+    MAPPOOL,
     KICK,
     CAPTAIN,
+    CAPTAINPOOL,
     TEAMNAME,
+    NEEDSUB,
+    SPECTATE,
+    COACH,
+    HERE,
+    NOTIFY,
+    STATS,
+    COMMEND,
+    COMMENDS,
+    WHOIS,
+    NOSHOW,
+    REMATCH,
+    REDRAFT,
+    STRIKE,
+    STRIKES,
     PICK,
     DEFENSE,
     ATTACK,
     RECOVERQUEUE,
     CLEAR,
+    LOCK,
+    UNLOCK,
+    ALIAS,
+    SETCONFIG,
+    SHOWCONFIG,
+    RELOADCONFIG,
+    END,
+    SCHEDULE,
+    STATUS,
+    SETSTATE,
+    FORCETEAMS,
+    FORCECAPTAIN,
+    FORCEPICK,
+    SWAP,
+    REPLACE,
+    TRANSFERCAPTAIN,
+    COINFLIP,
+    SKIP,
+    VOTEKICK,
+    VOTECANCEL,
+    LAST,
+    EXPORT,
+    ANALYTICS,
+    ACTIVITY,
+    BACKUP,
+    RESTORE,
+    IMPORTRIOTIDS,
+    SETRIOTID,
     HELP,
     UNKNOWN,
 }
@@ -140,65 +977,257 @@ impl FromStr for Command {
             ".addmap" => Ok(Command::ADDMAP),
             ".cancel" => Ok(Command::CANCEL),
             ".captain" => Ok(Command::CAPTAIN),
+            ".captainpool" => Ok(Command::CAPTAINPOOL),
             ".teamname" => Ok(Command::TEAMNAME),
+            ".needsub" => Ok(Command::NEEDSUB),
+            ".spectate" => Ok(Command::SPECTATE),
+            ".coach" => Ok(Command::COACH),
+            ".here" => Ok(Command::HERE),
+            ".notify" => Ok(Command::NOTIFY),
+            ".stats" => Ok(Command::STATS),
+            ".commend" => Ok(Command::COMMEND),
+            ".commends" => Ok(Command::COMMENDS),
+            ".whois" => Ok(Command::WHOIS),
+            ".noshow" => Ok(Command::NOSHOW),
+            ".rematch" => Ok(Command::REMATCH),
+            ".redraft" => Ok(Command::REDRAFT),
+            ".strike" => Ok(Command::STRIKE),
+            ".strikes" => Ok(Command::STRIKES),
             ".pick" => Ok(Command::PICK),
             ".defense" => Ok(Command::DEFENSE),
             ".attack" => Ok(Command::ATTACK),
             ".removemap" => Ok(Command::REMOVEMAP),
+            ".mappool" => Ok(Command::MAPPOOL),
             ".recoverqueue" => Ok(Command::RECOVERQUEUE),
             ".clear" => Ok(Command::CLEAR),
+            ".lock" => Ok(Command::LOCK),
+            ".unlock" => Ok(Command::UNLOCK),
+            ".alias" => Ok(Command::ALIAS),
+            ".setconfig" => Ok(Command::SETCONFIG),
+            ".showconfig" => Ok(Command::SHOWCONFIG),
+            ".reloadconfig" => Ok(Command::RELOADCONFIG),
+            ".end" => Ok(Command::END),
+            ".schedule" => Ok(Command::SCHEDULE),
+            ".status" => Ok(Command::STATUS),
+            ".setstate" => Ok(Command::SETSTATE),
+            ".forceteams" => Ok(Command::FORCETEAMS),
+            ".forcecaptain" => Ok(Command::FORCECAPTAIN),
+            ".forcepick" => Ok(Command::FORCEPICK),
+            ".swap" => Ok(Command::SWAP),
+            ".replace" => Ok(Command::REPLACE),
+            ".transfercaptain" => Ok(Command::TRANSFERCAPTAIN),
+            ".coinflip" => Ok(Command::COINFLIP),
+            ".skip" => Ok(Command::SKIP),
+            ".votekick" => Ok(Command::VOTEKICK),
+            ".votecancel" => Ok(Command::VOTECANCEL),
+            ".last" => Ok(Command::LAST),
+            ".export" => Ok(Command::EXPORT),
+            ".analytics" => Ok(Command::ANALYTICS),
+            ".activity" => Ok(Command::ACTIVITY),
+            ".backup" => Ok(Command::BACKUP),
+            ".restore" => Ok(Command::RESTORE),
+            ".importriotids" => Ok(Command::IMPORTRIOTIDS),
+            ".setriotid" => Ok(Command::SETRIOTID),
             ".help" => Ok(Command::HELP),
             _ => Err(()),
         }
     }
 }
 
+/// Maps a command alias to its built-in command, runtime aliases take precedence over config aliases
+fn resolve_alias(data: &TypeMap, command_name: String) -> String {
+    if Command::from_str(&command_name).is_ok() {
+        return command_name;
+    }
+    if let Some(target) = data.get::<CommandAliases>().unwrap().get(&command_name) {
+        return target.clone();
+    }
+    data.get::<Config>().unwrap().aliases.as_ref()
+        .and_then(|aliases| aliases.get(&command_name))
+        .map(|target| target.to_lowercase())
+        .unwrap_or(command_name)
+}
+
+/// Runs a command, errors are reported to the user by the caller
+async fn dispatch(command: Command, context: Context, msg: Message) -> BotResult {
+    match command {
+        Command::JOIN => bot_service::handle_join_command(context, msg).await,
+        Command::LEAVE => bot_service::handle_leave(context, msg).await,
+        Command::LIST => bot_service::handle_list(context, msg).await,
+        Command::START => bot_service::handle_start(context, msg).await,
+        Command::RIOTID => bot_service::handle_riotid(context, msg).await,
+        Command::MAPS => bot_service::handle_map_list(context, msg).await,
+        Command::KICK => bot_service::handle_kick(context, msg).await,
+        Command::CANCEL => bot_service::handle_cancel(context, msg).await,
+        Command::ADDMAP => bot_service::handle_add_map(context, msg).await,
+        Command::REMOVEMAP => bot_service::handle_remove_map(context, msg).await,
+        Command::MAPPOOL => bot_service::handle_map_pool(context, msg).await,
+        Command::TEAMNAME => bot_service::handle_teamname(context, msg).await,
+        Command::CAPTAIN => bot_service::handle_captain(context, msg).await,
+        Command::CAPTAINPOOL => bot_service::handle_captain_pool(context, msg).await,
+        Command::NEEDSUB => bot_service::handle_needsub(context, msg).await,
+        Command::SPECTATE => bot_service::handle_spectate(context, msg).await,
+        Command::COACH => bot_service::handle_coach(context, msg).await,
+        Command::HERE => bot_service::handle_here(context, msg).await,
+        Command::NOTIFY => bot_service::handle_notify(context, msg).await,
+        Command::STATS => bot_service::handle_stats(context, msg).await,
+        Command::COMMEND => bot_service::handle_commend(context, msg).await,
+        Command::COMMENDS => bot_service::handle_commends(context, msg).await,
+        Command::WHOIS => bot_service::handle_whois(context, msg).await,
+        Command::NOSHOW => bot_service::handle_noshow(context, msg).await,
+        Command::REMATCH => bot_service::handle_rematch(context, msg).await,
+        Command::REDRAFT => bot_service::handle_redraft(context, msg).await,
+        Command::STRIKE => bot_service::handle_strike(context, msg).await,
+        Command::STRIKES => bot_service::handle_strikes(context, msg).await,
+        Command::PICK => bot_service::handle_pick(context, msg, false).await,
+        Command::DEFENSE => bot_service::handle_defense_option(context, msg).await,
+        Command::ATTACK => bot_service::handle_attack_option(context, msg).await,
+        Command::RECOVERQUEUE => bot_service::handle_recover_queue(context, msg).await,
+        Command::CLEAR => bot_service::handle_clear(context, msg).await,
+        Command::LOCK => bot_service::handle_lock(context, msg).await,
+        Command::UNLOCK => bot_service::handle_unlock(context, msg).await,
+        Command::ALIAS => bot_service::handle_alias(context, msg).await,
+        Command::SETCONFIG => bot_service::handle_set_config(context, msg).await,
+        Command::SHOWCONFIG => bot_service::handle_show_config(context, msg).await,
+        Command::RELOADCONFIG => bot_service::handle_reload_config(context, msg).await,
+        Command::END => bot_service::handle_end(context, msg).await,
+        Command::SCHEDULE => bot_service::handle_schedule(context, msg).await,
+        Command::STATUS => bot_service::handle_status(context, msg).await,
+        Command::SETSTATE => bot_service::handle_set_state(context, msg).await,
+        Command::FORCETEAMS => bot_service::handle_force_teams(context, msg).await,
+        Command::FORCECAPTAIN => bot_service::handle_force_captain(context, msg).await,
+        Command::FORCEPICK => bot_service::handle_pick(context, msg, true).await,
+        Command::SWAP => bot_service::handle_swap(context, msg).await,
+        Command::REPLACE => bot_service::handle_replace(context, msg).await,
+        Command::TRANSFERCAPTAIN => bot_service::handle_transfer_captain(context, msg).await,
+        Command::COINFLIP => bot_service::handle_coinflip(context, msg).await,
+        Command::SKIP => bot_service::handle_skip(context, msg).await,
+        Command::VOTEKICK => bot_service::handle_votekick(context, msg).await,
+        Command::VOTECANCEL => bot_service::handle_votecancel(context, msg).await,
+        Command::LAST => bot_service::handle_last(context, msg).await,
+        Command::EXPORT => bot_service::handle_export(context, msg).await,
+        Command::ANALYTICS => bot_service::handle_analytics(context, msg).await,
+        Command::ACTIVITY => bot_service::handle_activity(context, msg).await,
+        Command::BACKUP => bot_service::handle_backup(context, msg).await,
+        Command::RESTORE => bot_service::handle_restore(context, msg).await,
+        Command::IMPORTRIOTIDS => bot_service::handle_import_riot_ids(context, msg).await,
+        Command::SETRIOTID => bot_service::handle_set_riot_id(context, msg).await,
+        Command::HELP => bot_service::handle_help(context, msg).await,
+        Command::UNKNOWN => bot_service::handle_unknown(context, msg).await,
+    }
+}
+
 #[async_trait]
 impl EventHandler for Handler {
     async fn message(&self, context: Context, msg: Message) {
         if msg.author.bot { return; }
         if !msg.content.starts_with('.') { return; }
-        let command = Command::from_str(&msg.content.to_lowercase()
+        let command_name = msg.content.to_lowercase()
             .trim()
             .split(' ')
             .take(1)
-            .collect::<Vec<_>>()[0])
+            .collect::<Vec<_>>()[0]
+            .to_string();
+        let command_name = resolve_alias(&*context.data.read().await, command_name);
+        let command = Command::from_str(&command_name)
             .unwrap_or(Command::UNKNOWN);
-        match command {
-            Command::JOIN => bot_service::handle_join(&context, &msg, &msg.author).await,
-            Command::LEAVE => bot_service::handle_leave(context, msg).await,
-            Command::LIST => bot_service::handle_list(context, msg).await,
-            Command::START => bot_service::handle_start(context, msg).await,
-            Command::RIOTID => bot_service::handle_riotid(context, msg).await,
-            Command::MAPS => bot_service::handle_map_list(context, msg).await,
-            Command::KICK => bot_service::handle_kick(context, msg).await,
-            Command::CANCEL => bot_service::handle_cancel(context, msg).await,
-            Command::ADDMAP => bot_service::handle_add_map(context, msg).await,
-            Command::REMOVEMAP => bot_service::handle_remove_map(context, msg).await,
-            Command::TEAMNAME => bot_service::handle_teamname(context, msg).await,
-            Command::CAPTAIN => bot_service::handle_captain(context, msg).await,
-            Command::PICK => bot_service::handle_pick(context, msg).await,
-            Command::DEFENSE => bot_service::handle_defense_option(context, msg).await,
-            Command::ATTACK => bot_service::handle_attack_option(context, msg).await,
-            Command::RECOVERQUEUE => bot_service::handle_recover_queue(context, msg).await,
-            Command::CLEAR => bot_service::handle_clear(context, msg).await,
-            Command::HELP => bot_service::handle_help(context, msg).await,
-            Command::UNKNOWN => bot_service::handle_unknown(context, msg).await,
+        if msg.guild_id.is_none() {
+            // identity commands can be sent privately, everything else needs the queue channel
+            if !matches!(command, Command::RIOTID | Command::TEAMNAME | Command::HELP) {
+                bot_service::send_simple_msg(&context, &msg, "Only `.riotid`, `.teamname` and `.help` can be used in direct messages.").await;
+                return;
+            }
+        } else if !matches!(command, Command::HELP | Command::UNKNOWN) && !bot_service::command_channel_check(&context, &msg).await { return; }
+        if bot_service::command_on_cooldown(&context, &msg, &command_name).await { return; }
+        if let Err(why) = dispatch(command, context.clone(), msg.clone()).await {
+            bot_service::handle_command_error(&context, &msg, why).await;
         }
+        bot_service::refresh_status_message(&context).await;
+    }
+    async fn reaction_add(&self, context: Context, reaction: Reaction) {
+        bot_service::handle_afk_reaction(&context, &reaction).await;
+        bot_service::handle_schedule_reaction(&context, &reaction, true).await;
+        bot_service::handle_side_pick_reaction(&context, &reaction).await;
+        bot_service::handle_queue_reaction(&context, &reaction, true).await;
+        bot_service::handle_sub_reaction(context.clone(), reaction).await;
+        bot_service::refresh_status_message(&context).await;
+    }
+    async fn reaction_remove(&self, context: Context, reaction: Reaction) {
+        bot_service::handle_schedule_reaction(&context, &reaction, false).await;
+        bot_service::handle_queue_reaction(&context, &reaction, false).await;
+        bot_service::refresh_status_message(&context).await;
+    }
+    async fn presence_update(&self, context: Context, new_data: PresenceUpdateEvent) {
+        bot_service::handle_presence_update(context.clone(), new_data).await;
+        bot_service::refresh_status_message(&context).await;
+    }
+    async fn voice_state_update(&self, context: Context, _: Option<GuildId>, _old: Option<VoiceState>, new: VoiceState) {
+        bot_service::handle_voice_state_update(context.clone(), new).await;
+        bot_service::refresh_status_message(&context).await;
+    }
+    async fn cache_ready(&self, context: Context, guilds: Vec<GuildId>) {
+        bot_service::validate_config(&context, &guilds).await;
+        bot_service::restore_channel_names(&context).await;
     }
     async fn ready(&self, context: Context, ready: Ready) {
-        println!("{} is connected!", ready.user.name);
+        match ready.shard {
+            Some([shard_id, shard_count]) => println!("{} is connected on shard {}/{}!", ready.user.name, shard_id + 1, shard_count),
+            None => println!("{} is connected!", ready.user.name),
+        }
+        web::set_context(&context);
+        // every shard gets a ready event and so does every reconnect, the timers share the bot data and only run once
+        if ready.shard.is_some_and(|[shard_id, _]| shard_id != 0) || PERIODIC_TASKS_STARTED.swap(true, Ordering::SeqCst) { return; }
+        let idle_context = context.clone();
+        tokio::spawn(async move { bot_service::idle_afk_check(&idle_context).await });
+        bot_service::resume_scheduled_scrims(&context).await;
+        bot_service::resume_queue_expiry(&context).await;
+        let window_context = context.clone();
+        tokio::spawn(async move { bot_service::announce_queue_window(&window_context).await });
+        let status_context = context.clone();
+        tokio::spawn(async move { bot_service::keep_status_message_updated(&status_context).await });
+        let events_context = context.clone();
+        tokio::spawn(async move { bot_service::save_queue_events(&events_context).await });
         autoclear_queue(&context).await;
     }
 }
 
+static PERIODIC_TASKS_STARTED: AtomicBool = AtomicBool::new(false);
+
+struct RawHandler;
+
+#[async_trait]
+impl RawEventHandler for RawHandler {
+    async fn raw_event(&self, _context: Context, _event: Event) {
+        web::record_event();
+    }
+}
+
 #[tokio::main]
-async fn main() -> () {
-    let config = read_config().await.unwrap();
+async fn main() {
+    let config = match read_config().await {
+        Ok(config) => config,
+        Err(why) => {
+            eprintln!("{}", why);
+            return;
+        }
+    };
+    if config.discord.token.trim().is_empty() {
+        eprintln!("Config problem: discord.token is empty, add your discord bot api token to the config file");
+        return;
+    }
+    i18n::set_language(config.language.as_deref());
+    ops::set_channel(config.discord.ops_channel_id);
     let token = &config.discord.token;
+    let shard_count = config.discord.shard_count;
     let framework = StandardFramework::new();
-    let mut client = Client::builder(&token)
+    let mut intents = GatewayIntents::non_privileged();
+    if config.offline_grace_minutes.is_some() {
+        intents |= GatewayIntents::GUILD_PRESENCES;
+    }
+    let mut client = Client::builder(token)
+        .intents(intents)
         .event_handler(Handler {})
+        .raw_event_handler(RawHandler {})
         .framework(framework)
         .await
         .expect("Error creating client");
@@ -206,31 +1235,222 @@ async fn main() -> () {
         let mut data = client.data.write().await;
         data.insert::<UserQueue>(Vec::new());
         data.insert::<QueueMessages>(HashMap::new());
+        data.insert::<Parties>(Vec::new());
+        data.insert::<Waitlist>(Vec::new());
+        data.insert::<LastMatch>(read_last_match().await.unwrap());
+        data.insert::<Spectators>(Vec::new());
+        data.insert::<SubRequests>(HashMap::new());
+        data.insert::<PendingSwap>(None);
+        data.insert::<CommendsGiven>(HashMap::new());
+        data.insert::<QueueActivity>(None);
+        data.insert::<ActiveAfkCheck>(None);
+        data.insert::<SetupCancel>(CancellationToken::new());
+        data.insert::<ActiveVotes>(Vec::new());
+        data.insert::<OfflineSince>(HashMap::new());
+        data.insert::<QueueExpiry>(HashMap::new());
+        data.insert::<QueueJoinTimes>(HashMap::new());
+        data.insert::<LastQueuePing>(None);
+        data.insert::<QueueLock>(None);
+        data.insert::<DisabledConfigIds>(HashSet::new());
+        data.insert::<ChannelRenames>(HashMap::new());
+        data.insert::<OriginalChannelNames>(read_original_channel_names().await.unwrap());
+        data.insert::<PriorityPlayers>(Vec::new());
+        data.insert::<ActiveMatch>(None);
+        data.insert::<ScheduledScrims>(read_scheduled_scrims().await.unwrap());
+        data.insert::<NotifySubscriptions>(read_notify_subscriptions().await.unwrap());
+        data.insert::<CaptainPool>(read_captain_pool().await.unwrap());
+        let queue_events = read_queue_events().await.unwrap();
+        data.insert::<QueueSessionState>(bot_service::QueueSessions::replay(&queue_events));
+        data.insert::<QueueEvents>(queue_events);
+        data.insert::<QueueEventsUnsaved>(false);
+        data.insert::<QueueStatusMessage>(read_status_message().await.unwrap());
+        data.insert::<DashboardSessions>(HashMap::new());
+        data.insert::<PlayerStatsCache>(read_player_stats().await.unwrap());
+        data.insert::<MatchHistory>(read_match_history().await.unwrap());
+        data.insert::<CommandAliases>(read_aliases().await.unwrap());
         data.insert::<Config>(config);
         data.insert::<RiotIdCache>(read_riot_ids().await.unwrap());
+        data.insert::<RiotAccounts>(read_riot_accounts().await.unwrap());
+        data.insert::<RegionCache>(read_regions().await.unwrap());
         data.insert::<TeamNameCache>(read_teamnames().await.unwrap());
         data.insert::<BotState>(StateContainer { state: State::Queue });
         data.insert::<Maps>(read_maps().await.unwrap());
-        data.insert::<Draft>(Draft {
-            captain_a: None,
-            captain_b: None,
-            current_picker: None,
-            team_a: Vec::new(),
-            team_b: Vec::new(),
-            team_b_start_side: String::from(""),
-        });
-    }
-    if let Err(why) = client.start().await {
+        data.insert::<MapPools>(read_map_pools().await.unwrap());
+        data.insert::<MapImages>(read_map_images().await.unwrap());
+        data.insert::<CurrentMatch>(Match::default());
+        if let Some(session) = read_session().await {
+            restore_session(&mut data, session);
+        }
+    }
+    if let Some(port) = client.data.read().await.get::<Config>().unwrap().http_port {
+        tokio::spawn(web::serve(port, client.shard_manager.clone(), client.data.clone()));
+    }
+    let shutdown_data = client.data.clone();
+    let shutdown_http = client.cache_and_http.http.clone();
+    let shard_manager = client.shard_manager.clone();
+    tokio::spawn(async move {
+        wait_for_shutdown_signal().await;
+        println!("Shutting down, saving state...");
+        bot_service::shutdown(&shutdown_http, &shutdown_data).await;
+        shard_manager.lock().await.shutdown_all().await;
+    });
+    let started = match shard_count {
+        Some(0) => client.start_autosharded().await,
+        Some(shard_count) => client.start_shards(shard_count).await,
+        None => client.start().await,
+    };
+    if let Err(why) = started {
         println!("Client error: {:?}", why);
     }
 }
 
-async fn read_config() -> Result<Config, serde_yaml::Error> {
-    let yaml = std::fs::read_to_string("config.yaml").unwrap();
-    let config: Config = serde_yaml::from_str(&yaml)?;
+const CONFIG_FILES: [&str; 3] = ["config.yaml", "config.toml", "config.json"];
+
+fn config_file() -> Option<&'static str> {
+    CONFIG_FILES.iter().copied().find(|file| std::path::Path::new(file).exists())
+}
+
+/// The config file with the `.setconfig` overrides applied
+async fn read_config() -> Result<Config, String> {
+    let mut value = serde_json::to_value(read_config_file()?).unwrap();
+    for (key, override_value) in read_config_overrides() {
+        set_config_value(&mut value, &key, override_value);
+    }
+    let mut config: Config = serde_json::from_value(value).map_err(|why| format!("Error applying {}: {}", CONFIG_OVERRIDES, why))?;
+    lowercase_aliases(&mut config);
     Ok(config)
 }
 
+/// The config as written in the config file
+fn read_config_file() -> Result<Config, String> {
+    let file = config_file()
+        .ok_or_else(|| format!("No config file found, searched for: {}", CONFIG_FILES.join(", ")))?;
+    let content = std::fs::read_to_string(file).map_err(|why| format!("Error reading {}: {}", file, why))?;
+    let config: Result<Config, String> = match file {
+        "config.toml" => toml::from_str(&content).map_err(|why| why.to_string()),
+        "config.json" => serde_json::from_str(&content).map_err(|why| why.to_string()),
+        _ => serde_yaml::from_str(&content).map_err(|why| why.to_string()),
+    };
+    config.map_err(|why| format!("Error parsing {}: {}", file, why))
+}
+
+/// Commands are matched in lowercase, so are the alias names
+fn lowercase_aliases(config: &mut Config) {
+    config.aliases = config.aliases.take().map(|aliases| aliases.into_iter().map(|(alias, target)| (alias.to_lowercase(), target)).collect());
+}
+
+/// Options only the config file can set, `.setconfig` refuses them and `.showconfig` hides them
+const SECRET_CONFIG_KEYS: [&str; 3] = ["discord.token", "api_key", "dashboard_token"];
+
+/// Options changed with `.setconfig` by their dotted key, applied over the config file so the file and its comments are never rewritten
+const CONFIG_OVERRIDES: &str = "config_overrides.json";
+
+fn read_config_overrides() -> HashMap<String, serde_json::Value> {
+    if std::fs::read(CONFIG_OVERRIDES).is_ok() {
+        let json_str = std::fs::read_to_string(CONFIG_OVERRIDES).unwrap();
+        serde_json::from_str(&json_str).unwrap()
+    } else {
+        HashMap::new()
+    }
+}
+
+/// Replaces the option at a dotted key like `discord.queue_channel_id`, returns false if there is no such option
+fn set_config_value(config: &mut serde_json::Value, key: &str, value: serde_json::Value) -> bool {
+    let mut target = config;
+    for part in key.split('.') {
+        target = match target.get_mut(part) {
+            Some(target) => target,
+            None => return false,
+        };
+    }
+    *target = value;
+    true
+}
+
+#[cfg(unix)]
+async fn wait_for_shutdown_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut sigterm = signal(SignalKind::terminate()).expect("Cannot listen for SIGTERM");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = sigterm.recv() => {}
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_shutdown_signal() {
+    tokio::signal::ctrl_c().await.expect("Cannot listen for Ctrl-C");
+}
+
+async fn read_session() -> Option<Session> {
+    let json_str = std::fs::read_to_string("session.json").ok()?;
+    // only restore once, a crash later on must not bring back this snapshot
+    if let Err(why) = std::fs::remove_file("session.json") {
+        eprintln!("Cannot remove session.json: {:?}", why);
+    }
+    match serde_json::from_str(&json_str) {
+        Ok(session) => Some(session),
+        Err(why) => {
+            eprintln!("Cannot restore session.json, starting with an empty queue: {:?}", why);
+            None
+        }
+    }
+}
+
+fn restore_session(data: &mut TypeMap, session: Session) {
+    // a map vote can't be resumed, fall back to the full queue so `.start` can be run again
+    let state = if session.state == State::MapPick { State::Queue } else { session.state };
+    let mut current_match = session.current_match;
+    if state.is_captain_pick() {
+        // captains claimed so far are kept in the state, picks of a draft that can't be resumed are dropped
+        let draft = &mut current_match.draft;
+        draft.captain_a = None;
+        draft.captain_b = None;
+        draft.team_a = Vec::new();
+        draft.team_b = Vec::new();
+        draft.skipped_turns = Vec::new();
+    }
+    println!("Restored {} queued player(s) in the {} state", session.queue.len(), state);
+    data.insert::<BotState>(StateContainer { state });
+    data.insert::<UserQueue>(session.queue);
+    data.insert::<QueueMessages>(session.queue_messages);
+    data.insert::<Parties>(session.parties);
+    data.insert::<Waitlist>(session.waitlist);
+    data.insert::<Spectators>(session.spectators);
+    data.insert::<PriorityPlayers>(session.priority_players);
+    data.insert::<CurrentMatch>(current_match);
+    data.insert::<LastMatch>(session.last_match);
+    data.insert::<ActiveMatch>(session.active_match);
+    let from_timestamps = |times: HashMap<u64, i64>| -> HashMap<u64, DateTime<Local>> {
+        times.into_iter().filter_map(|(user_id, timestamp)| Some((user_id, Local.timestamp_opt(timestamp, 0).single()?))).collect()
+    };
+    data.insert::<QueueJoinTimes>(from_timestamps(session.queue_join_times));
+    data.insert::<QueueExpiry>(from_timestamps(session.queue_expiry));
+    if let Some((channel_id, guild_id)) = session.queue_channel {
+        data.insert::<QueueActivity>(Some(QueueActivityInfo { channel_id, guild_id, updated: Local::now() }));
+    }
+}
+
+async fn read_status_message() -> Result<Option<StatusMessage>, serde_json::Error> {
+    if std::fs::read("status_message.json").is_ok() {
+        let json_str = std::fs::read_to_string("status_message.json").unwrap();
+        let json = serde_json::from_str(&json_str).unwrap();
+        Ok(json)
+    } else {
+        Ok(None)
+    }
+}
+
+async fn read_last_match() -> Result<Option<Match>, serde_json::Error> {
+    if std::fs::read("last_match.json").is_ok() {
+        let json_str = std::fs::read_to_string("last_match.json").unwrap();
+        let json = serde_json::from_str(&json_str).unwrap();
+        Ok(json)
+    } else {
+        Ok(None)
+    }
+}
+
 async fn read_riot_ids() -> Result<HashMap<u64, String>, serde_json::Error> {
     if std::fs::read("riot_ids.json").is_ok() {
         let json_str = std::fs::read_to_string("riot_ids.json").unwrap();
@@ -241,7 +1461,37 @@ async fn read_riot_ids() -> Result<HashMap<u64, String>, serde_json::Error> {
     }
 }
 
-async fn read_teamnames() -> Result<HashMap<u64, String>, serde_json::Error> {
+async fn read_original_channel_names() -> Result<HashMap<u64, String>, serde_json::Error> {
+    if std::fs::read("original_channel_names.json").is_ok() {
+        let json_str = std::fs::read_to_string("original_channel_names.json").unwrap();
+        let json = serde_json::from_str(&json_str).unwrap();
+        Ok(json)
+    } else {
+        Ok(HashMap::new())
+    }
+}
+
+async fn read_riot_accounts() -> Result<HashMap<u64, Vec<String>>, serde_json::Error> {
+    if std::fs::read("riot_accounts.json").is_ok() {
+        let json_str = std::fs::read_to_string("riot_accounts.json").unwrap();
+        let json = serde_json::from_str(&json_str).unwrap();
+        Ok(json)
+    } else {
+        Ok(HashMap::new())
+    }
+}
+
+async fn read_regions() -> Result<HashMap<u64, String>, serde_json::Error> {
+    if std::fs::read("regions.json").is_ok() {
+        let json_str = std::fs::read_to_string("regions.json").unwrap();
+        let json = serde_json::from_str(&json_str).unwrap();
+        Ok(json)
+    } else {
+        Ok(HashMap::new())
+    }
+}
+
+async fn read_teamnames() -> Result<HashMap<u64, TeamProfile>, serde_json::Error> {
     if std::fs::read("teamnames.json").is_ok() {
         let json_str = std::fs::read_to_string("teamnames.json").unwrap();
         let json = serde_json::from_str(&json_str).unwrap();
@@ -261,30 +1511,210 @@ async fn read_maps() -> Result<Vec<String>, serde_json::Error> {
     }
 }
 
+/// Built-in `.mappool` presets, a pool saved under the same name in map_pools.json replaces them
+const COMPETITIVE_MAPS: [&str; 7] = ["Abyss", "Ascent", "Bind", "Corrode", "Haven", "Lotus", "Sunset"];
+const ALL_MAPS: [&str; 12] = ["Abyss", "Ascent", "Bind", "Breeze", "Corrode", "Fracture", "Haven", "Icebox", "Lotus", "Pearl", "Split", "Sunset"];
+
+async fn read_map_pools() -> Result<HashMap<String, Vec<String>>, serde_json::Error> {
+    let mut map_pools: HashMap<String, Vec<String>> = if std::fs::read("map_pools.json").is_ok() {
+        let json_str = std::fs::read_to_string("map_pools.json").unwrap();
+        serde_json::from_str(&json_str).unwrap()
+    } else {
+        HashMap::new()
+    };
+    for (name, maps) in [("competitive", &COMPETITIVE_MAPS[..]), ("all", &ALL_MAPS[..])] {
+        map_pools.entry(String::from(name)).or_insert_with(|| maps.iter().map(|map| String::from(*map)).collect());
+    }
+    Ok(map_pools)
+}
+
+async fn read_map_images() -> Result<HashMap<String, String>, serde_json::Error> {
+    if std::fs::read("map_images.json").is_ok() {
+        let json_str = std::fs::read_to_string("map_images.json").unwrap();
+        let json = serde_json::from_str(&json_str).unwrap();
+        Ok(json)
+    } else {
+        Ok(HashMap::new())
+    }
+}
+
+async fn read_notify_subscriptions() -> Result<HashMap<u64, usize>, serde_json::Error> {
+    if std::fs::read("notify_subscriptions.json").is_ok() {
+        let json_str = std::fs::read_to_string("notify_subscriptions.json").unwrap();
+        let json = serde_json::from_str(&json_str).unwrap();
+        Ok(json)
+    } else {
+        Ok(HashMap::new())
+    }
+}
+
+async fn read_captain_pool() -> Result<Vec<u64>, serde_json::Error> {
+    if std::fs::read("captain_pool.json").is_ok() {
+        let json_str = std::fs::read_to_string("captain_pool.json").unwrap();
+        let json = serde_json::from_str(&json_str).unwrap();
+        Ok(json)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+async fn read_queue_events() -> Result<Vec<QueueEvent>, serde_json::Error> {
+    if std::fs::read("queue_events.json").is_ok() {
+        let json_str = std::fs::read_to_string("queue_events.json").unwrap();
+        let json = serde_json::from_str(&json_str).unwrap();
+        Ok(json)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+async fn read_player_stats() -> Result<HashMap<u64, PlayerStats>, serde_json::Error> {
+    if std::fs::read("player_stats.json").is_ok() {
+        let json_str = std::fs::read_to_string("player_stats.json").unwrap();
+        let json = serde_json::from_str(&json_str).unwrap();
+        Ok(json)
+    } else {
+        Ok(HashMap::new())
+    }
+}
+
+async fn read_match_history() -> Result<Vec<Match>, serde_json::Error> {
+    if std::fs::read("match_history.json").is_ok() {
+        let json_str = std::fs::read_to_string("match_history.json").unwrap();
+        let json = serde_json::from_str(&json_str).unwrap();
+        Ok(json)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+async fn read_scheduled_scrims() -> Result<Vec<ScheduledScrim>, serde_json::Error> {
+    if std::fs::read("scheduled_scrims.json").is_ok() {
+        let json_str = std::fs::read_to_string("scheduled_scrims.json").unwrap();
+        let json = serde_json::from_str(&json_str).unwrap();
+        Ok(json)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+async fn read_aliases() -> Result<HashMap<String, String>, serde_json::Error> {
+    if std::fs::read("aliases.json").is_ok() {
+        let json_str = std::fs::read_to_string("aliases.json").unwrap();
+        let json = serde_json::from_str(&json_str).unwrap();
+        Ok(json)
+    } else {
+        Ok(HashMap::new())
+    }
+}
+
 async fn autoclear_queue(context: &Context) {
-    let autoclear_hour_prop = get_autoclear_hour(context).await;
-    if let Some(autoclear_hour) = autoclear_hour_prop {
-        println!("Autoclear feature started");
-        loop {
-            let current: DateTime<Local> = Local::now();
-            let mut autoclear: DateTime<Local> = Local.ymd(current.year(), current.month(), current.day())
-                .and_hms(autoclear_hour, 0, 0);
-            if autoclear.signed_duration_since(current).num_milliseconds() < 0 { autoclear = autoclear + ChronoDuration::days(1) }
-            let time_between: ChronoDuration = autoclear.signed_duration_since(current);
-            task::sleep(CoreDuration::from_millis(time_between.num_milliseconds() as u64)).await;
-            {
-                let mut data = context.data.write().await;
-                let user_queue: &mut Vec<User> = &mut data.get_mut::<UserQueue>().unwrap();
-                user_queue.clear();
-                let queued_msgs: &mut HashMap<u64, String> = data.get_mut::<QueueMessages>().unwrap();
-                queued_msgs.clear();
+    println!("Autoclear feature started");
+    // minutes up to here have been checked, starting during the autoclear minute doesn't clear right away
+    let mut checked_until: DateTime<Local> = Local::now();
+    loop {
+        // wake up at the start of every minute and re-read the schedule so .setconfig changes apply without a restart
+        task::sleep(CoreDuration::from_secs(60 - Local::now().second() as u64)).await;
+        let current: DateTime<Local> = Local::now();
+        let last_checked = std::mem::replace(&mut checked_until, current);
+        let (warning, clear_due) = {
+            let data = context.data.read().await;
+            let config: &Config = data.get::<Config>().unwrap();
+            let warning_minutes = config.autoclear_warning_minutes.unwrap_or(15);
+            let queue_len = data.get::<UserQueue>().unwrap().len();
+            let in_setup = data.get::<BotState>().unwrap().state != State::Queue;
+            let warning = if warning_minutes > 0 && queue_len > 0 && !in_setup && autoclear_due(config, &(current + ChronoDuration::minutes(warning_minutes))) {
+                bot_service::queue_channel(&data)
+                    .map(|channel_id| (channel_id, format!("The queue clears in {} minutes, {} player(s) will be removed.", warning_minutes, queue_len)))
+            } else {
+                None
+            };
+            (warning, autoclear_due_between(config, &last_checked, &current))
+        };
+        if let Some((channel_id, response)) = warning {
+            delivery::say(&context.http, channel_id, &response).await;
+        }
+        if !clear_due { continue; }
+        {
+            let outbox = Outbox::default();
+            let mut data = context.data.write().await;
+            // never wipe a match that is mid-setup, the next scheduled autoclear picks it up
+            if data.get::<BotState>().unwrap().state != State::Queue {
+                println!("Skipping autoclear, a match setup is in progress");
+                continue;
             }
+            let removed = data.get::<UserQueue>().unwrap().len() + data.get::<Waitlist>().unwrap().len();
+            if !data.get::<UserQueue>().unwrap().is_empty() {
+                bot_service::record_queue_event(&mut data, QueueEventKind::Clear, None);
+            }
+            let user_queue: &mut Vec<User> = data.get_mut::<UserQueue>().unwrap();
+            user_queue.clear();
+            let queued_msgs: &mut HashMap<u64, String> = data.get_mut::<QueueMessages>().unwrap();
+            queued_msgs.clear();
+            let parties: &mut Vec<Vec<User>> = data.get_mut::<Parties>().unwrap();
+            parties.clear();
+            let waitlist: &mut Vec<User> = data.get_mut::<Waitlist>().unwrap();
+            waitlist.clear();
+            let queue_expiry: &mut HashMap<u64, DateTime<Local>> = data.get_mut::<QueueExpiry>().unwrap();
+            queue_expiry.clear();
+            let queue_join_times: &mut HashMap<u64, DateTime<Local>> = data.get_mut::<QueueJoinTimes>().unwrap();
+            queue_join_times.clear();
+            let spectators: &mut Vec<User> = data.get_mut::<Spectators>().unwrap();
+            spectators.clear();
+            let afk_check: &mut Option<AfkCheck> = data.get_mut::<ActiveAfkCheck>().unwrap();
+            *afk_check = None;
+            let draft: &mut Draft = &mut data.get_mut::<CurrentMatch>().unwrap().draft;
+            draft.team_a = vec![];
+            draft.team_b = vec![];
+            draft.captain_a = None;
+            draft.captain_b = None;
+            draft.coach_a = None;
+            draft.coach_b = None;
+            draft.map = None;
+            let bot_state: &mut StateContainer = data.get_mut::<BotState>().unwrap();
+            bot_state.state = State::Queue;
+            if removed > 0 {
+                bot_service::audit_log(&outbox, &data, "Autoclear", format!("The queue was cleared, {} player(s) removed", removed)).await;
+                if let Some(channel_id) = bot_service::queue_channel(&data) {
+                    outbox.say(channel_id, format!("The queue has been cleared, {} player(s) removed.", removed)).await;
+                }
+            }
+            drop(data);
+            outbox.send(context).await;
+        }
+        // make sure a slow clear doesn't run twice in the same minute
+        task::sleep(CoreDuration::from_secs(1)).await;
+    }
+}
+
+/// Whether an autoclear is due in any minute after `from` up to `to`, so a late wake up doesn't skip a clear and a
+/// minute is never cleared twice
+fn autoclear_due_between(config: &Config, from: &DateTime<Local>, to: &DateTime<Local>) -> bool {
+    // after a long suspend only the last day is caught up on
+    let from = (*from).max(*to - ChronoDuration::days(1));
+    let mut minute = from.with_second(0).and_then(|from| from.with_nanosecond(0)).unwrap() + ChronoDuration::minutes(1);
+    while minute <= *to {
+        if autoclear_due(config, &minute) {
+            return true;
         }
+        minute += ChronoDuration::minutes(1);
     }
+    false
 }
 
-async fn get_autoclear_hour(client: &Context) -> Option<u32> {
-    let data = client.data.write().await;
-    let config: &Config = &data.get::<Config>().unwrap();
-    config.autoclear_hour
+/// Checks autoclear_hour and the autoclear_schedule cron expressions against the current minute,
+/// dates listed in autoclear_skip_dates are never cleared
+fn autoclear_due(config: &Config, current: &DateTime<Local>) -> bool {
+    let today = current.format("%Y-%m-%d").to_string();
+    if config.autoclear_skip_dates.as_ref().map(|dates| dates.contains(&today)).unwrap_or(false) {
+        return false;
+    }
+    if config.autoclear_hour == Some(current.hour()) && current.minute() == 0 {
+        return true;
+    }
+    config.autoclear_schedule.iter()
+        .flatten()
+        .filter_map(|expression| cron::Schedule::parse(expression).ok())
+        .any(|schedule| schedule.matches(current))
 }
+
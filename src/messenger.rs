@@ -0,0 +1,234 @@
+use std::sync::Mutex;
+
+use serenity::async_trait;
+use serenity::builder::CreateEmbed;
+use serenity::client::Context;
+use serenity::model::channel::ReactionType;
+use serenity::model::guild::GuildContainer;
+use serenity::model::id::{ChannelId, GuildId, MessageId, RoleId, UserId};
+use serenity::model::user::User;
+
+use crate::{delivery, ops};
+
+/// The messages the setup logic sends. `Context` sends them to Discord, the tests record them instead
+#[async_trait]
+pub(crate) trait Messenger: Send + Sync {
+    async fn say(&self, channel_id: ChannelId, content: String) -> Option<MessageId>;
+
+    /// Failures are reported by the implementation, callers have nothing to handle
+    async fn react(&self, channel_id: ChannelId, message_id: MessageId, reactions: Vec<ReactionType>);
+
+    async fn send_embed(&self, channel_id: ChannelId, embed: CreateEmbed) -> Option<MessageId>;
+
+    /// Returns false if the message couldn't be edited, i.e. because it was deleted
+    async fn edit_embed(&self, channel_id: ChannelId, message_id: MessageId, embed: CreateEmbed) -> bool;
+}
+
+/// Role lookups for permission checks
+#[async_trait]
+pub(crate) trait Guild: Send + Sync {
+    async fn has_role(&self, guild_id: GuildId, user: &User, role_id: u64) -> bool;
+}
+
+/// Everything the setup logic needs from Discord
+pub(crate) trait Discord: Messenger + Guild {}
+
+impl<T: Messenger + Guild> Discord for T {}
+
+#[async_trait]
+impl Messenger for Context {
+    async fn say(&self, channel_id: ChannelId, content: String) -> Option<MessageId> {
+        delivery::say(&self.http, channel_id, content).await.map(|message| message.id)
+    }
+
+    async fn react(&self, channel_id: ChannelId, message_id: MessageId, reactions: Vec<ReactionType>) {
+        if let Err(why) = delivery::react_all(&self.http, channel_id, message_id, reactions).await {
+            ops::report(self, format!("Cannot add reaction: {:?}", why)).await;
+        }
+    }
+
+    async fn send_embed(&self, channel_id: ChannelId, embed: CreateEmbed) -> Option<MessageId> {
+        match channel_id.send_message(&self.http, |m| m.set_embed(embed)).await {
+            Ok(message) => Some(message.id),
+            Err(why) => {
+                ops::report(self, format!("Cannot send embed to {}: {:?}", channel_id, why)).await;
+                None
+            }
+        }
+    }
+
+    async fn edit_embed(&self, channel_id: ChannelId, message_id: MessageId, embed: CreateEmbed) -> bool {
+        channel_id.edit_message(&self.http, message_id, |m| m.set_embed(embed)).await.is_ok()
+    }
+}
+
+#[async_trait]
+impl Guild for Context {
+    async fn has_role(&self, guild_id: GuildId, user: &User, role_id: u64) -> bool {
+        user.has_role(&self.http, GuildContainer::from(guild_id), role_id).await.unwrap_or(false)
+    }
+}
+
+/// Discord work collected while the bot data is locked, `send` runs it in order once the lock is dropped. Messages
+/// queued here have no id yet, so anything that needs its message back (prompts, the draft embed) can't use it
+#[derive(Default)]
+pub(crate) struct Outbox {
+    pending: Mutex<Vec<Pending>>,
+}
+
+enum Pending {
+    Say(ChannelId, String),
+    React(ChannelId, MessageId, Vec<ReactionType>),
+    Embed(ChannelId, CreateEmbed),
+    EditEmbed(ChannelId, MessageId, CreateEmbed),
+    Dm(UserId, String),
+    AddRole(GuildId, UserId, RoleId),
+    Move(GuildId, UserId, ChannelId),
+}
+
+impl Outbox {
+    fn push(&self, pending: Pending) {
+        self.pending.lock().unwrap().push(pending);
+    }
+
+    pub(crate) fn dm(&self, user_id: UserId, content: String) {
+        self.push(Pending::Dm(user_id, content));
+    }
+
+    /// Gives the member the role, members who have it already are left alone
+    pub(crate) fn add_role(&self, guild_id: GuildId, user_id: UserId, role_id: RoleId) {
+        self.push(Pending::AddRole(guild_id, user_id, role_id));
+    }
+
+    /// Moves the member to a voice channel, members who aren't connected to voice stay where they are
+    pub(crate) fn move_member(&self, guild_id: GuildId, user_id: UserId, channel_id: ChannelId) {
+        self.push(Pending::Move(guild_id, user_id, channel_id));
+    }
+
+    pub(crate) async fn send(self, context: &Context) {
+        for pending in self.pending.into_inner().unwrap() {
+            match pending {
+                Pending::Say(channel_id, content) => {
+                    context.say(channel_id, content).await;
+                }
+                Pending::React(channel_id, message_id, reactions) => {
+                    context.react(channel_id, message_id, reactions).await;
+                }
+                Pending::Embed(channel_id, embed) => {
+                    context.send_embed(channel_id, embed).await;
+                }
+                Pending::EditEmbed(channel_id, message_id, embed) => {
+                    if !context.edit_embed(channel_id, message_id, embed).await {
+                        ops::report(context, format!("Cannot edit message {} in {}", message_id, channel_id)).await;
+                    }
+                }
+                Pending::Dm(user_id, content) => match user_id.create_dm_channel(&context.http).await {
+                    Ok(channel) => {
+                        delivery::say(&context.http, channel.id, content).await;
+                    }
+                    Err(why) => ops::report(context, format!("Cannot open a dm with {}: {:?}", user_id, why)).await,
+                },
+                Pending::AddRole(guild_id, user_id, role_id) => match guild_id.member(&context.http, user_id).await {
+                    Ok(mut member) if !member.roles.contains(&role_id) => {
+                        if let Err(why) = member.add_role(&context.http, role_id).await {
+                            ops::report(context, format!("Cannot add role {} to {}, check bot permissions: {:?}", role_id, user_id, why)).await;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(why) => ops::report(context, format!("Cannot fetch member {}: {:?}", user_id, why)).await,
+                },
+                Pending::Move(guild_id, user_id, channel_id) => {
+                    if let Err(why) = guild_id.move_member(&context.http, user_id, channel_id).await {
+                        ops::report(context, format!("Cannot move user: {:?}", why)).await;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Queues instead of sending, see `Outbox`. Queued messages return no id and edits count as done
+#[async_trait]
+impl Messenger for Outbox {
+    async fn say(&self, channel_id: ChannelId, content: String) -> Option<MessageId> {
+        self.push(Pending::Say(channel_id, content));
+        None
+    }
+
+    async fn react(&self, channel_id: ChannelId, message_id: MessageId, reactions: Vec<ReactionType>) {
+        self.push(Pending::React(channel_id, message_id, reactions));
+    }
+
+    async fn send_embed(&self, channel_id: ChannelId, embed: CreateEmbed) -> Option<MessageId> {
+        self.push(Pending::Embed(channel_id, embed));
+        None
+    }
+
+    async fn edit_embed(&self, channel_id: ChannelId, message_id: MessageId, embed: CreateEmbed) -> bool {
+        self.push(Pending::EditEmbed(channel_id, message_id, embed));
+        true
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// Records what would have been sent to Discord. Roles are handed out with `grant`
+    #[derive(Default)]
+    pub(crate) struct MockDiscord {
+        pub(crate) messages: Mutex<Vec<(ChannelId, String)>>,
+        pub(crate) embeds: Mutex<Vec<(ChannelId, CreateEmbed)>>,
+        pub(crate) reactions: Mutex<Vec<(MessageId, ReactionType)>>,
+        roles: Mutex<HashMap<u64, Vec<u64>>>,
+        next_message_id: Mutex<u64>,
+    }
+
+    impl MockDiscord {
+        pub(crate) fn grant(&self, user: &User, role_id: u64) {
+            self.roles.lock().unwrap().entry(*user.id.as_u64()).or_default().push(role_id);
+        }
+
+        /// The sent messages in order, without their channel
+        pub(crate) fn said(&self) -> Vec<String> {
+            self.messages.lock().unwrap().iter().map(|(_, content)| content.clone()).collect()
+        }
+
+        fn message_id(&self) -> MessageId {
+            let mut next_message_id = self.next_message_id.lock().unwrap();
+            *next_message_id += 1;
+            MessageId(*next_message_id)
+        }
+    }
+
+    #[async_trait]
+    impl Messenger for MockDiscord {
+        async fn say(&self, channel_id: ChannelId, content: String) -> Option<MessageId> {
+            self.messages.lock().unwrap().push((channel_id, content));
+            Some(self.message_id())
+        }
+
+        async fn react(&self, _channel_id: ChannelId, message_id: MessageId, reactions: Vec<ReactionType>) {
+            self.reactions.lock().unwrap().extend(reactions.into_iter().map(|reaction| (message_id, reaction)));
+        }
+
+        async fn send_embed(&self, channel_id: ChannelId, embed: CreateEmbed) -> Option<MessageId> {
+            self.embeds.lock().unwrap().push((channel_id, embed));
+            Some(self.message_id())
+        }
+
+        async fn edit_embed(&self, channel_id: ChannelId, _message_id: MessageId, embed: CreateEmbed) -> bool {
+            self.embeds.lock().unwrap().push((channel_id, embed));
+            true
+        }
+    }
+
+    #[async_trait]
+    impl Guild for MockDiscord {
+        async fn has_role(&self, _guild_id: GuildId, user: &User, role_id: u64) -> bool {
+            self.roles.lock().unwrap().get(user.id.as_u64()).is_some_and(|roles| roles.contains(&role_id))
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+use chrono::Local;
+use serenity::client::Context;
+use serenity::model::id::ChannelId;
+
+use crate::delivery;
+
+static OPS_CHANNEL: AtomicU64 = AtomicU64::new(0);
+static LAST_REPORT: AtomicI64 = AtomicI64::new(0);
+static SUPPRESSED: AtomicU64 = AtomicU64::new(0);
+
+/// Minimum seconds between two reports in the ops channel, errors in between are counted and mentioned in the next report
+const REPORT_INTERVAL_SECONDS: i64 = 30;
+
+/// Sets the channel `report` posts to, `None` only logs errors to the console
+pub(crate) fn set_channel(channel_id: Option<u64>) {
+    OPS_CHANNEL.store(channel_id.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// Logs an error and posts it to the ops channel if one is configured. Kept out of the TypeMap so it can be
+/// called while the data lock is held
+pub(crate) async fn report(context: &Context, error: String) {
+    eprintln!("{}", error);
+    let channel_id = OPS_CHANNEL.load(Ordering::Relaxed);
+    if channel_id == 0 { return; }
+    let now = Local::now().timestamp();
+    let last_report = LAST_REPORT.load(Ordering::Relaxed);
+    if now - last_report < REPORT_INTERVAL_SECONDS
+        || LAST_REPORT.compare_exchange(last_report, now, Ordering::Relaxed, Ordering::Relaxed).is_err() {
+        SUPPRESSED.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+    let mut summary = format!("⚠️ {}", error.chars().take(1800).collect::<String>());
+    let suppressed = SUPPRESSED.swap(0, Ordering::Relaxed);
+    if suppressed > 0 {
+        summary.push_str(&format!("\n({} more error(s) since the last report, see the bot logs)", suppressed));
+    }
+    delivery::say(&context.http, ChannelId(channel_id), summary).await;
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::RwLock as StdRwLock;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::Duration;
+
+use chrono::{Local, TimeZone};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use hyper::service::{make_service_fn, service_fn};
+use serde_json::{json, Value};
+use serenity::client::Context;
+use serenity::client::bridge::gateway::ShardManager;
+use serenity::gateway::ConnectionStage;
+use serenity::model::user::User;
+use serenity::prelude::{Mutex, RwLock, TypeMap};
+
+use crate::bot_service::{setup_captains, start_sides, team_name};
+use crate::dashboard;
+use crate::{ActiveMatch, BotState, Config, CurrentMatch, LastMatch, Match, MatchHistory, QueueLock, QueueMessages, RegionCache, RiotIdCache, State, TeamNameCache, UserQueue, Waitlist};
+
+/// What every request handler gets access to
+struct Shared {
+    shard_manager: Arc<Mutex<ShardManager>>,
+    data: Arc<RwLock<TypeMap>>,
+}
+
+// unix timestamp of the last gateway event, 0 until the first one arrives
+static LAST_EVENT: AtomicI64 = AtomicI64::new(0);
+
+// set on ready, lets the dashboard post to Discord
+static CONTEXT: StdRwLock<Option<Context>> = StdRwLock::new(None);
+
+pub(crate) fn record_event() {
+    LAST_EVENT.store(Local::now().timestamp(), Ordering::Relaxed);
+}
+
+pub(crate) fn set_context(context: &Context) {
+    *CONTEXT.write().unwrap() = Some(context.clone());
+}
+
+pub(crate) fn context() -> Option<Context> {
+    CONTEXT.read().unwrap().clone()
+}
+
+pub(crate) async fn serve(port: u16, shard_manager: Arc<Mutex<ShardManager>>, data: Arc<RwLock<TypeMap>>) {
+    let shared = Arc::new(Shared { shard_manager, data });
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let server = match Server::try_bind(&addr) {
+        Ok(server) => server,
+        Err(why) => {
+            eprintln!("Cannot start the http server on port {}: {:?}", port, why);
+            return;
+        }
+    };
+    let make_service = make_service_fn(move |_| {
+        let shared = shared.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| route(request, shared.clone())))
+        }
+    });
+    println!("Http server listening on {}", addr);
+    if let Err(why) = server.serve(make_service).await {
+        eprintln!("Http server error: {:?}", why);
+    }
+}
+
+async fn route(request: Request<Body>, shared: Arc<Shared>) -> Result<Response<Body>, Infallible> {
+    if request.uri().path().starts_with("/dashboard") {
+        return Ok(dashboard::route(request, shared.data.clone()).await);
+    }
+    if request.method() != Method::GET {
+        return Ok(json_response(StatusCode::METHOD_NOT_ALLOWED, json!({ "error": "method not allowed" })));
+    }
+    let path = request.uri().path();
+    if path == "/healthz" {
+        return Ok(healthz(&shared).await);
+    }
+    if !["/queue", "/current-match", "/history"].contains(&path) {
+        return Ok(json_response(StatusCode::NOT_FOUND, json!({ "error": "not found" })));
+    }
+    // some commands still post their reply while holding the bot data, don't keep api clients waiting on a slow one
+    let data = match tokio::time::timeout(Duration::from_secs(5), shared.data.read()).await {
+        Ok(data) => data,
+        Err(_) => return Ok(json_response(StatusCode::SERVICE_UNAVAILABLE, json!({ "error": "busy, try again shortly" }))),
+    };
+    let api_key = match &data.get::<Config>().unwrap().api_key {
+        Some(api_key) if !api_key.is_empty() => api_key,
+        _ => return Ok(json_response(StatusCode::NOT_FOUND, json!({ "error": "the api is disabled, set api_key in the config to enable it" }))),
+    };
+    if request_api_key(&request) != Some(api_key.as_str()) {
+        return Ok(json_response(StatusCode::UNAUTHORIZED, json!({ "error": "missing or invalid api key" })));
+    }
+    let body = match path {
+        "/queue" => queue(&data),
+        "/current-match" => current_match(&data),
+        _ => history(&data, query_param(&request, "limit").and_then(|limit| limit.parse().ok()).unwrap_or(20)),
+    };
+    Ok(json_response(StatusCode::OK, body))
+}
+
+/// Accepts either `Authorization: Bearer <key>` or `X-Api-Key: <key>`
+fn request_api_key(request: &Request<Body>) -> Option<&str> {
+    let headers = request.headers();
+    if let Some(key) = headers.get("x-api-key").and_then(|value| value.to_str().ok()) {
+        return Some(key);
+    }
+    headers.get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+fn query_param<'a>(request: &'a Request<Body>, name: &str) -> Option<&'a str> {
+    request.uri().query()?
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+pub(crate) fn player(user: &User, data: &TypeMap) -> Value {
+    json!({
+        "id": user.id.to_string(),
+        "name": user.name,
+        "riot_id": data.get::<RiotIdCache>().unwrap().get(user.id.as_u64()),
+        "region": data.get::<RegionCache>().unwrap().get(user.id.as_u64()),
+    })
+}
+
+fn queue(data: &TypeMap) -> Value {
+    let queue_msgs: &HashMap<u64, String> = data.get::<QueueMessages>().unwrap();
+    let players: Vec<Value> = data.get::<UserQueue>().unwrap()
+        .iter()
+        .map(|user| {
+            let mut player = player(user, data);
+            player["note"] = json!(queue_msgs.get(user.id.as_u64()));
+            player
+        })
+        .collect();
+    let waitlist: Vec<Value> = data.get::<Waitlist>().unwrap().iter().map(|user| player(user, data)).collect();
+    json!({
+        "state": data.get::<BotState>().unwrap().state.to_string(),
+        "size": players.len(),
+        "players": players,
+        "waitlist": waitlist,
+        "locked": data.get::<QueueLock>().unwrap(),
+    })
+}
+
+fn team(data: &TypeMap, captain: &Option<User>, players: &[User], coach: &Option<User>, side: Option<String>) -> Value {
+    let profile = captain.as_ref().and_then(|captain| data.get::<TeamNameCache>().unwrap().get(captain.id.as_u64()));
+    json!({
+        "name": captain.as_ref().map(|captain| team_name(data, captain)),
+        "emoji": profile.and_then(|profile| profile.emoji.as_ref()),
+        "color": profile.and_then(|profile| profile.color).map(|color| format!("#{:06x}", color)),
+        "captain": captain.as_ref().map(|captain| player(captain, data)),
+        "players": players.iter().map(|user| player(user, data)).collect::<Vec<Value>>(),
+        "coach": coach.as_ref().map(|coach| player(coach, data)),
+        "start_side": side,
+    })
+}
+
+/// The setup in progress, or the last completed one while no setup is running
+fn current_match(data: &TypeMap) -> Value {
+    let state = &data.get::<BotState>().unwrap().state;
+    let in_setup = !matches!(state, State::Queue | State::Ready);
+    let setup_match;
+    let current_match = if !in_setup {
+        match data.get::<LastMatch>().unwrap() {
+            Some(last_match) => last_match,
+            None => return json!({ "state": state.to_string(), "match": null }),
+        }
+    } else {
+        // the match record only gets the captains once the setup is ready
+        let mut current_match = data.get::<CurrentMatch>().unwrap().clone();
+        let (captain_a, captain_b) = setup_captains(data);
+        current_match.draft.captain_a = captain_a;
+        current_match.draft.captain_b = captain_b;
+        setup_match = current_match;
+        &setup_match
+    };
+    let mut current_match = match_json(data, current_match);
+    current_match["in_setup"] = json!(in_setup);
+    current_match["active"] = json!(!in_setup && data.get::<ActiveMatch>().unwrap().is_some());
+    json!({
+        "state": state.to_string(),
+        "match": current_match,
+    })
+}
+
+/// Map, teams, Riot IDs, starting sides, timestamps and the reported score of a match
+pub(crate) fn match_json(data: &TypeMap, current_match: &Match) -> Value {
+    let draft = &current_match.draft;
+    let (team_a_side, team_b_side) = if draft.team_b_start_side.is_empty() {
+        (None, None)
+    } else {
+        let (team_a_side, team_b_side) = start_sides(draft, data.get::<Config>().unwrap());
+        (Some(team_a_side), Some(team_b_side))
+    };
+    json!({
+        "map": draft.map,
+        "team_a": team(data, &draft.captain_a, &draft.team_a, &draft.coach_a, team_a_side),
+        "team_b": team(data, &draft.captain_b, &draft.team_b, &draft.coach_b, team_b_side),
+        "started": timestamp(current_match.started),
+        "ready": current_match.ready.and_then(timestamp),
+        "ended": current_match.result.as_ref().and_then(|result| timestamp(result.ended)),
+        "score": current_match.result.as_ref().and_then(|result| result.score)
+            .map(|(team_a, team_b)| json!({ "team_a": team_a, "team_b": team_b })),
+    })
+}
+
+// matches loaded from before the start time was kept have 0
+fn timestamp(time: i64) -> Option<String> {
+    Local.timestamp_opt(time, 0).single().filter(|_| time > 0).map(|time| time.to_rfc3339())
+}
+
+fn history(data: &TypeMap, limit: usize) -> Value {
+    let matches: Vec<Value> = data.get::<MatchHistory>().unwrap()
+        .iter()
+        .rev()
+        .take(limit)
+        .filter(|record| record.result.is_some())
+        .map(|record| {
+            let result = record.result.as_ref().unwrap();
+            let mut json = match_json(data, record);
+            json["team_a"]["name"] = json!(result.team_a_name);
+            json["team_b"]["name"] = json!(result.team_b_name);
+            json
+        })
+        .collect();
+    json!({ "matches": matches })
+}
+
+/// 200 while every shard is connected to the gateway, 503 otherwise
+async fn healthz(shared: &Shared) -> Response<Body> {
+    let runners = shared.shard_manager.lock().await.runners.clone();
+    let runners = runners.lock().await;
+    let connected = !runners.is_empty() && runners.values().all(|runner| runner.stage == ConnectionStage::Connected);
+    let shards: Vec<Value> = runners
+        .iter()
+        .map(|(id, runner)| json!({
+            "id": id.0,
+            "stage": runner.stage.to_string(),
+            "latency_ms": runner.latency.map(|latency| latency.as_millis() as u64),
+        }))
+        .collect();
+    let last_event = match LAST_EVENT.load(Ordering::Relaxed) {
+        0 => None,
+        timestamp => Local.timestamp_opt(timestamp, 0).single(),
+    };
+    let body = json!({
+        "status": if connected { "ok" } else { "disconnected" },
+        "shards": shards,
+        "last_event": last_event.map(|time| time.to_rfc3339()),
+        "last_event_seconds_ago": last_event.map(|time| Local::now().signed_duration_since(time).num_seconds()),
+    });
+    let status = if connected { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    json_response(status, body)
+}
+
+fn json_response(status: StatusCode, body: Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+use chrono::Local;
+use serde_json::{json, Value};
+
+use crate::Config;
+
+/// Posts `{"event", "timestamp", "data"}` to every `webhook_urls` entry in the background
+pub(crate) fn send(config: &Config, event: &str, data: Value) {
+    let urls: Vec<String> = config.webhook_urls.iter().flatten().filter(|url| valid_url(url)).cloned().collect();
+    if urls.is_empty() {
+        return;
+    }
+    let payload = json!({
+        "event": event,
+        "timestamp": Local::now().to_rfc3339(),
+        "data": data,
+    });
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        for url in urls {
+            match client.post(&url).json(&payload).timeout(Duration::from_secs(10)).send().await {
+                Ok(response) if !response.status().is_success() => eprintln!("Webhook {} responded with {}", url, response.status()),
+                Err(why) => eprintln!("Cannot send webhook to {}: {:?}", url, why),
+                _ => {}
+            }
+        }
+    });
+}
+
+/// Whether a `webhook_urls` entry can be posted to, other entries are skipped
+pub(crate) fn valid_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}